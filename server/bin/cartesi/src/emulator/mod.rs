@@ -0,0 +1,48 @@
+mod rollup_server;
+
+use crate::rollup_io::read_inputs;
+use atb::logging::init_logger;
+use clap::Parser;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+#[derive(Debug, Parser)]
+pub struct EmulateOpts {
+    /// JSONL file of `RollupResponse` or `AdvanceRequest` records, read from stdin when omitted
+    #[clap(short, long)]
+    input: Option<PathBuf>,
+
+    /// Address to serve the rollup http server api on, `mono` connects to 127.0.0.1:5004 by default
+    #[clap(short, long, default_value = "127.0.0.1:5004")]
+    listen: SocketAddr,
+
+    /// msg_sender used for advance inputs recorded without metadata
+    #[clap(long, default_value = "0x70997970c51812dc3a010c7d01b50e0d17dc79c8")]
+    msg_sender: String,
+}
+
+pub fn run(opts: EmulateOpts) {
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("tokio runtime needed to continue. qed");
+
+    rt.block_on(async {
+        init_logger("warn, cartesi=info", true);
+
+        let inputs = match read_inputs(opts.input.as_deref()) {
+            Ok(inputs) => inputs,
+            Err(e) => {
+                log::error!("Failed to read inputs: {}", e);
+                return;
+            }
+        };
+
+        log::info!(
+            "Emulating rollup http server on {} with {} inputs",
+            opts.listen,
+            inputs.len()
+        );
+        rollup_server::serve(opts.listen, inputs, opts.msg_sender).await;
+    });
+}
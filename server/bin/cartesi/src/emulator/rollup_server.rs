@@ -0,0 +1,272 @@
+use crate::rollup_io::{decode_notice, decode_report, fill_metadata};
+use atb_types::Utc;
+use domain::cartesi::{
+    Exception, IndexResponse, Notice, Report, RequestType, RollupResponse, Voucher,
+};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{header as HyperHeader, Body, Method, Request, Response, Server, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+//#NOTE: the real rollup http server holds /finish for a while before answering 202, so the dapp doesn't spin
+const IDLE_FINISH_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Deserialize)]
+struct FinishRequest {
+    status: String,
+}
+
+struct EmulatorState {
+    pending: VecDeque<RollupResponse>,
+    default_msg_sender: String,
+    next_input_index: u64,
+    current: Option<String>,
+    notice_count: u64,
+    report_count: u64,
+    voucher_count: u64,
+}
+
+impl EmulatorState {
+    fn new(inputs: Vec<RollupResponse>, default_msg_sender: String) -> Self {
+        EmulatorState {
+            pending: inputs.into(),
+            default_msg_sender,
+            next_input_index: 0,
+            current: None,
+            notice_count: 0,
+            report_count: 0,
+            voucher_count: 0,
+        }
+    }
+
+    fn label(&self) -> &str {
+        self.current.as_deref().unwrap_or("-")
+    }
+
+    /// Close the current request with `status` and hand out the next pending one
+    fn finish(&mut self, status: &str) -> Option<RollupResponse> {
+        if let Some(current) = self.current.take() {
+            println!("[{}] finished: {}", current, status);
+        }
+
+        //#NOTE: like the rollups http server, outputs are numbered per input
+        self.notice_count = 0;
+        self.report_count = 0;
+        self.voucher_count = 0;
+
+        let mut rollup = self.pending.pop_front()?;
+
+        match rollup.request_type.parse::<RequestType>() {
+            Ok(RequestType::AdvanceState) => {
+                fill_metadata(
+                    &mut rollup,
+                    self.next_input_index,
+                    &self.default_msg_sender,
+                    Utc::now().timestamp() as u64,
+                );
+                self.current = Some(format!("input {}", self.next_input_index));
+                self.next_input_index += 1;
+            }
+            _ => {
+                self.current = Some(String::from("inspect"));
+            }
+        }
+
+        println!(
+            "[{}] {}",
+            self.label(),
+            serde_json::to_string(&rollup).unwrap()
+        );
+        Some(rollup)
+    }
+
+    fn notice(&mut self, notice: Notice) -> IndexResponse {
+        let index = self.notice_count;
+        self.notice_count += 1;
+
+        match decode_notice(&notice.payload) {
+            Ok(decoded) => println!(
+                "[{}] notice #{}:\n{}",
+                self.label(),
+                index,
+                serde_json::to_string_pretty(&decoded).unwrap()
+            ),
+            Err(e) => println!(
+                "[{}] notice #{} (undecodable: {}): {}",
+                self.label(),
+                index,
+                e,
+                notice.payload
+            ),
+        }
+
+        IndexResponse { index }
+    }
+
+    fn report(&mut self, report: Report) {
+        let index = self.report_count;
+        self.report_count += 1;
+
+        match decode_report(&report.payload) {
+            Ok(decoded) => println!(
+                "[{}] report #{}:\n{}",
+                self.label(),
+                index,
+                serde_json::to_string_pretty(&decoded).unwrap()
+            ),
+            Err(e) => println!(
+                "[{}] report #{} (undecodable: {}): {}",
+                self.label(),
+                index,
+                e,
+                report.payload
+            ),
+        }
+    }
+
+    fn voucher(&mut self, voucher: Voucher) -> IndexResponse {
+        let index = self.voucher_count;
+        self.voucher_count += 1;
+
+        println!(
+            "[{}] voucher #{} to {}: {}",
+            self.label(),
+            index,
+            voucher.destination,
+            voucher.payload
+        );
+
+        IndexResponse { index }
+    }
+
+    fn exception(&mut self, exception: Exception) {
+        let readable = decode_report(&exception.payload)
+            .map(|v| v.to_string())
+            .unwrap_or(exception.payload);
+        println!("[{}] exception: {}", self.label(), readable);
+    }
+}
+
+fn status_response(status: StatusCode) -> Response<Body> {
+    let mut resp = Response::new(Body::empty());
+    *resp.status_mut() = status;
+    resp
+}
+
+fn json_response<T: Serialize>(status: StatusCode, body: &T) -> Response<Body> {
+    let mut resp = Response::new(Body::from(serde_json::to_string(body).unwrap()));
+    *resp.status_mut() = status;
+    resp.headers_mut().insert(
+        HyperHeader::CONTENT_TYPE,
+        HyperHeader::HeaderValue::from_static("application/json"),
+    );
+    resp
+}
+
+async fn finish(state: &Mutex<EmulatorState>, bz: &[u8]) -> Response<Body> {
+    let finish = match serde_json::from_slice::<FinishRequest>(bz) {
+        Ok(finish) => finish,
+        Err(_) => return status_response(StatusCode::BAD_REQUEST),
+    };
+
+    let next = state.lock().unwrap().finish(&finish.status);
+    match next {
+        Some(rollup) => json_response(StatusCode::OK, &rollup),
+        None => {
+            tokio::time::sleep(IDLE_FINISH_DELAY).await;
+            status_response(StatusCode::ACCEPTED)
+        }
+    }
+}
+
+fn notice(state: &Mutex<EmulatorState>, bz: &[u8]) -> Response<Body> {
+    match serde_json::from_slice::<Notice>(bz) {
+        Ok(notice) => {
+            let index_response = state.lock().unwrap().notice(notice);
+            json_response(StatusCode::OK, &index_response)
+        }
+        Err(_) => status_response(StatusCode::BAD_REQUEST),
+    }
+}
+
+fn report(state: &Mutex<EmulatorState>, bz: &[u8]) -> Response<Body> {
+    match serde_json::from_slice::<Report>(bz) {
+        Ok(report) => {
+            state.lock().unwrap().report(report);
+            status_response(StatusCode::OK)
+        }
+        Err(_) => status_response(StatusCode::BAD_REQUEST),
+    }
+}
+
+fn voucher(state: &Mutex<EmulatorState>, bz: &[u8]) -> Response<Body> {
+    match serde_json::from_slice::<Voucher>(bz) {
+        Ok(voucher) => {
+            let index_response = state.lock().unwrap().voucher(voucher);
+            json_response(StatusCode::OK, &index_response)
+        }
+        Err(_) => status_response(StatusCode::BAD_REQUEST),
+    }
+}
+
+fn exception(state: &Mutex<EmulatorState>, bz: &[u8]) -> Response<Body> {
+    match serde_json::from_slice::<Exception>(bz) {
+        Ok(exception) => {
+            state.lock().unwrap().exception(exception);
+            status_response(StatusCode::OK)
+        }
+        Err(_) => status_response(StatusCode::BAD_REQUEST),
+    }
+}
+
+async fn handle(
+    state: Arc<Mutex<EmulatorState>>,
+    req: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+    if req.method() != Method::POST {
+        return Ok(status_response(StatusCode::METHOD_NOT_ALLOWED));
+    }
+
+    let path = req.uri().path().to_owned();
+    let bz = match hyper::body::to_bytes(req.into_body()).await {
+        Ok(bz) => bz,
+        Err(e) => {
+            log::debug!("Failed to read request body: {}", e);
+            return Ok(status_response(StatusCode::BAD_REQUEST));
+        }
+    };
+
+    let resp = match path.as_str() {
+        "/finish" => finish(&state, &bz).await,
+        "/notice" => notice(&state, &bz),
+        "/report" => report(&state, &bz),
+        "/voucher" => voucher(&state, &bz),
+        "/exception" => exception(&state, &bz),
+        _ => status_response(StatusCode::NOT_FOUND),
+    };
+
+    Ok(resp)
+}
+
+pub async fn serve(listen: SocketAddr, inputs: Vec<RollupResponse>, default_msg_sender: String) {
+    let state = Arc::new(Mutex::new(EmulatorState::new(inputs, default_msg_sender)));
+
+    let make_svc = make_service_fn(move |_conn| {
+        let state = state.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |req| handle(state.clone(), req))) }
+    });
+
+    let server = Server::bind(&listen)
+        .serve(make_svc)
+        .with_graceful_shutdown(async {
+            tokio::signal::ctrl_c().await.ok();
+        });
+
+    if let Err(e) = server.await {
+        log::error!("Rollup emulator stopped: {}", e);
+    }
+}
@@ -1,4 +1,6 @@
+mod emulator;
 mod mono;
+mod rollup_io;
 use clap::Parser;
 
 #[derive(Debug, Parser)]
@@ -7,6 +9,14 @@ enum Commands {
     #[clap(name = "mono")]
     Mono,
 
+    /// Serve a local stand-in of the rollup http server, feeding inputs from a file or stdin
+    #[clap(name = "emulate")]
+    Emulate(emulator::EmulateOpts),
+
+    /// Replay recorded inputs and write the outputs they produce as a JSONL transcript
+    #[clap(name = "replay")]
+    Replay(mono::ReplayOpts),
+
     #[clap(name = "version")]
     /// Prints binary build information
     Version,
@@ -25,6 +35,8 @@ fn main() {
     use Commands::*;
     match opts.command {
         Mono => mono::run(),
+        Emulate(emulate_opts) => emulator::run(emulate_opts),
+        Replay(replay_opts) => mono::replay(replay_opts),
         Version => {
             print_version();
         }
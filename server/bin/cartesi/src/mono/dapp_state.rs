@@ -0,0 +1,66 @@
+use super::balance_manager::BalanceManager;
+use super::ingame_wallet_manager::IngameWalletManager;
+use super::nft_inventory_manager::NftInventoryManager;
+use super::output_index_manager::OutputIndexManager;
+use domain::game_core::room_manager::RoomManager;
+use serde::{Deserialize, Serialize};
+
+/// Every manager the dapp keeps between inputs, written to snapshots as a whole
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DappState {
+    pub room_manager: RoomManager,
+    pub balance_manager: BalanceManager,
+    pub ingame_wallet_manager: IngameWalletManager,
+    pub nft_inventory_manager: NftInventoryManager,
+    pub output_index_manager: OutputIndexManager,
+}
+
+/// What `DappState::rollback` needs to undo a rejected input.
+///
+/// The balances and ledger are undone through the journal of the balance manager, the other managers are cloned:
+/// - the room manager clones its index maps only, the rooms themselves are shared behind `Arc` and replaced rather
+///   than mutated, so the game histories aren't copied
+/// - the in-game wallet manager holds one session key and two nonces per player
+/// - the NFT inventory is copied with the stats of every deposited character, it is bounded by the characters held
+///   in the dapp and cheap next to the JSON encoding every input already goes through
+///
+/// The output indices aren't part of it, they are only recorded once an input can't be rejected anymore.
+pub struct DappCheckpoint {
+    room_manager: RoomManager,
+    balance_manager: usize,
+    ingame_wallet_manager: IngameWalletManager,
+    nft_inventory_manager: NftInventoryManager,
+}
+
+impl DappState {
+    pub fn new() -> Self {
+        DappState {
+            room_manager: RoomManager::new(),
+            balance_manager: BalanceManager::new(),
+            ingame_wallet_manager: IngameWalletManager::new(),
+            nft_inventory_manager: NftInventoryManager::new(),
+            output_index_manager: OutputIndexManager::new(),
+        }
+    }
+
+    pub fn checkpoint(&self) -> DappCheckpoint {
+        DappCheckpoint {
+            room_manager: self.room_manager.clone(),
+            balance_manager: self.balance_manager.checkpoint(),
+            ingame_wallet_manager: self.ingame_wallet_manager.clone(),
+            nft_inventory_manager: self.nft_inventory_manager.clone(),
+        }
+    }
+
+    pub fn rollback(&mut self, checkpoint: DappCheckpoint) {
+        self.room_manager = checkpoint.room_manager;
+        self.balance_manager.rollback(checkpoint.balance_manager);
+        self.ingame_wallet_manager = checkpoint.ingame_wallet_manager;
+        self.nft_inventory_manager = checkpoint.nft_inventory_manager;
+    }
+
+    /// Must be called once an input is settled, a later rollback can't go back past it
+    pub fn commit(&mut self) {
+        self.balance_manager.commit();
+    }
+}
@@ -1,14 +1,185 @@
+use async_trait::async_trait;
 use base64::{engine::general_purpose, Engine as _};
 use domain::cartesi::{
-    DazzleNotice, FinishStatus, IndexResponse, Notice, NoticeType, Report, Voucher,
+    split_report_content, DazzleException, DazzleNotice, Exception, FinishStatus, IndexResponse,
+    Notice, NoticeType, Report, Voucher, MAX_REPORT_CONTENT_SIZE,
 };
 use domain::game_core::game::Room;
 use domain::game_core::{DazzleError, ServerError};
 use ethers_core::utils::hex;
+use hyper::client::HttpConnector;
 use hyper::{header as HyperHeader, Body, Client, Method, Request, Response};
 
+/// Indices the next notice and voucher of the current input get, notices and vouchers are numbered separately per input
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OutputIndices {
+    pub notice: u64,
+    pub voucher: u64,
+}
+
+/// Sink for every output an input can produce (notices, reports, vouchers and exceptions).
+///
+/// `HttpDispatcher` posts them to the rollup http server, while `OutputRecorder` keeps them in memory.
+/// Notices and vouchers return their index within the current input.
+#[async_trait]
+pub trait RollupOutputs: Send {
+    async fn add_notice(&mut self, notice: Notice) -> Result<u64, DazzleError>;
+    async fn add_report(&mut self, report: Report) -> Result<(), DazzleError>;
+    async fn add_voucher(&mut self, voucher: Voucher) -> Result<u64, DazzleError>;
+    async fn add_exception(&mut self, exception: Exception) -> Result<(), DazzleError>;
+    fn next_indices(&self) -> OutputIndices;
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpDispatcher {
+    url: String,
+    //#NOTE: one client for every call so connections to the rollup http server are kept alive
+    client: Client<HttpConnector>,
+    next_indices: OutputIndices,
+}
+
+impl HttpDispatcher {
+    pub fn new(url: &str) -> Self {
+        HttpDispatcher {
+            url: url.to_owned(),
+            client: Client::new(),
+            next_indices: Default::default(),
+        }
+    }
+
+    async fn post(&self, endpoint: &str, json: String) -> Result<Response<Body>, ServerError> {
+        let req = Request::builder()
+            .method(Method::POST)
+            .header(HyperHeader::CONTENT_TYPE, "application/json")
+            .uri(format!("{}/{}", self.url, endpoint))
+            .body(Body::from(json))
+            .map_err(|_| ServerError::FailedToBuildRequest)?;
+
+        self.client.request(req).await.map_err(|e| {
+            log::debug!("error while posting to /{}: {}", endpoint, e);
+            match endpoint {
+                "notice" => ServerError::FailedToSendNotice,
+                "exception" => ServerError::FailedToSendException,
+                _ => ServerError::FailedToSendReport,
+            }
+        })
+    }
+
+    pub async fn send_finish_request(&mut self, status: FinishStatus) -> Option<Response<Body>> {
+        log::debug!("Call to Http Dispatcher: Finishing");
+
+        //#NOTE: output indices start over with the next input
+        self.next_indices = Default::default();
+
+        let status_value = status.to_string();
+        log::debug!("status_value: {}", status_value);
+
+        let mut json_status = std::collections::HashMap::new();
+        json_status.insert("status", status_value);
+
+        match self
+            .post("finish", serde_json::to_string(&json_status).unwrap())
+            .await
+        {
+            Ok(resp) => Some(resp),
+            Err(e) => {
+                log::debug!("error while send_finish_request: {}", e);
+                None
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl RollupOutputs for HttpDispatcher {
+    async fn add_notice(&mut self, notice: Notice) -> Result<u64, DazzleError> {
+        let notice_json = serde_json::to_string(&notice).unwrap();
+        log::debug!("notice_json: {}", notice_json);
+
+        let notice_resp = self.post("notice", notice_json).await?;
+
+        let notice_status = notice_resp.status();
+        let bz = hyper::body::to_bytes(notice_resp)
+            .await
+            .map_err(|_| ServerError::FailedToHandleResponse)?;
+
+        let id_response = serde_json::from_slice::<IndexResponse>(&bz)
+            .map_err(|_| ServerError::FailedToHandleResponse)?;
+
+        log::debug!(
+            "Received notice status {} body {:?}",
+            notice_status,
+            &id_response
+        );
+
+        self.next_indices.notice = id_response.index + 1;
+        Ok(id_response.index)
+    }
+
+    async fn add_report(&mut self, report: Report) -> Result<(), DazzleError> {
+        let report_json = serde_json::to_string(&report).unwrap();
+
+        let report_resp = self.post("report", report_json).await?;
+
+        let report_status = report_resp.status();
+        let bz = hyper::body::to_bytes(report_resp)
+            .await
+            .map_err(|_| ServerError::FailedToHandleResponse)?
+            .to_vec();
+
+        let resp_string =
+            std::str::from_utf8(&bz).map_err(|_| ServerError::FailedToHandleResponse)?;
+
+        log::debug!(
+            "Received report status {} body {:?}",
+            report_status,
+            resp_string
+        );
+
+        Ok(())
+    }
+
+    async fn add_voucher(&mut self, voucher: Voucher) -> Result<u64, DazzleError> {
+        let voucher_json = serde_json::to_string(&voucher).unwrap();
+        log::debug!("voucher_json: {}", voucher_json);
+
+        let voucher_resp = self.post("voucher", voucher_json).await?;
+
+        let voucher_status = voucher_resp.status();
+        let bz = hyper::body::to_bytes(voucher_resp)
+            .await
+            .map_err(|_| ServerError::FailedToHandleResponse)?;
+
+        let id_response = serde_json::from_slice::<IndexResponse>(&bz)
+            .map_err(|_| ServerError::FailedToHandleResponse)?;
+
+        log::debug!(
+            "Received voucher status {} body {:?}",
+            voucher_status,
+            &id_response
+        );
+
+        self.next_indices.voucher = id_response.index + 1;
+        Ok(id_response.index)
+    }
+
+    async fn add_exception(&mut self, exception: Exception) -> Result<(), DazzleError> {
+        let exception_json = serde_json::to_string(&exception).unwrap();
+
+        let exception_resp = self.post("exception", exception_json).await?;
+
+        log::debug!("Received exception status {}", exception_resp.status());
+
+        Ok(())
+    }
+
+    fn next_indices(&self) -> OutputIndices {
+        self.next_indices
+    }
+}
+
 pub async fn send_room_snapshot_notice(
-    http_dispatcher_url: &str,
+    outputs: &mut dyn RollupOutputs,
     user: &str,
     room: &Room,
     balance: Option<String>,
@@ -16,25 +187,17 @@ pub async fn send_room_snapshot_notice(
     let snapshot_room = room.snapshot();
     let room_notice = serde_json::to_string(&snapshot_room).unwrap();
 
-    send_notice(
-        http_dispatcher_url,
-        NoticeType::Room,
-        &room_notice,
-        user,
-        balance,
-    )
-    .await
+    send_notice(outputs, NoticeType::Room, &room_notice, user, balance).await
 }
 
 pub async fn send_notice(
-    http_dispatcher_url: &str,
+    outputs: &mut dyn RollupOutputs,
     notice_type: NoticeType,
     payload: &str,
     user: &str,
     balance: Option<String>,
 ) -> Result<FinishStatus, DazzleError> {
     log::debug!("Call to Http Dispatcher: Adding Notice");
-    let client = Client::new();
 
     let base64_payload = general_purpose::STANDARD.encode(payload);
     log::debug!("Base64-encoded payload: {}", base64_payload);
@@ -53,79 +216,16 @@ pub async fn send_notice(
         payload: format!("0x{}", hexed_inner_notice),
     };
 
-    let notice_json = serde_json::to_string(&notice).unwrap();
-    log::debug!("notice_json: {}", notice_json);
-
-    let notice_req = Request::builder()
-        .method(Method::POST)
-        .header(HyperHeader::CONTENT_TYPE, "application/json")
-        .uri(format!("{}/notice", http_dispatcher_url))
-        .body(Body::from(notice_json))
-        .map_err(|_| ServerError::FailedToBuildRequest)?;
-
-    let notice_resp = client
-        .request(notice_req)
-        .await
-        .map_err(|_| ServerError::FailedToSendNotice)?;
-
-    let notice_status = notice_resp.status();
-    let bz = hyper::body::to_bytes(notice_resp)
-        .await
-        .map_err(|_| ServerError::FailedToHandleResponse)?;
-
-    let id_response = serde_json::from_slice::<IndexResponse>(&bz)
-        .map_err(|_| ServerError::FailedToHandleResponse)?;
-
-    log::debug!(
-        "Received notice status {} body {:?}",
-        notice_status,
-        &id_response
-    );
+    outputs.add_notice(notice).await?;
 
     Ok(FinishStatus::Accept)
 }
 
-pub async fn send_finish_request(
-    http_dispatcher_url: &str,
-    status: FinishStatus,
-) -> Option<Response<Body>> {
-    log::debug!("Call to Http Dispatcher: Finishing");
-    let client = Client::new();
-
-    let status_value = status.to_string();
-    log::debug!("status_value: {}", status_value);
-
-    let mut json_status = std::collections::HashMap::new();
-    json_status.insert("status", status_value);
-
-    let finish_req = match Request::builder()
-        .method(Method::POST)
-        .header(HyperHeader::CONTENT_TYPE, "application/json")
-        .uri(format!("{}/finish", http_dispatcher_url))
-        .body(Body::from(serde_json::to_string(&json_status).unwrap()))
-    {
-        Ok(req) => req,
-        Err(e) => {
-            log::debug!("error while generating send_finish_request body: {}", e);
-            return None;
-        }
-    };
-
-    match client.request(finish_req).await {
-        Ok(resp) => Some(resp),
-        Err(e) => {
-            log::debug!("error while send_finish_request: {}", e);
-            return None;
-        }
-    }
-}
-
 pub async fn send_report(
-    http_dispatcher_url: &str,
+    outputs: &mut dyn RollupOutputs,
     payload: &str,
 ) -> Result<FinishStatus, DazzleError> {
     log::debug!("Call to Http Dispatcher: Adding Report");
-    let client = Client::new();
 
     let hexed_payload = hex::encode(payload);
 
@@ -133,45 +233,37 @@ pub async fn send_report(
         payload: format!("0x{}", hexed_payload),
     };
 
-    let report_json = serde_json::to_string(&report).unwrap();
-
-    let report_req = Request::builder()
-        .method(Method::POST)
-        .header(HyperHeader::CONTENT_TYPE, "application/json")
-        .uri(format!("{}/report", http_dispatcher_url))
-        .body(Body::from(report_json))
-        .map_err(|_| ServerError::FailedToBuildRequest)?;
+    outputs.add_report(report).await?;
 
-    let report_resp = client
-        .request(report_req)
-        .await
-        .map_err(|_| ServerError::FailedToSendReport)?;
+    Ok(FinishStatus::Accept)
+}
 
-    let report_status = report_resp.status();
-    let bz = hyper::body::to_bytes(report_resp)
-        .await
-        .map_err(|_| ServerError::FailedToHandleResponse)?
-        .to_vec();
+/// Like `send_report`, but content above `MAX_REPORT_CONTENT_SIZE` goes out as numbered `ReportChunk`s
+pub async fn send_chunked_report(
+    outputs: &mut dyn RollupOutputs,
+    payload: &str,
+) -> Result<FinishStatus, DazzleError> {
+    if payload.len() <= MAX_REPORT_CONTENT_SIZE {
+        return send_report(outputs, payload).await;
+    }
 
-    let resp_string = std::str::from_utf8(&bz).map_err(|_| ServerError::FailedToHandleResponse)?;
+    let chunks = split_report_content(payload, MAX_REPORT_CONTENT_SIZE);
+    log::debug!("Splitting report into {} chunks", chunks.len());
 
-    log::debug!(
-        "Received report status {} body {:?}",
-        report_status,
-        resp_string
-    );
+    for chunk in chunks {
+        send_report(outputs, &serde_json::to_string(&chunk).unwrap()).await?;
+    }
 
     Ok(FinishStatus::Accept)
 }
 
-#[allow(dead_code)]
+/// Returns the index of the voucher within the current input
 pub async fn send_voucher(
-    http_dispatcher_url: &str,
+    outputs: &mut dyn RollupOutputs,
     dapp_address: &str,
     payload: &[u8],
-) -> Result<FinishStatus, DazzleError> {
+) -> Result<u64, DazzleError> {
     log::debug!("Call to Http Dispatcher: Adding Voucher");
-    let client = Client::new();
     let hexed_payload = hex::encode(payload);
     log::debug!("Hex-encoded payload: {}", hexed_payload);
 
@@ -180,34 +272,19 @@ pub async fn send_voucher(
         payload: format!("0x{}", hexed_payload),
     };
 
-    let voucher_json = serde_json::to_string(&voucher).unwrap();
-    log::debug!("voucher_json: {}", voucher_json);
-
-    let voucher_req = Request::builder()
-        .method(Method::POST)
-        .header(HyperHeader::CONTENT_TYPE, "application/json")
-        .uri(format!("{}/voucher", http_dispatcher_url))
-        .body(Body::from(voucher_json))
-        .map_err(|_| ServerError::FailedToBuildRequest)?;
-
-    let voucher_resp = client
-        .request(voucher_req)
-        .await
-        .map_err(|_| ServerError::FailedToSendReport)?;
-
-    let voucher_status = voucher_resp.status();
-    let bz = hyper::body::to_bytes(voucher_resp)
-        .await
-        .map_err(|_| ServerError::FailedToHandleResponse)?
-        .to_vec();
+    outputs.add_voucher(voucher).await
+}
 
-    let resp_string = std::str::from_utf8(&bz).map_err(|_| ServerError::FailedToHandleResponse)?;
+pub async fn send_exception(
+    outputs: &mut dyn RollupOutputs,
+    exception: &DazzleException,
+) -> Result<(), DazzleError> {
+    log::debug!("Call to Http Dispatcher: Throwing Exception");
 
-    log::debug!(
-        "Received voucher status {} body {:?}",
-        voucher_status,
-        resp_string
-    );
+    let exception_json = serde_json::to_string(exception).unwrap();
+    let exception = Exception {
+        payload: format!("0x{}", hex::encode(exception_json)),
+    };
 
-    Ok(FinishStatus::Accept)
+    outputs.add_exception(exception).await
 }
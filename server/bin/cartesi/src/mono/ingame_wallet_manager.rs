@@ -1,10 +1,22 @@
+use domain::cartesi::SessionScope;
+use domain::game_core::ServerError;
 use ethers_core::types::Address;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-#[derive(Debug, Clone, Serialize)]
+/// An in-game wallet acting for a metamask wallet within `scope`, until the input timestamp reaches `expires_at`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionKey {
+    pub ingame_wallet: Address,
+    pub scope: SessionScope,
+    pub expires_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IngameWalletManager {
-    wallet_map: HashMap<Address, Address>,
+    wallet_map: HashMap<Address, SessionKey>, // metamask wallet -> its session key
+    attach_nonces: HashMap<Address, u64>,     // in-game wallet -> last nonce it was attached with
+    relay_nonces: HashMap<Address, u64>,      // player -> last nonce of its relayed actions
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -15,43 +27,186 @@ pub struct IngameWalletManagerState {
 impl IngameWalletManager {
     pub fn new() -> Self {
         IngameWalletManager {
-            wallet_map: HashMap::<Address, Address>::new(),
+            wallet_map: HashMap::<Address, SessionKey>::new(),
+            attach_nonces: HashMap::<Address, u64>::new(),
+            relay_nonces: HashMap::<Address, u64>::new(),
         }
     }
 
-    // pub fn get_ingame_wallet(&self, metamask_wallet_address: &Address) -> Option<&Address> {
-    //     self.wallet_map.get(metamask_wallet_address)
-    // }
+    pub fn get_ingame_wallet(&self, metamask_wallet_address: &Address) -> Option<&Address> {
+        self.wallet_map
+            .get(metamask_wallet_address)
+            .map(|session| &session.ingame_wallet)
+    }
+
+    pub fn get_session(&self, metamask_wallet_address: &Address) -> Option<&SessionKey> {
+        self.wallet_map.get(metamask_wallet_address)
+    }
+
+    /// The metamask wallet an in-game wallet acts for, with its session
+    pub fn get_session_by_key(
+        &self,
+        ingame_wallet_address: &Address,
+    ) -> Option<(&Address, &SessionKey)> {
+        self.wallet_map
+            .iter()
+            .find(|(_, session)| session.ingame_wallet == *ingame_wallet_address)
+    }
 
-    //#TODO: When to delete mapping?
+    pub fn get_metamask_wallet(&self, ingame_wallet_address: &Address) -> Option<&Address> {
+        self.get_session_by_key(ingame_wallet_address)
+            .map(|(metamask_wallet, _)| metamask_wallet)
+    }
+
+    //#NOTE: a mapping is deleted when its session key expires, is revoked or is rotated
+    /// Must be called before handling an input, session keys expired at its timestamp stop acting right away
+    pub fn expire_sessions(&mut self, timestamp: u64) {
+        self.wallet_map
+            .retain(|_, session| session.expires_at > timestamp);
+    }
 
     pub fn get_current_state(&self) -> IngameWalletManagerState {
         IngameWalletManagerState {
             wallet_map: self
                 .wallet_map
                 .iter()
-                .map(|(metamask_wallet, ingame_wallet)| {
+                .map(|(metamask_wallet, session)| {
                     (
                         format!("{:#x}", metamask_wallet),
-                        format!("{:#x}", ingame_wallet),
+                        format!("{:#x}", session.ingame_wallet),
                     )
                 })
                 .collect(),
         }
     }
 
-    pub fn set_ingame_wallet(
+    pub fn set_ingame_wallet(&mut self, metamask_wallet_address: &Address, session: SessionKey) {
+        self.wallet_map.insert(*metamask_wallet_address, session);
+    }
+
+    /// Attach an in-game wallet whose signature was verified, it may belong to one metamask wallet only
+    /// and a nonce it was already attached with can't be replayed
+    pub fn attach_ingame_wallet(
         &mut self,
         metamask_wallet_address: &Address,
-        ingame_wallet_address: Address,
-    ) {
-        self.wallet_map
-            .insert(*metamask_wallet_address, ingame_wallet_address);
+        session: SessionKey,
+        nonce: u64,
+    ) -> Result<(), ServerError> {
+        let ingame_wallet_address = session.ingame_wallet;
+        if self.is_ingame_wallet_attached(&ingame_wallet_address) {
+            return Err(ServerError::IngameWalletAlreadyAttached(format!(
+                "{:#x}",
+                ingame_wallet_address
+            )));
+        }
+        use_nonce(&mut self.attach_nonces, &ingame_wallet_address, nonce)?;
+        self.set_ingame_wallet(metamask_wallet_address, session);
+        Ok(())
+    }
+
+    /// Consume the nonce of an action relayed for `player`, it must be above the last one
+    pub fn use_relay_nonce(&mut self, player: &Address, nonce: u64) -> Result<(), ServerError> {
+        use_nonce(&mut self.relay_nonces, player, nonce)
+    }
+
+    pub fn revoke_session_key(&mut self, metamask_wallet_address: &Address) -> Option<SessionKey> {
+        self.wallet_map.remove(metamask_wallet_address)
     }
 
     pub fn is_ingame_wallet_attached(&self, ingame_wallet_address: &Address) -> bool {
-        self.wallet_map
-            .values()
-            .any(|&addr| addr.eq(ingame_wallet_address))
+        self.get_session_by_key(ingame_wallet_address).is_some()
+    }
+}
+
+fn use_nonce(
+    nonces: &mut HashMap<Address, u64>,
+    signer: &Address,
+    nonce: u64,
+) -> Result<(), ServerError> {
+    if let Some(last_nonce) = nonces.get(signer) {
+        if nonce <= *last_nonce {
+            return Err(ServerError::NonceAlreadyUsed(nonce));
+        }
+    }
+
+    nonces.insert(*signer, nonce);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    const METAMASK_WALLET: &str = "0x2af645839ea4ca82452afd195e210420e7cc1f90";
+    const OTHER_METAMASK_WALLET: &str = "0x3c44cdddb6a900fa2b585dd299e03d12fa4293bc";
+    const INGAME_WALLET: &str = "0x70997970c51812dc3a010c7d01b50e0d17dc79c8";
+
+    fn session() -> SessionKey {
+        SessionKey {
+            ingame_wallet: Address::from_str(INGAME_WALLET).unwrap(),
+            scope: SessionScope::Gameplay,
+            expires_at: 1_700_000_000,
+        }
+    }
+
+    #[test]
+    fn attach_nonce_must_increase() {
+        let metamask_wallet = Address::from_str(METAMASK_WALLET).unwrap();
+        let mut manager = IngameWalletManager::new();
+        manager
+            .attach_ingame_wallet(&metamask_wallet, session(), 3)
+            .unwrap();
+        manager.revoke_session_key(&metamask_wallet);
+
+        for nonce in [0, 3] {
+            assert!(matches!(
+                manager.attach_ingame_wallet(&metamask_wallet, session(), nonce),
+                Err(ServerError::NonceAlreadyUsed(n)) if n == nonce
+            ));
+        }
+        manager
+            .attach_ingame_wallet(&metamask_wallet, session(), 4)
+            .unwrap();
+    }
+
+    #[test]
+    fn attached_wallet_keeps_its_nonce() {
+        let mut manager = IngameWalletManager::new();
+        manager
+            .attach_ingame_wallet(&Address::from_str(METAMASK_WALLET).unwrap(), session(), 0)
+            .unwrap();
+
+        //#NOTE: refused before its nonce is looked at, so the nonce stays usable
+        let other_metamask_wallet = Address::from_str(OTHER_METAMASK_WALLET).unwrap();
+        assert!(matches!(
+            manager.attach_ingame_wallet(&other_metamask_wallet, session(), 1),
+            Err(ServerError::IngameWalletAlreadyAttached(_))
+        ));
+        manager.revoke_session_key(&Address::from_str(METAMASK_WALLET).unwrap());
+        manager
+            .attach_ingame_wallet(&other_metamask_wallet, session(), 1)
+            .unwrap();
+    }
+
+    #[test]
+    fn relay_nonces_are_kept_per_player() {
+        let player = Address::from_str(INGAME_WALLET).unwrap();
+        let other_player = Address::from_str(METAMASK_WALLET).unwrap();
+        let mut manager = IngameWalletManager::new();
+
+        manager.use_relay_nonce(&player, 1).unwrap();
+        assert!(matches!(
+            manager.use_relay_nonce(&player, 1),
+            Err(ServerError::NonceAlreadyUsed(1))
+        ));
+        manager.use_relay_nonce(&other_player, 1).unwrap();
+        manager.use_relay_nonce(&player, 5).unwrap();
+        assert!(manager.use_relay_nonce(&player, 2).is_err());
+
+        //#NOTE: relay nonces and attach nonces don't share a counter
+        manager
+            .attach_ingame_wallet(&other_player, session(), 0)
+            .unwrap();
     }
 }
@@ -1,9 +1,17 @@
 mod balance_manager;
+mod dapp_state;
 mod http_dispatcher;
 mod ingame_wallet_manager;
+mod nft_inventory_manager;
+mod output_index_manager;
+mod output_recorder;
+mod replay;
+mod serde_util;
 mod service;
+mod snapshot;
 use atb::logging::init_logger;
 
+pub use replay::{replay, ReplayOpts};
 use service::rollup;
 
 pub fn run() {
@@ -0,0 +1,135 @@
+use super::serde_util::nested_map_entries;
+use atb_types::prelude::uuid::Uuid;
+use domain::cartesi::NftCharacter;
+use domain::game_core::character::CharacterV2;
+use domain::game_core::config::GameplayConfigManager;
+use domain::game_core::probability_mod::RandomNumHolder;
+use domain::game_core::ServerError;
+use ethers_core::types::{Address, U256};
+use ethers_core::utils::keccak256;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+//#NOTE: tier the stats of deposited NFT characters are rolled at
+pub const NFT_CHARACTER_TIER_LV: usize = 1;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NftInventoryManagerState {
+    pub inventory: HashMap<String, Vec<NftCharacter>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NftInventoryManager {
+    #[serde(with = "nested_map_entries")]
+    inventory: HashMap<Address, HashMap<(Address, U256), Option<CharacterV2>>>, // owner -> (collection, token id) -> stats
+}
+
+/// The stats of an NFT character, rolled from a seed of its collection and token id.
+///
+/// Every deposit of a token gets the same character, whatever the depositor sends along with it.
+pub fn roll_nft_character(collection: &Address, token_id: U256) -> CharacterV2 {
+    let mut bz = collection.as_bytes().to_vec();
+    let mut token_id_bz = [0u8; 32];
+    token_id.to_big_endian(&mut token_id_bz);
+    bz.extend_from_slice(&token_id_bz);
+    let hash = keccak256(bz);
+
+    let seed = u64::from_be_bytes(hash[..8].try_into().unwrap());
+    let mut character = CharacterV2::roll_with(
+        NFT_CHARACTER_TIER_LV,
+        &GameplayConfigManager::new(),
+        &mut RandomNumHolder::seeded(seed),
+    );
+    character
+        .attribute
+        .set_id(Uuid::from_slice(&hash[16..]).unwrap());
+    character
+}
+
+impl NftInventoryManager {
+    pub fn new() -> Self {
+        NftInventoryManager {
+            inventory: HashMap::<Address, HashMap<(Address, U256), Option<CharacterV2>>>::new(),
+        }
+    }
+
+    pub fn deposit(
+        &mut self,
+        owner: &Address,
+        collection: &Address,
+        token_id: U256,
+        character: Option<CharacterV2>,
+    ) {
+        self.inventory
+            .entry(*owner)
+            .or_default()
+            .insert((*collection, token_id), character);
+    }
+
+    pub fn get_character(
+        &self,
+        owner: &Address,
+        collection: &Address,
+        token_id: U256,
+    ) -> Result<&CharacterV2, ServerError> {
+        self.inventory
+            .get(owner)
+            .and_then(|tokens| tokens.get(&(*collection, token_id)))
+            .ok_or_else(|| ServerError::RequestNotOwnedNFT(nft_label(collection, token_id)))?
+            .as_ref()
+            .ok_or_else(|| ServerError::NftWithoutCharacter(nft_label(collection, token_id)))
+    }
+
+    pub fn withdraw(
+        &mut self,
+        owner: &Address,
+        collection: &Address,
+        token_id: U256,
+    ) -> Result<Option<CharacterV2>, ServerError> {
+        let tokens = self
+            .inventory
+            .get_mut(owner)
+            .ok_or_else(|| ServerError::RequestNotOwnedNFT(nft_label(collection, token_id)))?;
+        let character = tokens
+            .remove(&(*collection, token_id))
+            .ok_or_else(|| ServerError::RequestNotOwnedNFT(nft_label(collection, token_id)))?;
+
+        if tokens.is_empty() {
+            self.inventory.remove(owner);
+        }
+        Ok(character)
+    }
+
+    pub fn get_inventory(&self, owner: &Address) -> Vec<NftCharacter> {
+        let mut characters: Vec<NftCharacter> = self
+            .inventory
+            .get(owner)
+            .into_iter()
+            .flatten()
+            .map(|((collection, token_id), character)| NftCharacter {
+                collection: format!("{:#x}", collection),
+                token_id: token_id.to_string(),
+                owner: format!("{:#x}", owner),
+                character: character.clone(),
+            })
+            .collect();
+
+        //#NOTE: HashMap order differs between runs, keep inspect results stable
+        characters.sort_by(|a, b| (&a.collection, &a.token_id).cmp(&(&b.collection, &b.token_id)));
+        characters
+    }
+
+    pub fn get_current_state(&self) -> NftInventoryManagerState {
+        NftInventoryManagerState {
+            inventory: self
+                .inventory
+                .keys()
+                .map(|owner| (format!("{:#x}", owner), self.get_inventory(owner)))
+                .collect(),
+        }
+    }
+}
+
+pub fn nft_label(collection: &Address, token_id: U256) -> String {
+    format!("{:#x}#{}", collection, token_id)
+}
@@ -0,0 +1,52 @@
+use domain::cartesi::{FinishStatus, InputOutputs};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+//#NOTE: only the outputs of the latest inputs are kept, older ones can still be looked up through the rollups graphql api
+pub const MAX_INDEXED_INPUTS: usize = 10_000;
+
+/// Index of every notice and voucher of the accepted inputs, so clients can locate an output on L1,
+/// and the inputs that were rejected, so a restored snapshot finishes the inputs replayed to it as they were
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutputIndexManager {
+    outputs: BTreeMap<u64, InputOutputs>, // input index -> outputs
+    //#NOTE: never trimmed, an input replayed after a restart may be any input the snapshot includes
+    #[serde(default)]
+    rejected_inputs: BTreeSet<u64>,
+}
+
+impl OutputIndexManager {
+    pub fn new() -> Self {
+        OutputIndexManager {
+            outputs: BTreeMap::<u64, InputOutputs>::new(),
+            rejected_inputs: BTreeSet::<u64>::new(),
+        }
+    }
+
+    pub fn record(&mut self, outputs: InputOutputs) {
+        if outputs.notices.is_empty() && outputs.vouchers.is_empty() {
+            return;
+        }
+        self.outputs.insert(outputs.input_index, outputs);
+
+        while self.outputs.len() > MAX_INDEXED_INPUTS {
+            self.outputs.pop_first();
+        }
+    }
+
+    pub fn get_outputs(&self, input_index: u64) -> Option<&InputOutputs> {
+        self.outputs.get(&input_index)
+    }
+
+    pub fn record_rejection(&mut self, input_index: u64) {
+        self.rejected_inputs.insert(input_index);
+    }
+
+    /// The status the input was finished with, for an input the state already includes
+    pub fn get_finish_status(&self, input_index: u64) -> FinishStatus {
+        match self.rejected_inputs.contains(&input_index) {
+            true => FinishStatus::Reject,
+            false => FinishStatus::Accept,
+        }
+    }
+}
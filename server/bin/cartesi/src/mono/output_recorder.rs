@@ -0,0 +1,151 @@
+use super::http_dispatcher::{OutputIndices, RollupOutputs};
+use async_trait::async_trait;
+use domain::cartesi::{
+    DazzleNotice, Exception, InputOutputs, Notice, NoticeOutput, Report, Voucher, VoucherOutput,
+};
+use domain::game_core::{DazzleError, ServerError};
+use ethers_core::utils::hex;
+
+/// In-memory `RollupOutputs`, keeps every output in the order it was emitted
+#[derive(Debug, Clone, Default)]
+pub struct OutputRecorder {
+    pub notices: Vec<Notice>,
+    pub reports: Vec<Report>,
+    pub vouchers: Vec<Voucher>,
+    pub exceptions: Vec<Exception>,
+    //#NOTE: indices of the first recorded notice and voucher, the ones handed out while recording are checked once forwarded
+    first_indices: OutputIndices,
+}
+
+impl OutputRecorder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Record outputs which will be forwarded to `outputs`, numbered after what `outputs` already holds
+    pub fn continuing(outputs: &dyn RollupOutputs) -> Self {
+        OutputRecorder {
+            first_indices: outputs.next_indices(),
+            ..Default::default()
+        }
+    }
+
+    /// Emit every recorded notice, voucher and report through `outputs`, and return the index `outputs` gave
+    /// each notice and voucher. Those indices were already handed out while recording, e.g. in voucher metas,
+    /// so an index that differs from the recorded one is an error.
+    pub async fn forward(
+        self,
+        outputs: &mut dyn RollupOutputs,
+        input_index: u64,
+    ) -> Result<InputOutputs, DazzleError> {
+        let mut input_outputs = InputOutputs {
+            input_index,
+            notices: vec![],
+            vouchers: vec![],
+        };
+
+        for (notice, expected) in self.notices.into_iter().zip(self.first_indices.notice..) {
+            let decoded = decode_dazzle_notice(&notice);
+            let index = outputs.add_notice(notice).await?;
+            check_index("notice", expected, index)?;
+            if let Some(decoded) = decoded {
+                input_outputs.notices.push(NoticeOutput {
+                    index,
+                    notice_type: decoded.notice_type,
+                    user: decoded.user,
+                });
+            }
+        }
+        for (voucher, expected) in self.vouchers.into_iter().zip(self.first_indices.voucher..) {
+            let destination = voucher.destination.clone();
+            let index = outputs.add_voucher(voucher).await?;
+            check_index("voucher", expected, index)?;
+            input_outputs
+                .vouchers
+                .push(VoucherOutput { index, destination });
+        }
+
+        for report in self.reports {
+            outputs.add_report(report).await?;
+        }
+        Ok(input_outputs)
+    }
+
+    /// Emit only the recorded reports, e.g. the error report of a rejected operation
+    pub async fn forward_reports(self, outputs: &mut dyn RollupOutputs) -> Result<(), DazzleError> {
+        for report in self.reports {
+            outputs.add_report(report).await?;
+        }
+        Ok(())
+    }
+
+    pub fn dazzle_notices(&self) -> Vec<DazzleNotice> {
+        self.notices
+            .iter()
+            .filter_map(decode_dazzle_notice)
+            .collect()
+    }
+
+    #[cfg(test)]
+    pub fn report_payloads(&self) -> Vec<String> {
+        self.reports
+            .iter()
+            .map(|report| {
+                let bz = hex::decode(report.payload.trim_start_matches("0x")).unwrap();
+                String::from_utf8(bz).unwrap()
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl RollupOutputs for OutputRecorder {
+    async fn add_notice(&mut self, notice: Notice) -> Result<u64, DazzleError> {
+        self.notices.push(notice);
+        Ok(self.first_indices.notice + self.notices.len() as u64 - 1)
+    }
+
+    async fn add_report(&mut self, report: Report) -> Result<(), DazzleError> {
+        self.reports.push(report);
+        Ok(())
+    }
+
+    async fn add_voucher(&mut self, voucher: Voucher) -> Result<u64, DazzleError> {
+        self.vouchers.push(voucher);
+        Ok(self.first_indices.voucher + self.vouchers.len() as u64 - 1)
+    }
+
+    async fn add_exception(&mut self, exception: Exception) -> Result<(), DazzleError> {
+        self.exceptions.push(exception);
+        Ok(())
+    }
+
+    fn next_indices(&self) -> OutputIndices {
+        OutputIndices {
+            notice: self.first_indices.notice + self.notices.len() as u64,
+            voucher: self.first_indices.voucher + self.vouchers.len() as u64,
+        }
+    }
+}
+
+fn decode_dazzle_notice(notice: &Notice) -> Option<DazzleNotice> {
+    let bz = hex::decode(notice.payload.trim_start_matches("0x")).ok()?;
+    serde_json::from_slice(&bz).ok()
+}
+
+fn check_index(output: &str, expected: u64, index: u64) -> Result<(), ServerError> {
+    if index != expected {
+        log::error!(
+            "Rollup server numbered a {} {}, {} was handed out",
+            output,
+            index,
+            expected
+        );
+        return Err(ServerError::OutputIndexMismatch(
+            output.to_owned(),
+            expected,
+            index,
+        ));
+    }
+    Ok(())
+}
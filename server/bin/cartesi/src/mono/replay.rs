@@ -0,0 +1,169 @@
+use super::dapp_state::DappState;
+use super::output_recorder::OutputRecorder;
+use super::service::{process_advance_state, process_inspect_state, RollupConfig};
+use crate::rollup_io::{decode_notice, decode_report, fill_metadata, read_inputs, DecodedNotice};
+use atb::logging::init_logger;
+use clap::Parser;
+use domain::cartesi::{FinishStatus, RequestType, RollupResponse, Voucher};
+use domain::game_core::ServerError;
+use serde::Serialize;
+use serde_json::Value;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+#[derive(Debug, Parser)]
+pub struct ReplayOpts {
+    /// JSONL file of `RollupResponse` or `AdvanceRequest` records, read from stdin when omitted
+    #[clap(short, long)]
+    input: Option<PathBuf>,
+
+    /// Where to write the transcript, printed to stdout when omitted
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+
+    /// msg_sender used for advance inputs recorded without metadata
+    #[clap(long, default_value = "0x70997970c51812dc3a010c7d01b50e0d17dc79c8")]
+    msg_sender: String,
+}
+
+/// Everything a single input produced, one line of the transcript
+#[derive(Debug, Serialize)]
+pub struct TranscriptEntry {
+    pub request_type: String,
+    pub input_index: Option<u64>,
+    pub status: FinishStatus,
+    pub notices: Vec<DecodedNotice>,
+    pub reports: Vec<Value>,
+    pub vouchers: Vec<Voucher>,
+}
+
+impl TranscriptEntry {
+    fn new(
+        rollup: &RollupResponse,
+        status: FinishStatus,
+        outputs: OutputRecorder,
+    ) -> Result<Self, ServerError> {
+        Ok(TranscriptEntry {
+            request_type: rollup.request_type.clone(),
+            input_index: rollup.data.metadata.as_ref().map(|m| m.input_index),
+            status,
+            notices: outputs
+                .notices
+                .iter()
+                .map(|notice| decode_notice(&notice.payload))
+                .collect::<Result<_, _>>()?,
+            reports: outputs
+                .reports
+                .iter()
+                .map(|report| decode_report(&report.payload))
+                .collect::<Result<_, _>>()?,
+            vouchers: outputs.vouchers,
+        })
+    }
+}
+
+/// Run recorded inputs through a fresh dapp state and produce the transcript of their outputs.
+///
+/// Inputs are processed exactly like `rollup()` does, minus the rollup http server.
+/// Rooms draw all their randomness from the input seed, so the same inputs always give the same transcript.
+pub async fn replay_inputs(
+    inputs: Vec<RollupResponse>,
+    config: &RollupConfig,
+    default_msg_sender: &str,
+) -> Result<Vec<TranscriptEntry>, ServerError> {
+    let mut state = DappState::new();
+    let mut dapp_address = config.dapp_address.clone();
+    let mut next_input_index = 0;
+    let mut transcript = vec![];
+
+    for mut rollup in inputs {
+        let mut outputs = OutputRecorder::new();
+
+        let status = match rollup.request_type.parse::<RequestType>() {
+            Ok(RequestType::AdvanceState) => {
+                //#NOTE: timestamp is fixed so that inputs without metadata replay identically
+                fill_metadata(&mut rollup, next_input_index, default_msg_sender, 0);
+                next_input_index = rollup
+                    .data
+                    .metadata
+                    .as_ref()
+                    .map_or(next_input_index, |m| m.input_index)
+                    + 1;
+
+                if let Some(relayed_address) = config.relayed_dapp_address(&rollup) {
+                    dapp_address = relayed_address;
+                    log::debug!("Captured dapp address: {}", dapp_address);
+                    continue;
+                }
+
+                process_advance_state(
+                    rollup.data.clone(),
+                    &mut state,
+                    &mut outputs,
+                    config,
+                    &dapp_address,
+                )
+                .await
+            }
+            Ok(RequestType::InspectState) => {
+                process_inspect_state(rollup.data.clone(), &state, &mut outputs).await
+            }
+            Err(e) => {
+                log::error!("Error occurred while handling rollup request: {}", e);
+                continue;
+            }
+        };
+
+        transcript.push(TranscriptEntry::new(&rollup, status, outputs)?);
+    }
+
+    Ok(transcript)
+}
+
+pub fn replay(opts: ReplayOpts) {
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("tokio runtime needed to continue. qed");
+
+    rt.block_on(async {
+        init_logger("warn, cartesi=info", true);
+
+        let config = RollupConfig::from_env();
+        let transcript = match read_inputs(opts.input.as_deref()) {
+            Ok(inputs) => replay_inputs(inputs, &config, &opts.msg_sender).await,
+            Err(e) => Err(e),
+        };
+
+        let transcript = match transcript {
+            Ok(transcript) => transcript,
+            Err(e) => {
+                log::error!("Replay failed: {}", e);
+                return;
+            }
+        };
+
+        let mut writer: Box<dyn Write> = match opts.output.as_ref() {
+            Some(path) => match File::create(path) {
+                Ok(file) => Box::new(BufWriter::new(file)),
+                Err(e) => {
+                    log::error!("Failed to create transcript file: {}", e);
+                    return;
+                }
+            },
+            None => Box::new(std::io::stdout()),
+        };
+
+        for entry in transcript {
+            if let Err(e) = writeln!(writer, "{}", serde_json::to_string(&entry).unwrap()) {
+                log::error!("Failed to write transcript: {}", e);
+                return;
+            }
+        }
+
+        if let Err(e) = writer.flush() {
+            log::error!("Failed to write transcript: {}", e);
+        }
+    });
+}
@@ -0,0 +1,72 @@
+/// Serde of a map with tuple keys, which can't be json object keys, as a list of entries sorted by key
+pub mod map_entries {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::HashMap;
+    use std::hash::Hash;
+
+    pub fn serialize<K, V, S>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        K: Serialize + Ord,
+        V: Serialize,
+        S: Serializer,
+    {
+        let mut entries: Vec<(&K, &V)> = map.iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        entries.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<HashMap<K, V>, D::Error>
+    where
+        K: Deserialize<'de> + Eq + Hash,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        Ok(Vec::<(K, V)>::deserialize(deserializer)?
+            .into_iter()
+            .collect())
+    }
+}
+
+/// Same as `map_entries`, for a map whose values are maps with tuple keys
+pub mod nested_map_entries {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::HashMap;
+    use std::hash::Hash;
+
+    pub fn serialize<K, L, V, S>(
+        map: &HashMap<K, HashMap<L, V>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        K: Serialize + Ord,
+        L: Serialize + Ord,
+        V: Serialize,
+        S: Serializer,
+    {
+        let mut entries: Vec<(&K, Vec<(&L, &V)>)> = map
+            .iter()
+            .map(|(key, inner)| {
+                let mut inner_entries: Vec<(&L, &V)> = inner.iter().collect();
+                inner_entries.sort_by(|a, b| a.0.cmp(b.0));
+                (key, inner_entries)
+            })
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        entries.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, K, L, V, D>(
+        deserializer: D,
+    ) -> Result<HashMap<K, HashMap<L, V>>, D::Error>
+    where
+        K: Deserialize<'de> + Eq + Hash,
+        L: Deserialize<'de> + Eq + Hash,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        Ok(Vec::<(K, Vec<(L, V)>)>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(key, inner)| (key, inner.into_iter().collect()))
+            .collect())
+    }
+}
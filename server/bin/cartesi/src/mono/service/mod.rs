@@ -0,0 +1,2436 @@
+use super::balance_manager::{BalanceManager, StakeEscrow, ETHER};
+use super::dapp_state::DappState;
+use super::http_dispatcher::{
+    send_chunked_report, send_exception, send_notice, send_report, send_room_snapshot_notice,
+    send_voucher, HttpDispatcher, RollupOutputs,
+};
+use super::ingame_wallet_manager::{IngameWalletManager, SessionKey};
+use super::nft_inventory_manager::{nft_label, roll_nft_character, NftInventoryManager};
+use super::output_recorder::OutputRecorder;
+use super::snapshot::{is_new_input, read_snapshot, write_snapshot, DEFAULT_SNAPSHOT_INTERVAL};
+use atb_types::prelude::uuid::Uuid;
+use base64::{engine::general_purpose, Engine as _};
+use domain::cartesi::portals::{
+    decode_erc20_deposit, decode_erc721_deposit, decode_ether_deposit, Erc20Deposit, Erc721Deposit,
+    EtherDeposit,
+};
+use domain::cartesi::{
+    attach_ingame_wallet_message, relayed_action_typed_data, ActiveSkillsRequest, AdvanceMetadata,
+    AdvanceRequest, AttachIngameWalletRequest, BalanceResponse, CancelRoomRequest,
+    CreatePrivateRoomRequest, DazzleException, DazzleOperation, DazzleReport, DazzleRequest,
+    FindRoomRequest, FinishStatus, GameRequest, InputOutputs, InspectQuery, InspectResponse,
+    InventoryResponse, JoinPrivateRoomRequest, LedgerEntryKind, LedgerResponse,
+    MarkVoucherExecutedRequest, MoveRequest, NftCharacter, NftCharacterId, NoticeType,
+    PlayerRoomResponse, QuitGameRequest, RelayedRequest, RequestType, RevokeSessionKeyRequest,
+    RollupResponse, RoomPhase, RoomSummary, RoomsResponse, SessionKeyState, SessionScope,
+    StakeNotice, TransferRequest, VersionedRequest, VouchersResponse, WalletResponse,
+    WithdrawNftRequest, WithdrawRequest, DAZZLE_REQUEST_VERSION,
+};
+use domain::game_core::character::CharacterV2;
+use domain::game_core::config::{
+    GameplayConfigManager, ADMIN_WALLET_ADDRESS, BPS_UNIT, DEFAULT_PLATFORM_FEE_BPS,
+    MAX_BATCH_SIZE, MAX_SESSION_DURATION, MAX_STAKE, MIN_STAKE, SESSION_DURATION, STAKE,
+};
+use domain::game_core::game::Room;
+use domain::game_core::room_manager::*;
+use domain::game_core::{DazzleError, ServerError};
+use ethers_core::{
+    abi::{encode, short_signature, ParamType, Token},
+    types::{Address, RecoveryMessage, Signature, H256, U256},
+    utils::{hex, keccak256, to_checksum},
+};
+use futures::FutureExt;
+use hyper::StatusCode;
+use serde_json::{json, Value};
+use std::any::Any;
+use std::collections::HashSet;
+use std::env;
+use std::panic::AssertUnwindSafe;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+//#NOTE: backoff bounds when the rollup http server can't be reached through /finish
+const FINISH_RETRY_MIN_DELAY: Duration = Duration::from_millis(100);
+const FINISH_RETRY_MAX_DELAY: Duration = Duration::from_secs(10);
+
+/// Raw character lists carry stats nobody checked, only dev and tutorial builds take them
+#[cfg(feature = "raw-characters")]
+fn decode_character_list(base64_character_list: &str) -> Result<Vec<CharacterV2>, ServerError> {
+    let character_list_bz = general_purpose::STANDARD
+        .decode(base64_character_list)
+        .map_err(|e| {
+            log::debug!(
+                "Failed to decode base64 payload: {} - {}",
+                base64_character_list,
+                e.to_string()
+            );
+            ServerError::InvalidRequest
+        })?;
+
+    serde_json::from_slice(&character_list_bz).map_err(|e| {
+        log::debug!("Failed to deserialize party character data list: {}", e);
+        ServerError::InvalidJson
+    })
+}
+
+#[cfg(not(feature = "raw-characters"))]
+fn decode_character_list(_base64_character_list: &str) -> Result<Vec<CharacterV2>, ServerError> {
+    Err(ServerError::RawCharacterListDenied)
+}
+
+fn parse_token_id(token_id: &str) -> Result<U256, ServerError> {
+    U256::from_dec_str(token_id).map_err(|_| ServerError::InvalidNFT)
+}
+
+/// The party is made of deposited NFT characters, or of the raw character list when none is given and the
+/// `raw-characters` feature is on.
+/// NFTs are looked up in the inventory of the metamask wallet the sender is attached to, or of the sender itself.
+fn resolve_party(
+    nft_inventory_manager: &NftInventoryManager,
+    ingame_wallet_manager: &IngameWalletManager,
+    msg_sender: &str,
+    nft_characters: &[NftCharacterId],
+    base64_character_list: &str,
+) -> Result<Vec<CharacterV2>, ServerError> {
+    if nft_characters.is_empty() {
+        return decode_character_list(base64_character_list);
+    }
+
+    if nft_characters.iter().collect::<HashSet<_>>().len() != nft_characters.len() {
+        log::debug!("The same NFT character is used twice in a party");
+        return Err(ServerError::InvalidRequest);
+    }
+
+    let sender = parse_address(msg_sender)?;
+    let owner = ingame_wallet_manager
+        .get_metamask_wallet(&sender)
+        .copied()
+        .unwrap_or(sender);
+
+    nft_characters
+        .iter()
+        .map(|nft| {
+            let collection = parse_address(&nft.collection)?;
+            let token_id = parse_token_id(&nft.token_id)?;
+            nft_inventory_manager
+                .get_character(&owner, &collection, token_id)
+                .cloned()
+        })
+        .collect()
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn create_private_room(
+    room_manager: &mut RoomManager,
+    balance_manager: &mut BalanceManager,
+    nft_inventory_manager: &NftInventoryManager,
+    ingame_wallet_manager: &IngameWalletManager,
+    outputs: &mut dyn RollupOutputs,
+    msg_sender: &str,
+    req: CreatePrivateRoomRequest,
+    new_seed: u64,
+    platform_fee_bps: u32,
+) -> Result<FinishStatus, DazzleError> {
+    log::debug!("CREATE PRIVATE ROOM");
+
+    if room_manager.get_room_status(&req.user).is_some() {
+        // Cartesi mode support reconnect now, skip create room procedure if player is already in game
+        return Ok(FinishStatus::Accept);
+    }
+
+    let character_list = resolve_party(
+        nft_inventory_manager,
+        ingame_wallet_manager,
+        msg_sender,
+        &req.nft_characters,
+        &req.base64_character_list,
+    )?;
+
+    let stake_token = parse_token(req.stake_token.as_deref())?;
+    let stake = parse_stake(req.stake.as_deref())?;
+    //#NOTE: the room keeps the config it was created with, so a match settles with the fee it was entered at
+    let mut gameplay_config = GameplayConfigManager::new();
+    gameplay_config.set_platform_fee_bps(platform_fee_bps);
+    let room_status = room_manager.create_private_room(
+        &req.user,
+        &character_list,
+        Some(&gameplay_config),
+        GameMode::Cartesi,
+        Some(new_seed),
+        (stake_token != ETHER).then(|| format!("{:#x}", stake_token)),
+        &stake.to_string(),
+    )?;
+    log::debug!("    PRIVATE CODE: {}", &room_status.private_code);
+    log::debug!("    ROOM ID: {}", room_status.room_id.to_owned());
+
+    let new_room = room_manager.get_room(&room_status.room_id).unwrap();
+    lock_room_stake(
+        balance_manager,
+        outputs,
+        new_room,
+        &req.user,
+        &resolve_account(ingame_wallet_manager, msg_sender)?,
+    )
+    .await?;
+
+    send_room_snapshot_notice(outputs, &req.user, new_room, None).await
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn join_private_room(
+    room_manager: &mut RoomManager,
+    balance_manager: &mut BalanceManager,
+    nft_inventory_manager: &NftInventoryManager,
+    ingame_wallet_manager: &IngameWalletManager,
+    outputs: &mut dyn RollupOutputs,
+    msg_sender: &str,
+    req: JoinPrivateRoomRequest,
+    new_seed: u64,
+) -> Result<FinishStatus, DazzleError> {
+    log::debug!("Join PRIVATE ROOM, code: \"{}\"", &req.private_code);
+
+    //#NOTE: Cartesi mode support reconnect now, reject input if player is already in game
+    if room_manager.get_room_status(&req.user).is_some() {
+        return Ok(FinishStatus::Reject);
+    }
+
+    let character_list = resolve_party(
+        nft_inventory_manager,
+        ingame_wallet_manager,
+        msg_sender,
+        &req.nft_characters,
+        &req.base64_character_list,
+    )?;
+
+    let (room_status, _) = room_manager.join_private_room(
+        &req.user,
+        &req.private_code,
+        &character_list,
+        None,
+        None,
+    )?;
+
+    log::debug!("    PRIVATE CODE: {}", room_status.private_code.clone());
+    log::debug!("    ROOM ID: {}", room_status.room_id.to_owned());
+
+    let new_room = room_manager.update_room_rng(&room_status.room_id, new_seed)?;
+    lock_room_stake(
+        balance_manager,
+        outputs,
+        &new_room,
+        &req.user,
+        &resolve_account(ingame_wallet_manager, msg_sender)?,
+    )
+    .await?;
+
+    send_room_snapshot_notice(outputs, &req.user, &new_room, None).await
+}
+
+async fn cancel_room(
+    room_manager: &mut RoomManager,
+    balance_manager: &mut BalanceManager,
+    outputs: &mut dyn RollupOutputs,
+    req: CancelRoomRequest,
+) -> Result<FinishStatus, DazzleError> {
+    log::debug!("CANCEL ROOM, user: {}", req.user);
+
+    let uuid = *room_manager
+        .get_uuid_by_player(&req.user)
+        .ok_or(ServerError::RoomNotFound)?;
+    room_manager.cancel_room(&req.user)?;
+
+    if let Some(escrow) = balance_manager.take_escrow(&uuid) {
+        refund_escrow(balance_manager, outputs, &uuid, escrow).await?;
+    }
+    send_notice(outputs, NoticeType::CancelRoom, "", &req.user, None).await
+}
+
+async fn game_over(
+    room_manager: &mut RoomManager,
+    balance_manager: &mut BalanceManager,
+    outputs: &mut dyn RollupOutputs,
+    req: FindRoomRequest,
+) -> Result<FinishStatus, DazzleError> {
+    log::debug!("GAME OVER");
+
+    let uuid = room_manager
+        .get_uuid_by_player(&req.user)
+        .ok_or(ServerError::RoomNotFound)?
+        .clone();
+
+    if let Some(room) = room_manager.get_room(&uuid) {
+        settle_escrow(balance_manager, outputs, room).await?;
+    }
+
+    let (room_uuid, game_result) = room_manager.get_room_result(&req.user, false, None)?;
+    room_manager.remove_player(&room_uuid, &req.user)?;
+    room_manager.remove_empty_room(&room_uuid)?;
+
+    let address =
+        Address::from_str(&req.user).map_err(|_| ServerError::InvalidAddress(req.user.clone()))?;
+
+    let balance = balance_manager
+        .get_balance(&ETHER, &address)
+        .map_or_else(|| "0".to_owned(), |b| b.to_string());
+
+    if let Some(room) = room_manager.get_room(&uuid) {
+        //#NOTE: Since Room has been modified, we need to send a notice, so that CartesiHarvester can maintain the correct projection of the room_data
+        send_room_snapshot_notice(outputs, &req.user, room, Some(balance.clone())).await?;
+    }
+
+    let game_over_notice = serde_json::to_string(&game_result).unwrap();
+    send_notice(
+        outputs,
+        NoticeType::GameResult,
+        &game_over_notice,
+        &req.user,
+        Some(balance),
+    )
+    .await
+}
+
+async fn action_move(
+    room_manager: &mut RoomManager,
+    balance_manager: &mut BalanceManager,
+    outputs: &mut dyn RollupOutputs,
+    req: MoveRequest,
+    new_seed: u64,
+) -> Result<FinishStatus, DazzleError> {
+    log::debug!(
+        "MOVE ACTION {:?}, user: \"{}\"",
+        req.action,
+        req.user.clone()
+    );
+
+    room_manager.update_room_rng(&req.room_id, new_seed)?;
+
+    let room = room_manager.move_action(
+        &req.room_id,
+        &req.user,
+        &req.action,
+        &req.attacker_id,
+        &req.defender_id,
+    )?;
+
+    settle_escrow(balance_manager, outputs, &room).await?;
+    send_room_snapshot_notice(outputs, &req.user, &room, None).await
+}
+
+async fn activate_skill(
+    room_manager: &mut RoomManager,
+    balance_manager: &mut BalanceManager,
+    outputs: &mut dyn RollupOutputs,
+    req: ActiveSkillsRequest,
+    new_seed: u64,
+) -> Result<FinishStatus, DazzleError> {
+    log::debug!("ACTIVATE SKILLS, user: \"{}\"", req.user.clone());
+
+    room_manager.update_room_rng(&req.room_id, new_seed)?;
+
+    let room = room_manager.skill_action(
+        &req.room_id,
+        &req.user,
+        req.caster_id,
+        req.ally_target_id,
+        req.rival_target_id,
+    )?;
+    log::debug!("Done");
+    settle_escrow(balance_manager, outputs, &room).await?;
+    send_room_snapshot_notice(outputs, &req.user, &room, None).await
+}
+
+async fn quit_game(
+    room_manager: &mut RoomManager,
+    balance_manager: &mut BalanceManager,
+    outputs: &mut dyn RollupOutputs,
+    req: QuitGameRequest,
+) -> Result<FinishStatus, DazzleError> {
+    log::debug!("QUIT GAME, user: \"{}\"", req.user);
+
+    let room = room_manager.quit_game(&req.user)?;
+    settle_escrow(balance_manager, outputs, &room).await?;
+    send_room_snapshot_notice(outputs, &req.user, &room, None).await
+}
+
+fn stake_notice(room_id: &Uuid, token: &Address, amount: U256) -> String {
+    serde_json::to_string(&StakeNotice {
+        room_id: *room_id,
+        token: (*token != ETHER).then(|| format!("{:#x}", token)),
+        amount: amount.to_string(),
+    })
+    .unwrap()
+}
+
+/// Take the stake of a player entering the room from the balance that paid for it, stakes are paid out to that
+/// same balance when the room settles
+async fn lock_room_stake(
+    balance_manager: &mut BalanceManager,
+    outputs: &mut dyn RollupOutputs,
+    room: &Room,
+    user: &str,
+    payer: &Address,
+) -> Result<(), DazzleError> {
+    let token = room_stake_token(Some(room))?;
+    let stake = room_stake(room)?;
+    let new_balance = balance_manager.lock_stake(&room.uuid, &token, user, payer, stake)?;
+
+    send_notice(
+        outputs,
+        NoticeType::StakeLocked,
+        &stake_notice(&room.uuid, &token, stake),
+        &format!("{:#x}", payer),
+        Some(new_balance.to_string()),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn refund_escrow(
+    balance_manager: &mut BalanceManager,
+    outputs: &mut dyn RollupOutputs,
+    room_id: &Uuid,
+    escrow: StakeEscrow,
+) -> Result<(), DazzleError> {
+    for stake in escrow.stakes {
+        let new_balance = balance_manager.deposit(
+            &escrow.token,
+            &stake.payer,
+            stake.amount,
+            LedgerEntryKind::StakeRefund,
+            &room_id.to_string(),
+        );
+        send_notice(
+            outputs,
+            NoticeType::StakeRefund,
+            &stake_notice(room_id, &escrow.token, stake.amount),
+            &format!("{:#x}", stake.payer),
+            Some(new_balance.to_string()),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Pay the stakes of a finished room, less the platform fee which goes to the admin wallet, to the payer recorded
+/// for the winner's stake. The escrow is gone afterwards so this only pays once
+async fn settle_escrow(
+    balance_manager: &mut BalanceManager,
+    outputs: &mut dyn RollupOutputs,
+    room: &Room,
+) -> Result<(), DazzleError> {
+    if !room.is_finished() {
+        return Ok(());
+    }
+    let escrow = match balance_manager.take_escrow(&room.uuid) {
+        Some(escrow) => escrow,
+        None => return Ok(()),
+    };
+
+    let winner_id = room.get_winner_id();
+    let winner_payer = escrow
+        .stakes
+        .iter()
+        .find(|stake| Some(&stake.user) == winner_id.as_ref())
+        .map(|stake| stake.payer);
+
+    match winner_payer {
+        Some(payer) => {
+            let platform_fee = room
+                .game_over_result
+                .as_ref()
+                .and_then(|result| U256::from_dec_str(&result.platform_fee).ok())
+                .unwrap_or_default()
+                .min(escrow.total());
+            let reward = escrow.total() - platform_fee;
+            log::debug!(
+                "Room {} settled, paying {} to {:#x}",
+                room.uuid,
+                reward,
+                payer
+            );
+            let new_balance = balance_manager.deposit(
+                &escrow.token,
+                &payer,
+                reward,
+                LedgerEntryKind::StakePayout,
+                &room.uuid.to_string(),
+            );
+            send_notice(
+                outputs,
+                NoticeType::StakePayout,
+                &stake_notice(&room.uuid, &escrow.token, reward),
+                &format!("{:#x}", payer),
+                Some(new_balance.to_string()),
+            )
+            .await?;
+
+            if !platform_fee.is_zero() {
+                let admin = parse_address(ADMIN_WALLET_ADDRESS)?;
+                let admin_balance = balance_manager.deposit(
+                    &escrow.token,
+                    &admin,
+                    platform_fee,
+                    LedgerEntryKind::PlatformFee,
+                    &room.uuid.to_string(),
+                );
+                send_notice(
+                    outputs,
+                    NoticeType::PlatformFee,
+                    &stake_notice(&room.uuid, &escrow.token, platform_fee),
+                    &format!("{:#x}", admin),
+                    Some(admin_balance.to_string()),
+                )
+                .await?;
+            }
+            Ok(())
+        }
+        //#NOTE: no stake on record for the winner, give every stake back rather than keep it
+        None => refund_escrow(balance_manager, outputs, &room.uuid, escrow).await,
+    }
+}
+
+fn parse_token(token: Option<&str>) -> Result<Address, ServerError> {
+    token.map_or(Ok(ETHER), parse_address)
+}
+
+/// Ether leaves through the dapp's `withdrawEther`, ERC-20 tokens through the token's own `transfer`
+fn withdrawal_voucher(
+    token: &Address,
+    to_address: Address,
+    amount: U256,
+    dapp_address: &str,
+) -> (String, Vec<u8>) {
+    let params = vec![ParamType::Address, ParamType::Uint(256)];
+    let (destination, signature) = match *token == ETHER {
+        true => (dapp_address.to_owned(), "withdrawEther"),
+        false => (format!("{:#x}", token), "transfer"),
+    };
+
+    let mut payload_bz = short_signature(signature, &params).to_vec();
+    payload_bz.append(&mut encode(&[
+        Token::Address(to_address),
+        Token::Uint(amount),
+    ]));
+    (destination, payload_bz)
+}
+
+/// Funds belong to the metamask wallet, an in-game wallet with the stake scope acts on behalf of the metamask wallet it is attached to
+fn resolve_account(
+    ingame_wallet_manager: &IngameWalletManager,
+    msg_sender: &str,
+) -> Result<Address, ServerError> {
+    let sender = parse_address(msg_sender)?;
+    Ok(ingame_wallet_manager
+        .get_session_by_key(&sender)
+        .filter(|(_, session)| session.scope == SessionScope::GameplayAndStake)
+        .map_or(sender, |(metamask_wallet, _)| *metamask_wallet))
+}
+
+fn voucher_meta_json(balance_manager: &BalanceManager, address: &Address) -> String {
+    let voucher_meta_list = balance_manager
+        .get_voucher_meta(address)
+        .cloned()
+        .unwrap_or_default();
+    serde_json::to_string(&voucher_meta_list).unwrap()
+}
+
+/// `from_address` must be the sender, or the metamask wallet whose funds the sender spends
+fn check_funds_owner(
+    ingame_wallet_manager: &IngameWalletManager,
+    msg_sender: &str,
+    from_address: &Address,
+) -> Result<(), ServerError> {
+    let sender = parse_address(msg_sender)?;
+    if *from_address != sender
+        && *from_address != resolve_account(ingame_wallet_manager, msg_sender)?
+    {
+        return Err(ServerError::NotFundsOwner(
+            msg_sender.to_owned(),
+            format!("{:#x}", from_address),
+        ));
+    }
+    Ok(())
+}
+
+/// Pay `to_address` on L1 out of the sender's own funds, the voucher meta is kept for `to_address` who executes it
+async fn transfer(
+    balance_manager: &mut BalanceManager,
+    ingame_wallet_manager: &IngameWalletManager,
+    outputs: &mut dyn RollupOutputs,
+    dapp_address: &str,
+    metadata: AdvanceMetadata,
+    req: TransferRequest,
+) -> Result<FinishStatus, DazzleError> {
+    log::debug!(
+        "Transfer balance, from_address: \"{}\", to_address: \"{}\", amount: \"{}\"",
+        req.from_address,
+        req.to_address,
+        req.amount,
+    );
+
+    let from_address = parse_address(&req.from_address)?;
+    let to_address = parse_address(&req.to_address)?;
+    check_funds_owner(ingame_wallet_manager, &metadata.msg_sender, &from_address)?;
+
+    let amount =
+        U256::from_dec_str(&req.amount).map_err(|_| ServerError::InvalidCurrency(req.amount))?;
+    let token = parse_token(req.token.as_deref())?;
+
+    //#NOTE: withdraw before emitting the voucher, so that an insufficient balance never produces one
+    let (destination, payload_bz) = withdrawal_voucher(&token, to_address, amount, dapp_address);
+    let from_new_balance = balance_manager.withdraw(
+        &token,
+        &from_address,
+        amount,
+        LedgerEntryKind::Withdraw,
+        &destination,
+    )?;
+
+    let voucher_index = send_voucher(outputs, &destination, &payload_bz).await?;
+    balance_manager.update_voucher_meta(
+        &to_address,
+        &token,
+        amount.to_string(),
+        metadata,
+        voucher_index,
+    );
+
+    send_notice(
+        outputs,
+        NoticeType::Transfer,
+        &voucher_meta_json(balance_manager, &from_address),
+        &req.from_address,
+        Some(from_new_balance.to_string()),
+    )
+    .await?;
+
+    //#NOTE: the funds reach `to_address` through the voucher only, its in-dapp balance is unchanged
+    let to_balance = balance_manager
+        .get_balance(&token, &to_address)
+        .copied()
+        .unwrap_or_default();
+    send_notice(
+        outputs,
+        NoticeType::Transfer,
+        &voucher_meta_json(balance_manager, &to_address),
+        &req.to_address,
+        Some(to_balance.to_string()),
+    )
+    .await
+}
+
+/// Move funds between two in-dapp balances, only the sender's own funds can be moved
+async fn internal_transfer(
+    balance_manager: &mut BalanceManager,
+    ingame_wallet_manager: &IngameWalletManager,
+    outputs: &mut dyn RollupOutputs,
+    msg_sender: &str,
+    req: TransferRequest,
+) -> Result<FinishStatus, DazzleError> {
+    log::debug!(
+        "Internal transfer, from_address: \"{}\", to_address: \"{}\", amount: \"{}\"",
+        req.from_address,
+        req.to_address,
+        req.amount,
+    );
+
+    let from_address = parse_address(&req.from_address)?;
+    let to_address = parse_address(&req.to_address)?;
+    check_funds_owner(ingame_wallet_manager, msg_sender, &from_address)?;
+
+    let amount =
+        U256::from_dec_str(&req.amount).map_err(|_| ServerError::InvalidCurrency(req.amount))?;
+    let token = parse_token(req.token.as_deref())?;
+
+    let from_new_balance = balance_manager.withdraw(
+        &token,
+        &from_address,
+        amount,
+        LedgerEntryKind::TransferOut,
+        &format!("{:#x}", to_address),
+    )?;
+    let to_new_balance = balance_manager.deposit(
+        &token,
+        &to_address,
+        amount,
+        LedgerEntryKind::TransferIn,
+        &format!("{:#x}", from_address),
+    );
+
+    send_notice(
+        outputs,
+        NoticeType::InternalTransfer,
+        &voucher_meta_json(balance_manager, &from_address),
+        &req.from_address,
+        Some(from_new_balance.to_string()),
+    )
+    .await?;
+
+    send_notice(
+        outputs,
+        NoticeType::InternalTransfer,
+        &voucher_meta_json(balance_manager, &to_address),
+        &req.to_address,
+        Some(to_new_balance.to_string()),
+    )
+    .await
+}
+
+/// Withdraw the sender's own funds back to L1, the voucher always pays the owning metamask wallet
+async fn withdraw(
+    balance_manager: &mut BalanceManager,
+    ingame_wallet_manager: &IngameWalletManager,
+    outputs: &mut dyn RollupOutputs,
+    dapp_address: &str,
+    metadata: AdvanceMetadata,
+    req: WithdrawRequest,
+) -> Result<FinishStatus, DazzleError> {
+    let owner = resolve_account(ingame_wallet_manager, &metadata.msg_sender)?;
+    log::debug!(
+        "Withdraw, sender: \"{}\", owner: \"{:#x}\", amount: \"{}\"",
+        metadata.msg_sender,
+        owner,
+        req.amount,
+    );
+
+    let amount =
+        U256::from_dec_str(&req.amount).map_err(|_| ServerError::InvalidCurrency(req.amount))?;
+    let token = parse_token(req.token.as_deref())?;
+
+    //#NOTE: withdraw before emitting the voucher, so that an insufficient balance never produces one
+    let (destination, payload_bz) = withdrawal_voucher(&token, owner, amount, dapp_address);
+    let new_balance = balance_manager.withdraw(
+        &token,
+        &owner,
+        amount,
+        LedgerEntryKind::Withdraw,
+        &destination,
+    )?;
+
+    let voucher_index = send_voucher(outputs, &destination, &payload_bz).await?;
+    balance_manager.update_voucher_meta(
+        &owner,
+        &token,
+        amount.to_string(),
+        metadata,
+        voucher_index,
+    );
+
+    send_notice(
+        outputs,
+        NoticeType::Withdraw,
+        &voucher_meta_json(balance_manager, &owner),
+        &format!("{:#x}", owner),
+        Some(new_balance.to_string()),
+    )
+    .await
+}
+
+/// Forget a withdrawal voucher once its owner executed it on L1
+async fn mark_voucher_executed(
+    balance_manager: &mut BalanceManager,
+    ingame_wallet_manager: &IngameWalletManager,
+    outputs: &mut dyn RollupOutputs,
+    msg_sender: &str,
+    req: MarkVoucherExecutedRequest,
+) -> Result<FinishStatus, DazzleError> {
+    let owner = resolve_account(ingame_wallet_manager, msg_sender)?;
+    let meta = balance_manager.mark_voucher_executed(&owner, req.input_index, req.voucher_index)?;
+
+    send_notice(
+        outputs,
+        NoticeType::VoucherExecuted,
+        &serde_json::to_string(&meta).unwrap(),
+        &format!("{:#x}", owner),
+        None,
+    )
+    .await
+}
+
+pub async fn attach_ingame_wallet(
+    ingame_wallet_manager: &mut IngameWalletManager,
+    outputs: &mut dyn RollupOutputs,
+    dapp_address: &str,
+    chain_id: u64,
+    metadata: AdvanceMetadata,
+    req: AttachIngameWalletRequest,
+) -> Result<FinishStatus, DazzleError> {
+    let metamask_wallet_address = parse_address(&metadata.msg_sender)?;
+    if ingame_wallet_manager
+        .get_session(&metamask_wallet_address)
+        .is_some()
+    {
+        return Err(ServerError::SessionKeyExists(metadata.msg_sender.to_lowercase()).into());
+    }
+
+    start_session(
+        ingame_wallet_manager,
+        outputs,
+        dapp_address,
+        chain_id,
+        metadata,
+        req,
+    )
+    .await
+}
+
+/// Replace the session key of the sender, the current key must not be playing a room which the new key couldn't take over
+async fn rotate_session_key(
+    ingame_wallet_manager: &mut IngameWalletManager,
+    room_manager: &RoomManager,
+    outputs: &mut dyn RollupOutputs,
+    dapp_address: &str,
+    chain_id: u64,
+    metadata: AdvanceMetadata,
+    req: AttachIngameWalletRequest,
+) -> Result<FinishStatus, DazzleError> {
+    let metamask_wallet_address = parse_address(&metadata.msg_sender)?;
+    let current_key = *ingame_wallet_manager
+        .get_ingame_wallet(&metamask_wallet_address)
+        .ok_or_else(|| ServerError::SessionKeyNotFound(metadata.msg_sender.to_lowercase()))?;
+    if is_in_room(room_manager, &current_key) {
+        return Err(ServerError::SessionKeyInRoom(format!("{:#x}", current_key)).into());
+    }
+
+    start_session(
+        ingame_wallet_manager,
+        outputs,
+        dapp_address,
+        chain_id,
+        metadata,
+        req,
+    )
+    .await
+}
+
+async fn start_session(
+    ingame_wallet_manager: &mut IngameWalletManager,
+    outputs: &mut dyn RollupOutputs,
+    dapp_address: &str,
+    chain_id: u64,
+    metadata: AdvanceMetadata,
+    req: AttachIngameWalletRequest,
+) -> Result<FinishStatus, DazzleError> {
+    let ingame_wallet_str = req.ingame_wallet_address.to_lowercase();
+    let metamask_wallet_str = metadata.msg_sender.to_lowercase();
+
+    log::debug!(
+        "Register ingame wallet: \"{}\" with metamask : \"{}\", scope: {:?}",
+        ingame_wallet_str,
+        metamask_wallet_str,
+        req.scope,
+    );
+
+    let ingame_wallet_address = Address::from_str(&ingame_wallet_str)
+        .map_err(|_| ServerError::InvalidAddress(ingame_wallet_str.clone()))?;
+
+    let metamask_wallet_address = Address::from_str(&metamask_wallet_str)
+        .map_err(|_| ServerError::InvalidAddress(metamask_wallet_str.clone()))?;
+
+    //#NOTE: like relayed actions, attaching is refused until the address relay told the dapp address
+    let message = attach_ingame_wallet_message(
+        &metamask_wallet_str,
+        req.nonce,
+        &parse_address(dapp_address)?,
+        chain_id,
+    );
+    verify_signature(message, &req.signature, &ingame_wallet_address)?;
+    let session = SessionKey {
+        ingame_wallet: ingame_wallet_address,
+        scope: req.scope,
+        expires_at: session_expiry(req.expires_at, metadata.timestamp)?,
+    };
+    ingame_wallet_manager.attach_ingame_wallet(&metamask_wallet_address, session, req.nonce)?;
+
+    send_notice(
+        outputs,
+        NoticeType::AttachIngameWallet,
+        &ingame_wallet_str,
+        &metamask_wallet_str,
+        None,
+    )
+    .await
+}
+
+/// A session key lives SESSION_DURATION by default, and never longer than MAX_SESSION_DURATION
+fn session_expiry(expires_at: Option<u64>, timestamp: u64) -> Result<u64, ServerError> {
+    let expires_at = expires_at.unwrap_or(timestamp.saturating_add(SESSION_DURATION));
+    match expires_at > timestamp && expires_at <= timestamp.saturating_add(MAX_SESSION_DURATION) {
+        true => Ok(expires_at),
+        false => Err(ServerError::InvalidSessionExpiry(expires_at)),
+    }
+}
+
+/// Drop a session key, either its metamask wallet or the key itself may revoke it
+async fn revoke_session_key(
+    ingame_wallet_manager: &mut IngameWalletManager,
+    outputs: &mut dyn RollupOutputs,
+    msg_sender: &str,
+    req: RevokeSessionKeyRequest,
+) -> Result<FinishStatus, DazzleError> {
+    let sender = parse_address(msg_sender)?;
+    let key = parse_address(&req.ingame_wallet_address)?;
+    log::debug!(
+        "Revoke session key: \"{:#x}\", sender: \"{:#x}\"",
+        key,
+        sender
+    );
+
+    let metamask_wallet = match ingame_wallet_manager.get_session_by_key(&key) {
+        Some((metamask_wallet, _)) if *metamask_wallet == sender || key == sender => {
+            *metamask_wallet
+        }
+        _ => return Err(ServerError::SessionKeyNotFound(format!("{:#x}", key)).into()),
+    };
+    ingame_wallet_manager.revoke_session_key(&metamask_wallet);
+
+    send_notice(
+        outputs,
+        NoticeType::SessionKeyRevoked,
+        &format!("{:#x}", key),
+        &format!("{:#x}", metamask_wallet),
+        None,
+    )
+    .await
+}
+
+/// Check that `signature` (hex r, s, v) is the signature of `message` by `signer`, EIP-191 for a text message
+fn verify_signature<M: Into<RecoveryMessage>>(
+    message: M,
+    signature: &str,
+    signer: &Address,
+) -> Result<(), ServerError> {
+    let signature = Signature::from_str(signature).map_err(|_| ServerError::InvalidSignature)?;
+    signature
+        .verify(message, *signer)
+        .map_err(|_| ServerError::InvalidSignature)
+}
+
+fn is_in_room(room_manager: &RoomManager, player: &Address) -> bool {
+    //#NOTE: room users are the address strings sent by the client, either lowercase or checksummed
+    [format!("{:#x}", player), to_checksum(player, None)]
+        .iter()
+        .any(|user| room_manager.get_uuid_by_player(user).is_some())
+}
+
+/// ERC-721 tokens leave through the collection's `safeTransferFrom`, sent from the dapp to `to_address`
+fn nft_withdrawal_voucher(
+    collection: &Address,
+    to_address: Address,
+    token_id: U256,
+    dapp_address: &str,
+) -> Result<(String, Vec<u8>), ServerError> {
+    let params = vec![ParamType::Address, ParamType::Address, ParamType::Uint(256)];
+    let mut payload_bz = short_signature("safeTransferFrom", &params).to_vec();
+    payload_bz.append(&mut encode(&[
+        Token::Address(parse_address(dapp_address)?),
+        Token::Address(to_address),
+        Token::Uint(token_id),
+    ]));
+    Ok((format!("{:#x}", collection), payload_bz))
+}
+
+async fn withdraw_nft(
+    nft_inventory_manager: &mut NftInventoryManager,
+    room_manager: &RoomManager,
+    ingame_wallet_manager: &IngameWalletManager,
+    outputs: &mut dyn RollupOutputs,
+    dapp_address: &str,
+    msg_sender: &str,
+    req: WithdrawNftRequest,
+) -> Result<FinishStatus, DazzleError> {
+    log::debug!(
+        "Withdraw NFT, owner: \"{}\", collection: \"{}\", token id: \"{}\"",
+        msg_sender,
+        req.collection,
+        req.token_id,
+    );
+
+    //#NOTE: only the L1 wallet which deposited the NFT can take it back
+    let owner = parse_address(msg_sender)?;
+    let collection = parse_address(&req.collection)?;
+    let token_id = parse_token_id(&req.token_id)?;
+
+    //#NOTE: the party of a running room is copied from the inventory, keep the NFT until the owner leaves the room
+    let players = [
+        Some(owner),
+        ingame_wallet_manager.get_ingame_wallet(&owner).copied(),
+    ];
+    if players
+        .iter()
+        .flatten()
+        .any(|player| is_in_room(room_manager, player))
+    {
+        return Err(ServerError::NftInRoom(nft_label(&collection, token_id)).into());
+    }
+
+    let character = nft_inventory_manager.withdraw(&owner, &collection, token_id)?;
+
+    let (destination, payload_bz) =
+        nft_withdrawal_voucher(&collection, owner, token_id, dapp_address)?;
+    send_voucher(outputs, &destination, &payload_bz).await?;
+
+    let nft = NftCharacter {
+        collection: format!("{:#x}", collection),
+        token_id: token_id.to_string(),
+        owner: format!("{:#x}", owner),
+        character,
+    };
+    send_notice(
+        outputs,
+        NoticeType::NftWithdraw,
+        &serde_json::to_string(&nft).unwrap(),
+        &nft.owner,
+        None,
+    )
+    .await
+}
+
+fn parse_address(address: &str) -> Result<Address, ServerError> {
+    Address::from_str(address).map_err(|_| ServerError::InvalidAddress(address.to_owned()))
+}
+
+/// Inspect payload is the hex-encoded query path, see `InspectQuery`
+fn decode_inspect_query(payload: &str) -> Result<InspectQuery, ServerError> {
+    let bz = hex::decode(payload.trim_start_matches("0x")).map_err(|_| ServerError::InvalidHex)?;
+    let path = String::from_utf8(bz).map_err(|_| ServerError::InvalidRequest)?;
+    log::debug!("inspect query: {}", path);
+
+    path.parse()
+}
+
+fn query_state(query: InspectQuery, state: &DappState) -> Result<String, ServerError> {
+    let DappState {
+        room_manager,
+        balance_manager,
+        ingame_wallet_manager,
+        nft_inventory_manager,
+        output_index_manager,
+    } = state;
+
+    let report_json = match query {
+        InspectQuery::FullState => {
+            let room_manager_state = room_manager.get_current_state();
+            let balance_manager_state = balance_manager.get_current_state();
+            let ingame_wallet_manager_state = ingame_wallet_manager.get_current_state();
+            let nft_inventory_manager_state = nft_inventory_manager.get_current_state();
+            let inspect_res = InspectResponse {
+                user_to_room: room_manager_state.user_to_room,
+                balance: balance_manager_state.balance_map,
+                token_balances: balance_manager_state.token_balance_map,
+                voucher_meta: balance_manager_state.voucher_meta_map,
+                room_data: room_manager_state.room_data,
+                ingame_wallets: ingame_wallet_manager_state.wallet_map,
+                nft_inventory: nft_inventory_manager_state.inventory,
+            };
+            serde_json::to_string(&inspect_res)
+        }
+        InspectQuery::Room(uuid) => {
+            let room = room_manager
+                .get_room(&uuid)
+                .ok_or(ServerError::RoomNotFound)?;
+            serde_json::to_string(&room.snapshot())
+        }
+        InspectQuery::PlayerRoom(user) => {
+            let room_id = room_manager
+                .get_uuid_by_player(&user)
+                .or_else(|| room_manager.get_uuid_by_player(&user.to_lowercase()))
+                .cloned();
+            serde_json::to_string(&PlayerRoomResponse { user, room_id })
+        }
+        InspectQuery::Balance(address) => {
+            let owner = parse_address(&address)?;
+            let balance = balance_manager
+                .get_balance(&ETHER, &owner)
+                .map_or_else(|| "0".to_owned(), |b| b.to_string());
+            let tokens = balance_manager.get_token_balances(&owner);
+            serde_json::to_string(&BalanceResponse {
+                address,
+                balance,
+                tokens,
+            })
+        }
+        InspectQuery::Vouchers(address) => {
+            let vouchers = balance_manager
+                .get_voucher_meta(&parse_address(&address)?)
+                .cloned()
+                .unwrap_or_default();
+            serde_json::to_string(&VouchersResponse { address, vouchers })
+        }
+        InspectQuery::Outputs(input_index) => {
+            let outputs = output_index_manager
+                .get_outputs(input_index)
+                .cloned()
+                .unwrap_or(InputOutputs {
+                    input_index,
+                    notices: vec![],
+                    vouchers: vec![],
+                });
+            serde_json::to_string(&outputs)
+        }
+        InspectQuery::Ledger {
+            address,
+            offset,
+            limit,
+        } => {
+            let (total, entries) =
+                balance_manager.get_ledger(&parse_address(&address)?, offset, limit);
+            serde_json::to_string(&LedgerResponse {
+                address,
+                total,
+                offset,
+                entries,
+            })
+        }
+        InspectQuery::Wallet(metamask_wallet) => {
+            let session = ingame_wallet_manager
+                .get_session(&parse_address(&metamask_wallet)?)
+                .map(|session| SessionKeyState {
+                    ingame_wallet: format!("{:#x}", session.ingame_wallet),
+                    scope: session.scope,
+                    expires_at: session.expires_at,
+                });
+            serde_json::to_string(&WalletResponse {
+                metamask_wallet,
+                ingame_wallet: session.as_ref().map(|s| s.ingame_wallet.clone()),
+                session,
+            })
+        }
+        InspectQuery::Inventory(owner) => {
+            let characters = nft_inventory_manager.get_inventory(&parse_address(&owner)?);
+            serde_json::to_string(&InventoryResponse { owner, characters })
+        }
+        InspectQuery::Rooms { mode, status } => {
+            let rooms = room_manager
+                .iter_rooms()
+                .filter(|room| mode.is_none() || mode == Some(room.game_mode))
+                .map(|room| RoomSummary {
+                    uuid: room.uuid,
+                    game_mode: room.game_mode,
+                    status: RoomPhase::of(room),
+                    gamers: room.get_gamers_id(),
+                    stake_token: room.stake_token.clone(),
+                    stake: room.stake.clone(),
+                })
+                .filter(|summary| status.is_none() || status == Some(summary.status))
+                .collect();
+            serde_json::to_string(&RoomsResponse { rooms })
+        }
+    };
+
+    Ok(report_json.unwrap())
+}
+
+async fn inspect_state(
+    request: AdvanceRequest,
+    state: &DappState,
+    outputs: &mut dyn RollupOutputs,
+) -> Result<FinishStatus, DazzleError> {
+    log::debug!("inspect_state");
+
+    let report_json =
+        match decode_inspect_query(&request.payload).and_then(|query| query_state(query, state)) {
+            Ok(report_json) => report_json,
+            Err(e) => return reject_with_report(outputs, e.into()).await,
+        };
+
+    send_chunked_report(outputs, &report_json).await
+}
+
+pub async fn handle_deposit(
+    outputs: &mut dyn RollupOutputs,
+    balance_manager: &mut BalanceManager,
+    portal: &str,
+    deposit: &EtherDeposit,
+) -> Result<FinishStatus, DazzleError> {
+    let depositer = deposit.sender;
+    let deposit_amount = deposit.value;
+
+    log::debug!("Address: {} deposited {} eth", &depositer, deposit_amount);
+    let new_balance = balance_manager.deposit(
+        &ETHER,
+        &depositer,
+        deposit_amount,
+        LedgerEntryKind::Deposit,
+        portal,
+    );
+    log::debug!("New balance: {} eth", &new_balance);
+    let user = format!("{:#x}", depositer);
+    send_notice(
+        outputs,
+        NoticeType::Deposit,
+        "",
+        &user,
+        Some(new_balance.to_string()),
+    )
+    .await
+}
+
+pub async fn handle_erc20_deposit(
+    outputs: &mut dyn RollupOutputs,
+    balance_manager: &mut BalanceManager,
+    portal: &str,
+    bz_payload: &[u8],
+) -> Result<FinishStatus, DazzleError> {
+    let Erc20Deposit {
+        success,
+        token,
+        sender: depositer,
+        amount: deposit_amount,
+        ..
+    } = match decode_erc20_deposit(bz_payload) {
+        Ok(deposit) => deposit,
+        Err(e) => return reject_with_report(outputs, e.into()).await,
+    };
+
+    if !success {
+        log::debug!("ERC-20 portal transfer failed, nothing deposited");
+        return Ok(FinishStatus::Reject);
+    }
+
+    log::debug!(
+        "Address: {} deposited {} of token {}",
+        &depositer,
+        deposit_amount,
+        &token
+    );
+    let new_balance = balance_manager.deposit(
+        &token,
+        &depositer,
+        deposit_amount,
+        LedgerEntryKind::Deposit,
+        portal,
+    );
+    let user = format!("{:#x}", depositer);
+    send_notice(
+        outputs,
+        NoticeType::Erc20Deposit,
+        &format!("{:#x}", token),
+        &user,
+        Some(new_balance.to_string()),
+    )
+    .await
+}
+
+/// The character stats of a deposited token are the json `CharacterV2` carried in execLayerData
+/// Only tokens of the character collections are taken, their stats are rolled from the token rather than sent by the depositor
+pub async fn handle_erc721_deposit(
+    outputs: &mut dyn RollupOutputs,
+    nft_inventory_manager: &mut NftInventoryManager,
+    character_collections: &[String],
+    bz_payload: &[u8],
+) -> Result<FinishStatus, DazzleError> {
+    let Erc721Deposit {
+        token: collection,
+        sender: depositer,
+        token_id,
+        ..
+    } = match decode_erc721_deposit(bz_payload) {
+        Ok(deposit) => deposit,
+        Err(e) => return reject_with_report(outputs, e.into()).await,
+    };
+
+    if !character_collections
+        .iter()
+        .any(|allowed| parse_address(allowed).ok() == Some(collection))
+    {
+        let e = ServerError::UnknownNftCollection(format!("{:#x}", collection));
+        return reject_with_report(outputs, e.into()).await;
+    }
+    let character = Some(roll_nft_character(&collection, token_id));
+
+    log::debug!(
+        "Address: {} deposited NFT {}",
+        &depositer,
+        nft_label(&collection, token_id)
+    );
+    nft_inventory_manager.deposit(&depositer, &collection, token_id, character.clone());
+
+    let nft = NftCharacter {
+        collection: format!("{:#x}", collection),
+        token_id: token_id.to_string(),
+        owner: format!("{:#x}", depositer),
+        character,
+    };
+    send_notice(
+        outputs,
+        NoticeType::NftDeposit,
+        &serde_json::to_string(&nft).unwrap(),
+        &nft.owner,
+        None,
+    )
+    .await
+}
+
+fn room_stake_token(room: Option<&Room>) -> Result<Address, ServerError> {
+    parse_token(room.and_then(|room| room.stake_token.as_deref()))
+}
+
+fn room_stake(room: &Room) -> Result<U256, ServerError> {
+    U256::from_dec_str(&room.stake).map_err(|_| ServerError::InvalidCurrency(room.stake.clone()))
+}
+
+/// The stake chosen by a room creator, bounded by MIN_STAKE and MAX_STAKE
+fn parse_stake(stake: Option<&str>) -> Result<U256, ServerError> {
+    let stake_str = stake.unwrap_or(STAKE);
+    let stake = U256::from_dec_str(stake_str)
+        .map_err(|_| ServerError::InvalidCurrency(stake_str.to_owned()))?;
+
+    let min_stake = U256::from_dec_str(MIN_STAKE).expect("Invalid MIN_STAKE value in config.rs!");
+    let max_stake = U256::from_dec_str(MAX_STAKE).expect("Invalid MAX_STAKE value in config.rs!");
+    if stake < min_stake || stake > max_stake {
+        return Err(ServerError::InvalidStake(
+            stake.to_string(),
+            MIN_STAKE.to_owned(),
+            MAX_STAKE.to_owned(),
+        ));
+    }
+
+    Ok(stake)
+}
+
+/// Entering a room takes its stake, in the currency of that room.
+/// Players already in a room have paid, their stake is in escrow.
+fn request_stake(
+    room_manager: &RoomManager,
+    request: &DazzleRequest,
+) -> Result<Option<(Address, U256)>, ServerError> {
+    match request {
+        //#NOTE: a player already in a room reconnects to it, nothing is staked again
+        DazzleRequest::CreatePrivateRoom(req)
+            if room_manager
+                .get_room_status(&req.user.to_lowercase())
+                .is_some() =>
+        {
+            Ok(None)
+        }
+        DazzleRequest::CreatePrivateRoom(req) => Ok(Some((
+            parse_token(req.stake_token.as_deref())?,
+            parse_stake(req.stake.as_deref())?,
+        ))),
+        DazzleRequest::JoinPrivateRoom(req) => {
+            //#NOTE: an unknown code is reported by join_private_room itself
+            match room_manager
+                .get_uuid_by_private_code(&req.private_code.to_uppercase())
+                .and_then(|uuid| room_manager.get_room(uuid))
+            {
+                Some(room) => Ok(Some((room_stake_token(Some(room))?, room_stake(room)?))),
+                None => Ok(None),
+            }
+        }
+        _ => Ok(None),
+    }
+}
+
+/// The player `msg_sender` acts for, the `user` of a room request must be that player
+fn auth_msg_sender(
+    balance_manager: &BalanceManager,
+    ingame_wallet_manager: &IngameWalletManager,
+    msg_sender: &str,
+    user: &str,
+    stake: Option<(Address, U256)>,
+) -> Result<String, ServerError> {
+    let msg_sender_addr = Address::from_str(msg_sender)
+        .map_err(|_| ServerError::InvalidAddress(msg_sender.to_owned()))?;
+
+    //#NOTE: the player is the in-game wallet, a metamask wallet with an attached in-game wallet may act for it by itself, e.g. through a deposit's exec-layer data
+    let player = match ingame_wallet_manager.get_session_by_key(&msg_sender_addr) {
+        Some((_, session)) => {
+            if stake.is_some() && session.scope != SessionScope::GameplayAndStake {
+                return Err(ServerError::SessionScopeDenied(format!(
+                    "{:#x}",
+                    msg_sender_addr
+                )));
+            }
+            msg_sender_addr
+        }
+        None => *ingame_wallet_manager
+            .get_ingame_wallet(&msg_sender_addr)
+            .ok_or_else(|| ServerError::InvalidIngameWallet(msg_sender_addr.to_string()))?,
+    };
+
+    if Address::from_str(user).ok() != Some(player) {
+        return Err(ServerError::UserMismatch(
+            msg_sender.to_owned(),
+            user.to_owned(),
+        ));
+    }
+    let player = format!("{:#x}", player);
+
+    let (stake_token, stake) = match stake {
+        Some(stake) => stake,
+        None => return Ok(player),
+    };
+
+    //#NOTE: the stake is paid from the balance `lock_room_stake` takes it from, the metamask wallet for a session key
+    let default_balance = &U256::from(0);
+    let balance = balance_manager
+        .get_balance(
+            &stake_token,
+            &resolve_account(ingame_wallet_manager, msg_sender)?,
+        )
+        .unwrap_or(default_balance);
+
+    if balance < &stake {
+        return Err(ServerError::InsufficientTournamentFee(
+            balance.to_string(),
+            stake.to_string(),
+        ));
+    }
+
+    Ok(player)
+}
+
+pub async fn advance_state(
+    request: AdvanceRequest,
+    state: &mut DappState,
+    outputs: &mut dyn RollupOutputs,
+    config: &RollupConfig,
+    dapp_address: &str,
+) -> Result<FinishStatus, DazzleError> {
+    log::debug!("advance_state");
+
+    if request.metadata.is_none() {
+        log::debug!("No metadata inside the payload");
+        return Ok(FinishStatus::Reject);
+    }
+
+    let metadata = request.metadata.unwrap();
+    let msg_sender = metadata.msg_sender.clone();
+    log::debug!("advance_state.msg_sender: {}", msg_sender);
+    state.balance_manager.start_input(&metadata);
+    //#NOTE: rolled back with the rest of a rejected input like the machine does, the next input expires them again
+    state
+        .ingame_wallet_manager
+        .expire_sessions(metadata.timestamp);
+
+    let hex_payload = request.payload.trim_start_matches("0x");
+    log::debug!("hex_payload: {}", &hex_payload);
+
+    if msg_sender.to_lowercase() == config.ether_portal_contract.to_lowercase() {
+        log::debug!("handle_deposit");
+
+        let bz_payload = hex::decode(hex_payload).map_err(|_| ServerError::InvalidHex)?;
+        let deposit = match decode_ether_deposit(&bz_payload) {
+            Ok(deposit) => deposit,
+            Err(e) => return reject_with_report(outputs, e.into()).await,
+        };
+
+        let status =
+            handle_deposit(outputs, &mut state.balance_manager, &msg_sender, &deposit).await?;
+        if deposit.exec_layer_data.is_empty() {
+            return Ok(status);
+        }
+        return dispatch_deposit_request(deposit, state, outputs, dapp_address, config, &metadata)
+            .await;
+    }
+
+    if msg_sender.to_lowercase() == config.erc20_portal_contract.to_lowercase() {
+        log::debug!("handle_erc20_deposit");
+
+        let bz_payload = hex::decode(hex_payload).map_err(|_| ServerError::InvalidHex)?;
+        return handle_erc20_deposit(
+            outputs,
+            &mut state.balance_manager,
+            &msg_sender,
+            &bz_payload,
+        )
+        .await;
+    }
+
+    if msg_sender.to_lowercase() == config.erc721_portal_contract.to_lowercase() {
+        log::debug!("handle_erc721_deposit");
+
+        let bz_payload = hex::decode(hex_payload).map_err(|_| ServerError::InvalidHex)?;
+        return handle_erc721_deposit(
+            outputs,
+            &mut state.nft_inventory_manager,
+            &config.character_collections,
+            &bz_payload,
+        )
+        .await;
+    }
+
+    let bz_payload = hex::decode(hex_payload).map_err(|e| {
+        log::error!("Failed to decode hex payload: {}", e.to_string());
+        ServerError::InvalidHex
+    })?;
+
+    let dazzle_request = decode_dazzle_request(&bz_payload)?;
+    dispatch_dazzle_request(
+        dazzle_request,
+        state,
+        outputs,
+        dapp_address,
+        config,
+        &metadata,
+    )
+    .await
+}
+
+/// Run the request embedded in the exec-layer data of an Ether deposit on behalf of the depositor.
+///
+/// The Ether is already held by the dapp on L1, so the deposit is kept even when the request fails.
+/// The request's notices and vouchers are only emitted when it is accepted, its error report always is.
+async fn dispatch_deposit_request(
+    deposit: EtherDeposit,
+    state: &mut DappState,
+    outputs: &mut dyn RollupOutputs,
+    dapp_address: &str,
+    config: &RollupConfig,
+    metadata: &AdvanceMetadata,
+) -> Result<FinishStatus, DazzleError> {
+    let dazzle_request = match decode_dazzle_request(&deposit.exec_layer_data) {
+        Ok(request) => request,
+        Err(e) => {
+            log::error!("Report Error: {}", &e);
+            send_report(outputs, &serialize_error_report(e.into())).await?;
+            return Ok(FinishStatus::Accept);
+        }
+    };
+
+    let depositor_metadata = AdvanceMetadata {
+        msg_sender: format!("{:#x}", deposit.sender),
+        ..metadata.clone()
+    };
+
+    let checkpoint = state.checkpoint();
+    let mut recorder = OutputRecorder::continuing(outputs);
+    let status = dispatch_dazzle_request(
+        dazzle_request,
+        state,
+        &mut recorder,
+        dapp_address,
+        config,
+        &depositor_metadata,
+    )
+    .await;
+
+    match status {
+        Ok(FinishStatus::Accept) => {
+            recorder.forward(outputs, metadata.input_index).await?;
+        }
+        Ok(FinishStatus::Reject) | Err(_) => {
+            log::debug!("Deposit request rejected, keeping the deposit only");
+            state.rollback(checkpoint);
+            recorder.forward_reports(outputs).await?;
+        }
+    }
+
+    Ok(FinishStatus::Accept)
+}
+
+/// Dispatch a decoded request, a batch runs its operations in order
+async fn dispatch_dazzle_request(
+    dazzle_request: DazzleRequest,
+    state: &mut DappState,
+    outputs: &mut dyn RollupOutputs,
+    dapp_address: &str,
+    config: &RollupConfig,
+    metadata: &AdvanceMetadata,
+) -> Result<FinishStatus, DazzleError> {
+    log::debug!("operation: {}", dazzle_request.operation());
+
+    let new_rng_seed = metadata.timestamp + metadata.input_index;
+
+    let requests = match dazzle_request {
+        DazzleRequest::Batch(requests) => requests,
+        request => {
+            return dispatch_operation(
+                request,
+                state,
+                outputs,
+                dapp_address,
+                config,
+                metadata,
+                new_rng_seed,
+            )
+            .await
+        }
+    };
+
+    if requests.is_empty() || requests.len() > MAX_BATCH_SIZE {
+        return reject_with_report(
+            outputs,
+            ServerError::InvalidBatch(format!("expected 1 to {} operations", MAX_BATCH_SIZE))
+                .into(),
+        )
+        .await;
+    }
+
+    //#NOTE: all-or-nothing, the first rejected operation rejects the whole input and process_advance_state rolls the state back
+    let mut status = FinishStatus::Accept;
+    for (index, request) in requests.into_iter().enumerate() {
+        log::debug!("batch operation #{}: {}", index, request.operation());
+
+        //#NOTE: every operation needs its own seed, timestamps stay below 2^32 so this never collides with another input's seed
+        let seed = new_rng_seed.wrapping_add((index as u64) << 32);
+        status = dispatch_operation(
+            request,
+            state,
+            outputs,
+            dapp_address,
+            config,
+            metadata,
+            seed,
+        )
+        .await?;
+
+        if let FinishStatus::Reject = status {
+            break;
+        }
+    }
+
+    Ok(status)
+}
+
+/// Dispatch one operation, a relayed action runs as the player who signed it
+async fn dispatch_operation(
+    dazzle_request: DazzleRequest,
+    state: &mut DappState,
+    outputs: &mut dyn RollupOutputs,
+    dapp_address: &str,
+    config: &RollupConfig,
+    metadata: &AdvanceMetadata,
+    new_rng_seed: u64,
+) -> Result<FinishStatus, DazzleError> {
+    let (dazzle_request, metadata) = match dazzle_request {
+        DazzleRequest::Relayed(req) => {
+            match unwrap_relayed_request(state, dapp_address, config.chain_id, metadata, req) {
+                Ok(unwrapped) => unwrapped,
+                Err(e) => return reject_with_report(outputs, e.into()).await,
+            }
+        }
+        request => (request, metadata.clone()),
+    };
+
+    dispatch_request(
+        dazzle_request,
+        state,
+        outputs,
+        dapp_address,
+        config,
+        &metadata,
+        new_rng_seed,
+    )
+    .await
+}
+
+/// Check the signature, turn and nonce of a relayed action, and return it with the player as its sender
+fn unwrap_relayed_request(
+    state: &mut DappState,
+    dapp_address: &str,
+    chain_id: u64,
+    metadata: &AdvanceMetadata,
+    req: RelayedRequest,
+) -> Result<(DazzleRequest, AdvanceMetadata), ServerError> {
+    //#NOTE: the dapp address is the EIP-712 verifying contract, relayed actions are refused until the address relay told it
+    let dapp_address = parse_address(dapp_address)?;
+    let player = parse_address(req.action.user())?;
+    log::debug!(
+        "Relayed action of \"{:#x}\", relayer: \"{}\", nonce: {}",
+        player,
+        metadata.msg_sender,
+        req.nonce
+    );
+
+    let typed_data = relayed_action_typed_data(&dapp_address, chain_id, &req)?;
+    verify_signature(H256(keccak256(typed_data)), &req.signature, &player)?;
+
+    let room = state
+        .room_manager
+        .get_room(req.action.room_id())
+        .ok_or(ServerError::RoomNotFound)?;
+    let turn = room.game.turn as u64;
+    if req.turn != turn {
+        return Err(ServerError::StaleRelayedAction(req.turn, turn));
+    }
+    state
+        .ingame_wallet_manager
+        .use_relay_nonce(&player, req.nonce)?;
+
+    let player_metadata = AdvanceMetadata {
+        msg_sender: format!("{:#x}", player),
+        ..metadata.clone()
+    };
+    Ok((req.action.into(), player_metadata))
+}
+
+async fn dispatch_request(
+    dazzle_request: DazzleRequest,
+    state: &mut DappState,
+    outputs: &mut dyn RollupOutputs,
+    dapp_address: &str,
+    config: &RollupConfig,
+    metadata: &AdvanceMetadata,
+    new_rng_seed: u64,
+) -> Result<FinishStatus, DazzleError> {
+    let DappState {
+        room_manager,
+        balance_manager,
+        ingame_wallet_manager,
+        nft_inventory_manager,
+        ..
+    } = state;
+    let msg_sender = &metadata.msg_sender;
+    let stake = match request_stake(room_manager, &dazzle_request) {
+        Ok(stake) => stake,
+        Err(e) => return reject_with_report(outputs, e.into()).await,
+    };
+
+    match dazzle_request {
+        DazzleRequest::CreatePrivateRoom(mut req) => {
+            req.user = match auth_msg_sender(
+                balance_manager,
+                ingame_wallet_manager,
+                msg_sender,
+                &req.user,
+                stake,
+            ) {
+                Ok(player) => player,
+                Err(e) => return reject_with_report(outputs, e.into()).await,
+            };
+
+            match create_private_room(
+                room_manager,
+                balance_manager,
+                nft_inventory_manager,
+                ingame_wallet_manager,
+                outputs,
+                msg_sender,
+                req,
+                new_rng_seed,
+                config.platform_fee_bps,
+            )
+            .await
+            {
+                Ok(state) => Ok(state),
+                Err(e) => reject_with_report(outputs, e).await,
+            }
+        }
+        DazzleRequest::JoinPrivateRoom(mut req) => {
+            req.user = match auth_msg_sender(
+                balance_manager,
+                ingame_wallet_manager,
+                msg_sender,
+                &req.user,
+                stake,
+            ) {
+                Ok(player) => player,
+                Err(e) => return reject_with_report(outputs, e.into()).await,
+            };
+
+            match join_private_room(
+                room_manager,
+                balance_manager,
+                nft_inventory_manager,
+                ingame_wallet_manager,
+                outputs,
+                msg_sender,
+                req,
+                new_rng_seed,
+            )
+            .await
+            {
+                Ok(state) => Ok(state),
+                Err(e) => reject_with_report(outputs, e).await,
+            }
+        }
+        DazzleRequest::CancelRoom(mut req) => {
+            req.user = match auth_msg_sender(
+                balance_manager,
+                ingame_wallet_manager,
+                msg_sender,
+                &req.user,
+                stake,
+            ) {
+                Ok(player) => player,
+                Err(e) => return reject_with_report(outputs, e.into()).await,
+            };
+
+            match cancel_room(room_manager, balance_manager, outputs, req).await {
+                Ok(state) => Ok(state),
+                Err(e) => reject_with_report(outputs, e).await,
+            }
+        }
+
+        DazzleRequest::GameOver(mut req) => {
+            req.user = match auth_msg_sender(
+                balance_manager,
+                ingame_wallet_manager,
+                msg_sender,
+                &req.user,
+                stake,
+            ) {
+                Ok(player) => player,
+                Err(e) => return reject_with_report(outputs, e.into()).await,
+            };
+
+            match game_over(room_manager, balance_manager, outputs, req).await {
+                Ok(state) => Ok(state),
+                Err(e) => reject_with_report(outputs, e).await,
+            }
+        }
+        DazzleRequest::Move(mut req) => {
+            req.user = match auth_msg_sender(
+                balance_manager,
+                ingame_wallet_manager,
+                msg_sender,
+                &req.user,
+                stake,
+            ) {
+                Ok(player) => player,
+                Err(e) => return reject_with_report(outputs, e.into()).await,
+            };
+
+            match action_move(room_manager, balance_manager, outputs, req, new_rng_seed).await {
+                Ok(state) => Ok(state),
+                Err(e) => reject_with_report(outputs, e).await,
+            }
+        }
+        DazzleRequest::ActivateSkill(mut req) => {
+            req.user = match auth_msg_sender(
+                balance_manager,
+                ingame_wallet_manager,
+                msg_sender,
+                &req.user,
+                stake,
+            ) {
+                Ok(player) => player,
+                Err(e) => return reject_with_report(outputs, e.into()).await,
+            };
+
+            match activate_skill(room_manager, balance_manager, outputs, req, new_rng_seed).await {
+                Ok(state) => Ok(state),
+                Err(e) => reject_with_report(outputs, e).await,
+            }
+        }
+        DazzleRequest::QuitGame(mut req) => {
+            req.user = match auth_msg_sender(
+                balance_manager,
+                ingame_wallet_manager,
+                msg_sender,
+                &req.user,
+                stake,
+            ) {
+                Ok(player) => player,
+                Err(e) => return reject_with_report(outputs, e.into()).await,
+            };
+
+            match quit_game(room_manager, balance_manager, outputs, req).await {
+                Ok(state) => Ok(state),
+                Err(e) => reject_with_report(outputs, e).await,
+            }
+        }
+        DazzleRequest::AttachIngameWallet(req) => {
+            match attach_ingame_wallet(
+                ingame_wallet_manager,
+                outputs,
+                dapp_address,
+                config.chain_id,
+                metadata.clone(),
+                req,
+            )
+            .await
+            {
+                Ok(state) => Ok(state),
+                Err(e) => reject_with_report(outputs, e).await,
+            }
+        }
+        DazzleRequest::RotateSessionKey(req) => {
+            match rotate_session_key(
+                ingame_wallet_manager,
+                room_manager,
+                outputs,
+                dapp_address,
+                config.chain_id,
+                metadata.clone(),
+                req,
+            )
+            .await
+            {
+                Ok(state) => Ok(state),
+                Err(e) => reject_with_report(outputs, e).await,
+            }
+        }
+        DazzleRequest::RevokeSessionKey(req) => {
+            match revoke_session_key(ingame_wallet_manager, outputs, msg_sender, req).await {
+                Ok(state) => Ok(state),
+                Err(e) => reject_with_report(outputs, e).await,
+            }
+        }
+        DazzleRequest::TransferBalance(req) => {
+            match transfer(
+                balance_manager,
+                ingame_wallet_manager,
+                outputs,
+                dapp_address,
+                metadata.clone(),
+                req,
+            )
+            .await
+            {
+                Ok(state) => Ok(state),
+                Err(e) => reject_with_report(outputs, e).await,
+            }
+        }
+        DazzleRequest::InternalTransfer(req) => {
+            match internal_transfer(
+                balance_manager,
+                ingame_wallet_manager,
+                outputs,
+                msg_sender,
+                req,
+            )
+            .await
+            {
+                Ok(state) => Ok(state),
+                Err(e) => reject_with_report(outputs, e).await,
+            }
+        }
+        DazzleRequest::Withdraw(req) => {
+            match withdraw(
+                balance_manager,
+                ingame_wallet_manager,
+                outputs,
+                dapp_address,
+                metadata.clone(),
+                req,
+            )
+            .await
+            {
+                Ok(state) => Ok(state),
+                Err(e) => reject_with_report(outputs, e).await,
+            }
+        }
+        DazzleRequest::WithdrawNft(req) => {
+            match withdraw_nft(
+                nft_inventory_manager,
+                room_manager,
+                ingame_wallet_manager,
+                outputs,
+                dapp_address,
+                msg_sender,
+                req,
+            )
+            .await
+            {
+                Ok(state) => Ok(state),
+                Err(e) => reject_with_report(outputs, e).await,
+            }
+        }
+        DazzleRequest::MarkVoucherExecuted(req) => {
+            match mark_voucher_executed(
+                balance_manager,
+                ingame_wallet_manager,
+                outputs,
+                msg_sender,
+                req,
+            )
+            .await
+            {
+                Ok(state) => Ok(state),
+                Err(e) => reject_with_report(outputs, e).await,
+            }
+        }
+        //#NOTE: relayed actions are unwrapped by dispatch_operation before they get here
+        DazzleRequest::Relayed(_) => {
+            reject_with_report(outputs, ServerError::InvalidRequest.into()).await
+        }
+        DazzleRequest::Batch(_) => {
+            reject_with_report(
+                outputs,
+                ServerError::InvalidBatch(String::from("batches can't be nested")).into(),
+            )
+            .await
+        }
+    }
+}
+
+/// Decode an input into a `DazzleRequest`.
+///
+/// `VersionedRequest` is the current format. Inputs without a version are legacy `GameRequest`s,
+/// whose base64 data is unpacked and parsed into the same typed request.
+fn decode_dazzle_request(bz: &[u8]) -> Result<DazzleRequest, ServerError> {
+    let value: Value = serde_json::from_slice(bz).map_err(|_| ServerError::InvalidRequest)?;
+
+    if let Some(version) = value.get("version") {
+        let version: u32 = version
+            .as_u64()
+            .ok_or(ServerError::InvalidRequest)?
+            .try_into()
+            .map_err(|_| ServerError::InvalidRequest)?;
+        if version != DAZZLE_REQUEST_VERSION {
+            return Err(ServerError::UnsupportedRequestVersion(version));
+        }
+
+        let versioned: VersionedRequest = serde_json::from_value(value).map_err(|e| {
+            log::debug!("Failed to deserialize VersionedRequest: {}", e);
+            ServerError::InvalidRequest
+        })?;
+        return Ok(versioned.request);
+    }
+
+    let game_req: GameRequest =
+        serde_json::from_value(value).map_err(|_| ServerError::InvalidRequest)?;
+
+    if game_req.operation.parse::<DazzleOperation>().is_err() {
+        log::debug!("Not supported action: {}", game_req.operation);
+        return Err(ServerError::InvalidRequest);
+    }
+
+    let data_bz = general_purpose::STANDARD
+        .decode(&game_req.data)
+        .map_err(|e| {
+            log::debug!(
+                "Failed to decode base64 payload: {} - {}",
+                game_req.data.clone(),
+                e.to_string()
+            );
+            ServerError::InvalidRequest
+        })?;
+    let data: Value = serde_json::from_slice(&data_bz).map_err(|_| ServerError::InvalidJson)?;
+
+    serde_json::from_value(json!({ "operation": game_req.operation, "data": data })).map_err(|e| {
+        log::debug!(
+            "Failed to deserialize {} request: {}",
+            game_req.operation,
+            e
+        );
+        ServerError::InvalidRequest
+    })
+}
+
+fn serialize_error_report(err: DazzleError) -> String {
+    let dazzle_report = DazzleReport {
+        error_message: err.to_string(),
+    };
+    serde_json::to_string(&dazzle_report).unwrap()
+}
+
+/// Failed operations still report their error, but the input is rejected so its notices and vouchers are discarded
+async fn reject_with_report(
+    outputs: &mut dyn RollupOutputs,
+    err: DazzleError,
+) -> Result<FinishStatus, DazzleError> {
+    log::error!("Report Error: {}", &err);
+    send_report(outputs, &serialize_error_report(err)).await?;
+    Ok(FinishStatus::Reject)
+}
+
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    match panic.downcast::<String>() {
+        Ok(message) => *message,
+        Err(panic) => match panic.downcast::<&'static str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => String::from("unknown panic"),
+        },
+    }
+}
+
+/// Report a panic caught while handling a request.
+///
+/// It goes out as a report rather than through /exception, which would end the dapp under Rollups v1, the input is
+/// rejected instead and the next one handled as usual.
+async fn report_panic(
+    outputs: &mut dyn RollupOutputs,
+    request_type: RequestType,
+    input_index: Option<u64>,
+    panic: Box<dyn Any + Send>,
+) {
+    let exception = DazzleException {
+        request_type: request_type.to_string(),
+        input_index,
+        error_message: panic_message(panic),
+    };
+    log::error!(
+        "Panic occurred in {}: {}",
+        exception.request_type,
+        exception.error_message
+    );
+
+    if let Err(e) = send_report(outputs, &serde_json::to_string(&exception).unwrap()).await {
+        log::error!("Failed to report panic: {}", e);
+    }
+}
+
+/// Run one advance input as a transaction.
+///
+/// The Cartesi machine reverts itself when an input is rejected, but host mode keeps running the same process.
+/// So a checkpoint of the dapp state is taken beforehand and rolled back to whenever the input ends in Reject or an error,
+/// including the sessions the input expired, so host mode holds the same state as the machine after every input.
+/// A panicking handler is caught, reported and rejected like any other failure.
+pub async fn process_advance_state(
+    request: AdvanceRequest,
+    state: &mut DappState,
+    outputs: &mut dyn RollupOutputs,
+    config: &RollupConfig,
+    dapp_address: &str,
+) -> FinishStatus {
+    let checkpoint = state.checkpoint();
+    let input_index = request.metadata.as_ref().map(|m| m.input_index);
+
+    let result = AssertUnwindSafe(advance_and_index(
+        request,
+        state,
+        outputs,
+        config,
+        dapp_address,
+    ))
+    .catch_unwind()
+    .await;
+
+    let status = match result {
+        Ok(Ok(status)) => status,
+        Ok(Err(e)) => {
+            log::error!("Error occurred in advance_state: {}", e);
+            FinishStatus::Reject
+        }
+        Err(panic) => {
+            report_panic(outputs, RequestType::AdvanceState, input_index, panic).await;
+            FinishStatus::Reject
+        }
+    };
+
+    if let FinishStatus::Reject = status {
+        log::debug!("Input rejected, restoring the previous dapp state");
+        state.rollback(checkpoint);
+        if let Some(input_index) = input_index {
+            state.output_index_manager.record_rejection(input_index);
+        }
+    }
+    state.commit();
+
+    status
+}
+
+/// Runs `advance_state` against a recorder, so the index the rollup server gives every output of an accepted input is kept in the state.
+/// Outputs of a rejected input are discarded by the rollup server anyway, only its reports are forwarded.
+async fn advance_and_index(
+    request: AdvanceRequest,
+    state: &mut DappState,
+    outputs: &mut dyn RollupOutputs,
+    config: &RollupConfig,
+    dapp_address: &str,
+) -> Result<FinishStatus, DazzleError> {
+    let input_index = request.metadata.as_ref().map(|m| m.input_index);
+    let mut recorder = OutputRecorder::continuing(outputs);
+    let result = match advance_state(request, state, &mut recorder, config, dapp_address).await {
+        Ok(FinishStatus::Accept) => check_accounting(state, &mut recorder).await,
+        result => result,
+    };
+
+    match (&result, input_index) {
+        (Ok(FinishStatus::Accept), Some(input_index)) => {
+            //#NOTE: recorded once forwarded, the input can't be rejected anymore so the indices are never rolled back
+            let input_outputs = recorder.forward(outputs, input_index).await?;
+            state.output_index_manager.record(input_outputs);
+        }
+        _ => recorder.forward_reports(outputs).await?,
+    }
+    result
+}
+
+/// With the `strict-accounting` feature, an accepted input that breaks the conservation of funds is rejected instead
+#[cfg(feature = "strict-accounting")]
+async fn check_accounting(
+    state: &DappState,
+    outputs: &mut dyn RollupOutputs,
+) -> Result<FinishStatus, DazzleError> {
+    match state.balance_manager.check_accounting() {
+        Ok(()) => Ok(FinishStatus::Accept),
+        Err(e) => reject_with_report(outputs, e.into()).await,
+    }
+}
+
+#[cfg(not(feature = "strict-accounting"))]
+async fn check_accounting(
+    _state: &DappState,
+    _outputs: &mut dyn RollupOutputs,
+) -> Result<FinishStatus, DazzleError> {
+    Ok(FinishStatus::Accept)
+}
+
+/// Runs `inspect_state`, a panicking handler is caught, reported and rejected
+pub async fn process_inspect_state(
+    request: AdvanceRequest,
+    state: &DappState,
+    outputs: &mut dyn RollupOutputs,
+) -> FinishStatus {
+    let result = AssertUnwindSafe(inspect_state(request, state, outputs))
+        .catch_unwind()
+        .await;
+
+    match result {
+        Ok(Ok(status)) => status,
+        Ok(Err(e)) => {
+            log::error!("Error occurred in inspect_state: {}", e);
+            FinishStatus::Reject
+        }
+        Err(panic) => {
+            report_panic(outputs, RequestType::InspectState, None, panic).await;
+            FinishStatus::Reject
+        }
+    }
+}
+
+//#NOTE: the chain of the local devnet
+pub const DEFAULT_CHAIN_ID: u64 = 31337;
+
+pub struct RollupConfig {
+    pub http_dispatcher_url: String,
+    pub address_relay_contract: String,
+    pub ether_portal_contract: String,
+    pub erc20_portal_contract: String,
+    pub erc721_portal_contract: String,
+    //#NOTE: the ERC-721 collections of the game characters, deposits of any other collection are rejected
+    pub character_collections: Vec<String>,
+    pub dapp_address: String,
+    //#NOTE: part of the EIP-712 domain of relayed actions and of the attach message, so signatures can't be replayed on another chain
+    pub chain_id: u64,
+    //#NOTE: basis points of the pot of a staked match credited to ADMIN_WALLET_ADDRESS, fixed per room when it's created
+    pub platform_fee_bps: u32,
+    //#NOTE: host mode only, where a restarted dapp would lose its state, the cartesi machine keeps it in prod mode
+    pub snapshot_path: Option<String>,
+    //#NOTE: a snapshot is written after every `snapshot_interval` advance inputs, DEFAULT_SNAPSHOT_INTERVAL unless set.
+    //Above 1 is only safe when the rollup server replays the inputs from the start after a restart, set it to 1 otherwise
+    pub snapshot_interval: u64,
+}
+
+impl RollupConfig {
+    pub fn from_env() -> Self {
+        RollupConfig {
+            http_dispatcher_url: env::var("ROLLUP_HTTP_SERVER_URL")
+                .unwrap_or(String::from("http://127.0.0.1:5004")),
+            //#NOTE: These address can be known beforehand by using `sunodo address-book` command, and is suggested to hardcoded in dapp
+            address_relay_contract: env::var("DAPP_ADDRESS_RELAY_CONTRACT")
+                .unwrap_or(String::from("0xF5DE34d6BbC0446E2a45719E718efEbaaE179daE")),
+            ether_portal_contract: env::var("DAPP_ETHER_PORTAL_CONTRACT")
+                .unwrap_or(String::from("0xFfdbe43d4c855BF7e0f105c400A50857f53AB044")),
+            erc20_portal_contract: env::var("DAPP_ERC20_PORTAL_CONTRACT")
+                .unwrap_or(String::from("0x9C21AEb2093C32DDbC53eEF24B873BDCd1aDa1DB")),
+            erc721_portal_contract: env::var("DAPP_ERC721_PORTAL_CONTRACT")
+                .unwrap_or(String::from("0x237F8DD094C0e47f4236f12b4Fa01d6Dae89fb87")),
+            character_collections: env::var("DAZZLE_CHARACTER_COLLECTIONS")
+                .map(|collections| {
+                    collections
+                        .split(',')
+                        .map(|collection| collection.trim().to_owned())
+                        .filter(|collection| !collection.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            dapp_address: env::var("DAZZLE_DAPP_CONTRACT").unwrap_or(String::from("")),
+            chain_id: env::var("DAZZLE_CHAIN_ID")
+                .ok()
+                .and_then(|chain_id| chain_id.parse().ok())
+                .unwrap_or(DEFAULT_CHAIN_ID),
+            platform_fee_bps: env::var("DAZZLE_PLATFORM_FEE_BPS")
+                .ok()
+                .and_then(|bps| bps.parse().ok())
+                .filter(|bps| *bps <= BPS_UNIT)
+                .unwrap_or(DEFAULT_PLATFORM_FEE_BPS),
+            snapshot_path: env::var("DAZZLE_SNAPSHOT_PATH").ok(),
+            snapshot_interval: env::var("DAZZLE_SNAPSHOT_INTERVAL")
+                .ok()
+                .and_then(|interval| interval.parse().ok())
+                .unwrap_or(DEFAULT_SNAPSHOT_INTERVAL),
+        }
+    }
+
+    /// Inputs from the address relay carry the dapp address instead of a game request
+    pub fn relayed_dapp_address(&self, rollup: &RollupResponse) -> Option<String> {
+        let metadata = rollup.data.metadata.as_ref()?;
+        match metadata.msg_sender.to_lowercase() == self.address_relay_contract.to_lowercase() {
+            true => Some(rollup.data.payload.clone()),
+            false => None,
+        }
+    }
+}
+
+/// The state of the last snapshot with its dapp address and the index of the next input it needs, a fresh state
+/// when there is none. Without a snapshot path the input indices aren't checked
+fn restore_state(config: &RollupConfig) -> Result<(DappState, String, Option<u64>), ServerError> {
+    let path = match &config.snapshot_path {
+        Some(path) => path,
+        None => return Ok((DappState::new(), config.dapp_address.clone(), None)),
+    };
+    let fresh_state = (DappState::new(), config.dapp_address.clone(), Some(0));
+
+    match read_snapshot(Path::new(path))? {
+        Some(snapshot) => {
+            log::info!(
+                "Restored snapshot {}, resuming after input {}",
+                path,
+                snapshot.input_index
+            );
+            let dapp_address = match snapshot.dapp_address.is_empty() {
+                true => config.dapp_address.clone(),
+                false => snapshot.dapp_address,
+            };
+            Ok((snapshot.state, dapp_address, Some(snapshot.input_index + 1)))
+        }
+        None => Ok(fresh_state),
+    }
+}
+
+/// Refuse an input the dapp can't apply to its state, throwing `/exception` so the rollup node stops on it instead of
+/// taking the refusal for an input the dapp handled
+async fn refuse_input(
+    outputs: &mut dyn RollupOutputs,
+    rollup: &RollupResponse,
+    error_message: &str,
+) -> FinishStatus {
+    let exception = DazzleException {
+        request_type: rollup.request_type.clone(),
+        input_index: rollup.data.metadata.as_ref().map(|m| m.input_index),
+        error_message: error_message.to_owned(),
+    };
+    log::error!(
+        "Refusing {} input {:?}: {}",
+        exception.request_type,
+        exception.input_index,
+        exception.error_message
+    );
+
+    if let Err(e) = send_exception(outputs, &exception).await {
+        log::error!("Failed to send exception: {}", e);
+    }
+    FinishStatus::Reject
+}
+
+/// Write a snapshot after every `snapshot_interval` advance inputs, when a snapshot path is set
+fn save_snapshot(config: &RollupConfig, input_index: u64, dapp_address: &str, state: &DappState) {
+    let path = match &config.snapshot_path {
+        Some(path) => path,
+        None => return,
+    };
+    if (input_index + 1) % config.snapshot_interval.max(1) != 0 {
+        return;
+    }
+
+    match write_snapshot(Path::new(path), input_index, dapp_address, state) {
+        Ok(()) => log::debug!("Wrote snapshot {} at input {}", path, input_index),
+        Err(e) => log::error!("Failed to write snapshot: {}", e),
+    }
+}
+
+pub async fn rollup() {
+    let config = RollupConfig::from_env();
+
+    log::debug!("rollup_server url is : {}", config.http_dispatcher_url);
+    log::debug!("Sending finish");
+
+    let mut dispatcher = HttpDispatcher::new(&config.http_dispatcher_url);
+    //#NOTE: starting over next to an unreadable snapshot would overwrite it with an empty state, every input is refused instead
+    let mut halt_reason = None;
+    let (mut state, mut dapp_address, mut next_input_index) = match restore_state(&config) {
+        Ok(restored) => restored,
+        Err(e) => {
+            log::error!("Failed to restore the snapshot: {}", e);
+            halt_reason = Some(e.to_string());
+            (DappState::new(), config.dapp_address.clone(), None)
+        }
+    };
+    let mut status = FinishStatus::Accept;
+    let mut retry_delay = FINISH_RETRY_MIN_DELAY;
+    log::debug!("Init dapp address: {}", dapp_address.clone());
+
+    loop {
+        let resp = match dispatcher.send_finish_request(status.clone()).await {
+            Some(resp) => {
+                retry_delay = FINISH_RETRY_MIN_DELAY;
+                resp
+            }
+            None => {
+                log::warn!(
+                    "Failed to reach the rollup http server, retrying in {:?}",
+                    retry_delay
+                );
+                tokio::time::sleep(retry_delay).await;
+                retry_delay = (retry_delay * 2).min(FINISH_RETRY_MAX_DELAY);
+                continue;
+            }
+        };
+
+        if resp.status() == StatusCode::ACCEPTED {
+            log::debug!("No pending rollup request, trying again");
+        } else {
+            let buf = match hyper::body::to_bytes(resp).await {
+                Ok(bz) => bz.to_vec(),
+                Err(e) => {
+                    log::debug!("Failed to handle /finish response: {}", e);
+                    continue;
+                }
+            };
+
+            let rollup = match serde_json::from_slice::<RollupResponse>(&buf) {
+                Ok(json) => json,
+                Err(e) => {
+                    log::debug!("Failed to deserialize RollupResponse: {}", e);
+                    continue;
+                }
+            };
+
+            if let Some(reason) = &halt_reason {
+                status = refuse_input(&mut dispatcher, &rollup, reason).await;
+                continue;
+            }
+
+            let input_index = rollup.data.metadata.as_ref().map(|m| m.input_index);
+            if let (Some(next), Some(input_index)) = (next_input_index, input_index) {
+                match is_new_input(next, input_index) {
+                    Ok(true) => next_input_index = Some(input_index + 1),
+                    Ok(false) => {
+                        //#NOTE: replayed by the rollup server after a restart, the snapshot already includes it
+                        log::info!("Skipping input {}, already in the snapshot", input_index);
+                        status = state.output_index_manager.get_finish_status(input_index);
+                        continue;
+                    }
+                    Err(e) => {
+                        //#NOTE: the inputs in between are lost, applying this one to the snapshot would fork the state
+                        let reason = format!("Can't resume from the snapshot: {}", e);
+                        status = refuse_input(&mut dispatcher, &rollup, &reason).await;
+                        halt_reason = Some(reason);
+                        continue;
+                    }
+                }
+            }
+
+            if let Some(relayed_address) = config.relayed_dapp_address(&rollup) {
+                dapp_address = relayed_address;
+                log::debug!("Captured dapp address: {}", dapp_address);
+                continue;
+            }
+
+            let rollup_req: Result<RequestType, strum::ParseError> = rollup.request_type.parse();
+
+            match rollup_req {
+                Ok(RequestType::AdvanceState) => {
+                    //Now we need to handle error case so that we can send reject status through /finish call
+                    status = process_advance_state(
+                        rollup.data,
+                        &mut state,
+                        &mut dispatcher,
+                        &config,
+                        &dapp_address,
+                    )
+                    .await;
+                    if let Some(input_index) = input_index {
+                        save_snapshot(&config, input_index, &dapp_address, &state);
+                    }
+                }
+                Ok(RequestType::InspectState) => {
+                    status = process_inspect_state(rollup.data, &state, &mut dispatcher).await;
+                }
+                Err(e) => {
+                    log::error!("Error occurred while handling rollup request: {}", e);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,2518 @@
+use super::*;
+use crate::mono::http_dispatcher::OutputIndices;
+use crate::mono::replay::replay_inputs;
+use domain::cartesi::{
+    reassemble_reports, Exception, Notice, RelayedAction, Report, ReportChunk, Voucher,
+    MAX_REPORT_CONTENT_SIZE,
+};
+use domain::game_core::board::{Direction, MoveAction};
+use domain::game_core::config::{BOARD_HEIGHT, BOARD_WIDTH};
+use ethers_core::k256::ecdsa::{recoverable, signature::Signer, SigningKey};
+use proptest::prelude::*;
+use serde::Serialize;
+
+const ETHER_PORTAL: &str = "0xFfdbe43d4c855BF7e0f105c400A50857f53AB044";
+const ERC20_PORTAL: &str = "0x9C21AEb2093C32DDbC53eEF24B873BDCd1aDa1DB";
+const ERC721_PORTAL: &str = "0x237F8DD094C0e47f4236f12b4Fa01d6Dae89fb87";
+const CHARACTER_COLLECTION: &str = "0xe7f1725e7734ce288f8367e1bb143e90bb3f0512";
+const STABLECOIN: &str = "0x5fbdb2315678afecb367f032d93f642f64180aa3";
+const DAPP_ADDRESS: &str = "0x70ac08179605AF2D9e75782b8DEcDD3c22aA4D0C";
+const METAMASK_WALLET: &str = "0x2af645839ea4ca82452afd195e210420e7cc1f90";
+const INGAME_WALLET: &str = "0x70997970c51812dc3a010c7d01b50e0d17dc79c8";
+const OPPONENT_METAMASK_WALLET: &str = "0x3c44cdddb6a900fa2b585dd299e03d12fa4293bc";
+const OPPONENT_INGAME_WALLET: &str = "0x90f79bf6eb2c4f870365e785982e1f101e93b906";
+const RELAYER: &str = "0xa0ee7a142d267c1f36714e4a8f75612f20a79720";
+const CHAIN_ID: u64 = 31337;
+
+//#NOTE: hardhat private keys of the in-game wallets
+const INGAME_KEY: &str = "59c6995e998f97a5a0044966f0945389dc9e86dae88c7a8412f4603b6b78690d";
+const OPPONENT_INGAME_KEY: &str =
+    "7c852118294e51e653712a81e05800f419141751be58f605c371e15141b007a6";
+
+//#NOTE: EIP-191 signatures of the attach message (nonce 0, DAPP_ADDRESS on CHAIN_ID) by the hardhat keys of the in-game wallets, for their metamask wallet
+const INGAME_ATTACH_SIGNATURE: &str = "0x972750cf19af94c928920375a96134328b59e009805d288b4a220899bc5a50782e26f47fb585a59ccea7cb27fe2b4670c692afdc2dcb092031830be044f970fd1b";
+const OPPONENT_INGAME_ATTACH_SIGNATURE: &str = "0x320d20520c35a5ef4bc2931a09071070b962e9b9a070b366ef6dbb4c0e352af00a8a59dd0bf3654e7e0592cc4f1ed56ebb3bbb42367e8f38ab5c2a3d5349383f1c";
+const OPPONENT_INGAME_TO_METAMASK_SIGNATURE: &str = "0xacaa1a031c9efb023c37f6f7e4b9f2e0159f31ea3670209fdc7a07541fd31a851e3c657266acbf6d13821c0b531e0b17f02b23f755088bd4d36065debf56031a1b";
+//#NOTE: INGAME_ATTACH_SIGNATURE signed for chain 1 instead of CHAIN_ID
+const INGAME_ATTACH_OTHER_CHAIN_SIGNATURE: &str = "0x261c608254c41b2b87749dea8e1bc99682bc701f9459c4480dbce4318e5fa820303c62c476bc5d6cfb63dc347325434f7c2591ac3a8dbaa6608d0a748aba86431c";
+
+fn test_config() -> RollupConfig {
+    RollupConfig {
+        http_dispatcher_url: String::new(),
+        address_relay_contract: String::new(),
+        ether_portal_contract: ETHER_PORTAL.to_owned(),
+        erc20_portal_contract: ERC20_PORTAL.to_owned(),
+        erc721_portal_contract: ERC721_PORTAL.to_owned(),
+        character_collections: vec![CHARACTER_COLLECTION.to_owned()],
+        dapp_address: DAPP_ADDRESS.to_owned(),
+        chain_id: CHAIN_ID,
+        platform_fee_bps: DEFAULT_PLATFORM_FEE_BPS,
+        snapshot_path: None,
+        snapshot_interval: DEFAULT_SNAPSHOT_INTERVAL,
+    }
+}
+
+struct TestDapp {
+    state: DappState,
+    input_index: u64,
+    timestamp: u64,
+    //#NOTE: every advance input so far, to be replayed
+    inputs: Vec<AdvanceRequest>,
+}
+
+impl TestDapp {
+    fn new() -> Self {
+        TestDapp {
+            state: DappState::new(),
+            input_index: 0,
+            timestamp: 1_700_000_000,
+            inputs: vec![],
+        }
+    }
+
+    async fn advance(
+        &mut self,
+        msg_sender: &str,
+        payload: String,
+    ) -> (FinishStatus, OutputRecorder) {
+        let request = AdvanceRequest {
+            metadata: Some(AdvanceMetadata {
+                msg_sender: msg_sender.to_owned(),
+                input_index: self.input_index,
+                block_number: self.input_index,
+                timestamp: self.timestamp,
+            }),
+            payload,
+        };
+        self.input_index += 1;
+        self.inputs.push(request.clone());
+
+        let mut recorder = OutputRecorder::new();
+        let status = process_advance_state(
+            request,
+            &mut self.state,
+            &mut recorder,
+            &test_config(),
+            DAPP_ADDRESS,
+        )
+        .await;
+
+        (status, recorder)
+    }
+
+    async fn inspect(&self, path: &str) -> (FinishStatus, OutputRecorder) {
+        let request = AdvanceRequest {
+            metadata: None,
+            payload: format!("0x{}", hex::encode(path)),
+        };
+
+        let mut recorder = OutputRecorder::new();
+        let status = process_inspect_state(request, &self.state, &mut recorder).await;
+
+        (status, recorder)
+    }
+}
+
+fn attach_request(ingame_wallet: &str) -> AttachIngameWalletRequest {
+    let signature = match ingame_wallet {
+        INGAME_WALLET => INGAME_ATTACH_SIGNATURE,
+        OPPONENT_INGAME_WALLET => OPPONENT_INGAME_ATTACH_SIGNATURE,
+        _ => unreachable!("no attach signature for {}", ingame_wallet),
+    };
+    AttachIngameWalletRequest {
+        ingame_wallet_address: ingame_wallet.to_owned(),
+        nonce: 0,
+        signature: signature.to_owned(),
+        expires_at: None,
+        scope: SessionScope::GameplayAndStake,
+    }
+}
+
+fn deposit_payload(depositor: &str, amount: u64) -> String {
+    let mut bz = Address::from_str(depositor).unwrap().as_bytes().to_vec();
+    let mut amount_bz = [0u8; 32];
+    U256::from(amount).to_big_endian(&mut amount_bz);
+    bz.extend_from_slice(&amount_bz);
+    format!("0x{}", hex::encode(bz))
+}
+
+fn erc20_deposit_payload(token: &str, depositor: &str, amount: u64) -> String {
+    let mut bz = vec![1u8];
+    bz.extend_from_slice(Address::from_str(token).unwrap().as_bytes());
+    bz.extend_from_slice(Address::from_str(depositor).unwrap().as_bytes());
+    let mut amount_bz = [0u8; 32];
+    U256::from(amount).to_big_endian(&mut amount_bz);
+    bz.extend_from_slice(&amount_bz);
+    format!("0x{}", hex::encode(bz))
+}
+
+fn erc721_deposit_payload(collection: &str, depositor: &str, token_id: u64) -> String {
+    let mut bz = Address::from_str(collection).unwrap().as_bytes().to_vec();
+    bz.extend_from_slice(Address::from_str(depositor).unwrap().as_bytes());
+    let mut token_id_bz = [0u8; 32];
+    U256::from(token_id).to_big_endian(&mut token_id_bz);
+    bz.extend_from_slice(&token_id_bz);
+    bz.append(&mut encode(&[Token::Bytes(vec![]), Token::Bytes(vec![])]));
+    format!("0x{}", hex::encode(bz))
+}
+
+fn nft_character_id(token_id: u64) -> NftCharacterId {
+    NftCharacterId {
+        collection: CHARACTER_COLLECTION.to_owned(),
+        token_id: token_id.to_string(),
+    }
+}
+
+//#NOTE: the token of the NFT character each metamask wallet plays with
+fn party_token_id(metamask_wallet: &str) -> u64 {
+    match metamask_wallet {
+        METAMASK_WALLET => 1,
+        OPPONENT_METAMASK_WALLET => 2,
+        _ => unreachable!("no party for {}", metamask_wallet),
+    }
+}
+
+fn party(metamask_wallet: &str) -> Vec<NftCharacterId> {
+    vec![nft_character_id(party_token_id(metamask_wallet))]
+}
+
+async fn deposit_party(dapp: &mut TestDapp, metamask_wallet: &str) {
+    let (status, _) = dapp
+        .advance(
+            ERC721_PORTAL,
+            erc721_deposit_payload(
+                CHARACTER_COLLECTION,
+                metamask_wallet,
+                party_token_id(metamask_wallet),
+            ),
+        )
+        .await;
+    assert!(matches!(status, FinishStatus::Accept));
+}
+
+fn game_request_payload<T: Serialize>(operation: DazzleOperation, data: &T) -> String {
+    let game_req = GameRequest {
+        operation: operation.to_string(),
+        data: general_purpose::STANDARD.encode(serde_json::to_vec(data).unwrap()),
+    };
+    format!("0x{}", hex::encode(serde_json::to_vec(&game_req).unwrap()))
+}
+
+fn versioned_payload(request: DazzleRequest) -> String {
+    let versioned = VersionedRequest::new(request);
+    format!("0x{}", hex::encode(serde_json::to_vec(&versioned).unwrap()))
+}
+
+#[tokio::test]
+async fn deposit_sends_balance_notice() {
+    let mut dapp = TestDapp::new();
+    let (status, outputs) = dapp
+        .advance(ETHER_PORTAL, deposit_payload(METAMASK_WALLET, 1_000))
+        .await;
+
+    assert!(matches!(status, FinishStatus::Accept));
+    assert!(outputs.reports.is_empty());
+    assert!(outputs.vouchers.is_empty());
+
+    let notices = outputs.dazzle_notices();
+    assert_eq!(notices.len(), 1);
+    assert!(matches!(notices[0].notice_type, NoticeType::Deposit));
+    assert_eq!(notices[0].user, METAMASK_WALLET);
+    assert_eq!(notices[0].balance.as_deref(), Some("1000"));
+}
+
+#[tokio::test]
+async fn deposit_with_exec_layer_data_is_credited() {
+    let mut dapp = TestDapp::new();
+    let payload = format!(
+        "{}{}",
+        deposit_payload(METAMASK_WALLET, 1_000),
+        hex::encode(b"dazzle")
+    );
+    let (status, _) = dapp.advance(ETHER_PORTAL, payload).await;
+
+    assert!(matches!(status, FinishStatus::Accept));
+    assert_eq!(
+        dapp.state
+            .balance_manager
+            .get_balance(&ETHER, &Address::from_str(METAMASK_WALLET).unwrap()),
+        Some(&U256::from(1_000))
+    );
+}
+
+#[tokio::test]
+async fn truncated_deposit_gets_error_report() {
+    let mut dapp = TestDapp::new();
+    let payload = deposit_payload(METAMASK_WALLET, 1_000);
+    let (status, outputs) = dapp.advance(ETHER_PORTAL, payload[..50].to_owned()).await;
+
+    assert!(matches!(status, FinishStatus::Reject));
+    assert!(outputs.notices.is_empty());
+    let report: DazzleReport = serde_json::from_str(&outputs.report_payloads()[0]).unwrap();
+    assert!(report
+        .error_message
+        .contains("Invalid Ether portal payload"));
+}
+
+fn deposit_and_act_payload(depositor: &str, amount: u64, request: DazzleRequest) -> String {
+    let exec_layer_data = serde_json::to_vec(&VersionedRequest::new(request)).unwrap();
+    format!(
+        "{}{}",
+        deposit_payload(depositor, amount),
+        hex::encode(exec_layer_data)
+    )
+}
+
+#[tokio::test]
+async fn deposit_attaches_wallet_and_creates_room_at_once() {
+    let mut dapp = TestDapp::new();
+    deposit_party(&mut dapp, METAMASK_WALLET).await;
+    let request = DazzleRequest::Batch(vec![
+        DazzleRequest::AttachIngameWallet(attach_request(INGAME_WALLET)),
+        DazzleRequest::CreatePrivateRoom(CreatePrivateRoomRequest {
+            user: INGAME_WALLET.to_owned(),
+            base64_character_list: String::new(),
+            nft_characters: party(METAMASK_WALLET),
+            stake_token: None,
+            stake: None,
+        }),
+    ]);
+    let (status, outputs) = dapp
+        .advance(
+            ETHER_PORTAL,
+            deposit_and_act_payload(METAMASK_WALLET, STAKE.parse().unwrap(), request),
+        )
+        .await;
+
+    assert!(matches!(status, FinishStatus::Accept));
+    assert!(outputs.reports.is_empty());
+    let notices = outputs.dazzle_notices();
+    assert_eq!(notices.len(), 4);
+    assert!(matches!(notices[0].notice_type, NoticeType::Deposit));
+    assert!(matches!(
+        notices[1].notice_type,
+        NoticeType::AttachIngameWallet
+    ));
+    assert!(matches!(notices[2].notice_type, NoticeType::StakeLocked));
+    assert!(matches!(notices[3].notice_type, NoticeType::Room));
+    assert!(dapp
+        .state
+        .room_manager
+        .get_uuid_by_player(INGAME_WALLET)
+        .is_some());
+}
+
+#[tokio::test]
+async fn failed_deposit_request_keeps_the_deposit() {
+    let mut dapp = TestDapp::new();
+    let request = DazzleRequest::Batch(vec![
+        DazzleRequest::AttachIngameWallet(attach_request(INGAME_WALLET)),
+        DazzleRequest::QuitGame(QuitGameRequest {
+            user: INGAME_WALLET.to_owned(),
+        }),
+    ]);
+    let (status, outputs) = dapp
+        .advance(
+            ETHER_PORTAL,
+            deposit_and_act_payload(METAMASK_WALLET, 1_000, request),
+        )
+        .await;
+
+    assert!(matches!(status, FinishStatus::Accept));
+    let notices = outputs.dazzle_notices();
+    assert_eq!(notices.len(), 1);
+    assert!(matches!(notices[0].notice_type, NoticeType::Deposit));
+    assert_eq!(outputs.reports.len(), 1);
+
+    let depositor = Address::from_str(METAMASK_WALLET).unwrap();
+    assert_eq!(
+        dapp.state.balance_manager.get_balance(&ETHER, &depositor),
+        Some(&U256::from(1_000))
+    );
+    assert!(dapp
+        .state
+        .ingame_wallet_manager
+        .get_ingame_wallet(&depositor)
+        .is_none());
+}
+
+#[tokio::test]
+async fn attach_ingame_wallet_sends_notice() {
+    let mut dapp = TestDapp::new();
+    let req = attach_request(INGAME_WALLET);
+    let (status, outputs) = dapp
+        .advance(
+            METAMASK_WALLET,
+            game_request_payload(DazzleOperation::AttachIngameWallet, &req),
+        )
+        .await;
+
+    assert!(matches!(status, FinishStatus::Accept));
+    let notices = outputs.dazzle_notices();
+    assert_eq!(notices.len(), 1);
+    assert!(matches!(
+        notices[0].notice_type,
+        NoticeType::AttachIngameWallet
+    ));
+    assert_eq!(notices[0].user, METAMASK_WALLET);
+    assert!(dapp
+        .state
+        .ingame_wallet_manager
+        .is_ingame_wallet_attached(&Address::from_str(INGAME_WALLET).unwrap()));
+}
+
+#[tokio::test]
+async fn versioned_request_is_accepted() {
+    let mut dapp = TestDapp::new();
+    let request = DazzleRequest::AttachIngameWallet(attach_request(INGAME_WALLET));
+    let payload = versioned_payload(request);
+
+    let bz = hex::decode(payload.trim_start_matches("0x")).unwrap();
+    let json: Value = serde_json::from_slice(&bz).unwrap();
+    assert_eq!(json["version"], DAZZLE_REQUEST_VERSION);
+    assert_eq!(json["operation"], "attach_ingame_wallet");
+    assert_eq!(json["data"]["ingame_wallet_address"], INGAME_WALLET);
+
+    let (status, outputs) = dapp.advance(METAMASK_WALLET, payload).await;
+
+    assert!(matches!(status, FinishStatus::Accept));
+    assert_eq!(outputs.notices.len(), 1);
+    assert!(dapp
+        .state
+        .ingame_wallet_manager
+        .is_ingame_wallet_attached(&Address::from_str(INGAME_WALLET).unwrap()));
+}
+
+#[test]
+fn unsupported_request_version_is_refused() {
+    let bz = serde_json::to_vec(&json!({
+        "version": DAZZLE_REQUEST_VERSION + 1,
+        "operation": "quit_game",
+        "data": { "user": INGAME_WALLET },
+    }))
+    .unwrap();
+
+    assert!(matches!(
+        decode_dazzle_request(&bz),
+        Err(ServerError::UnsupportedRequestVersion(_))
+    ));
+}
+
+#[tokio::test]
+async fn batch_runs_operations_in_order() {
+    let mut dapp = TestDapp::new();
+    dapp.advance(ETHER_PORTAL, deposit_payload(METAMASK_WALLET, 1_000))
+        .await;
+
+    let batch = DazzleRequest::Batch(vec![
+        DazzleRequest::AttachIngameWallet(attach_request(INGAME_WALLET)),
+        DazzleRequest::InternalTransfer(TransferRequest {
+            from_address: METAMASK_WALLET.to_owned(),
+            to_address: INGAME_WALLET.to_owned(),
+            amount: "400".to_owned(),
+            token: None,
+        }),
+    ]);
+    let (status, outputs) = dapp
+        .advance(METAMASK_WALLET, versioned_payload(batch))
+        .await;
+
+    assert!(matches!(status, FinishStatus::Accept));
+    let notices = outputs.dazzle_notices();
+    assert_eq!(notices.len(), 3);
+    assert!(matches!(
+        notices[0].notice_type,
+        NoticeType::AttachIngameWallet
+    ));
+    assert!(matches!(
+        notices[1].notice_type,
+        NoticeType::InternalTransfer
+    ));
+    assert!(outputs.vouchers.is_empty());
+}
+
+#[tokio::test]
+async fn failed_batch_operation_rolls_back_whole_batch() {
+    let mut dapp = TestDapp::new();
+    let batch = DazzleRequest::Batch(vec![
+        DazzleRequest::AttachIngameWallet(attach_request(INGAME_WALLET)),
+        DazzleRequest::QuitGame(QuitGameRequest {
+            user: INGAME_WALLET.to_owned(),
+        }),
+    ]);
+    let (status, outputs) = dapp
+        .advance(METAMASK_WALLET, versioned_payload(batch))
+        .await;
+
+    assert!(matches!(status, FinishStatus::Reject));
+    assert_eq!(outputs.report_payloads().len(), 1);
+    assert!(!dapp
+        .state
+        .ingame_wallet_manager
+        .is_ingame_wallet_attached(&Address::from_str(INGAME_WALLET).unwrap()));
+}
+
+#[tokio::test]
+async fn unattached_sender_gets_error_report() {
+    let mut dapp = TestDapp::new();
+    let req = CreatePrivateRoomRequest {
+        user: INGAME_WALLET.to_owned(),
+        base64_character_list: String::new(),
+        nft_characters: vec![],
+        stake_token: None,
+        stake: None,
+    };
+    let (status, outputs) = dapp
+        .advance(
+            INGAME_WALLET,
+            game_request_payload(DazzleOperation::CreatePrivateRoom, &req),
+        )
+        .await;
+
+    assert!(matches!(status, FinishStatus::Reject));
+    assert!(outputs.notices.is_empty());
+
+    let reports = outputs.report_payloads();
+    assert_eq!(reports.len(), 1);
+    let report: DazzleReport = serde_json::from_str(&reports[0]).unwrap();
+    assert!(report.error_message.contains("Invalid ingame-wallet"));
+}
+
+#[tokio::test]
+async fn unknown_operation_is_rejected() {
+    let mut dapp = TestDapp::new();
+    let game_req = GameRequest {
+        operation: "not_an_operation".to_owned(),
+        data: String::new(),
+    };
+    let payload = format!("0x{}", hex::encode(serde_json::to_vec(&game_req).unwrap()));
+    let (status, outputs) = dapp.advance(METAMASK_WALLET, payload).await;
+
+    assert!(matches!(status, FinishStatus::Reject));
+    assert!(outputs.notices.is_empty());
+    assert!(outputs.reports.is_empty());
+}
+
+#[tokio::test]
+async fn failed_transfer_moves_no_funds() {
+    let mut dapp = TestDapp::new();
+    dapp.advance(ETHER_PORTAL, deposit_payload(METAMASK_WALLET, 1_000))
+        .await;
+
+    let req = TransferRequest {
+        from_address: METAMASK_WALLET.to_owned(),
+        to_address: INGAME_WALLET.to_owned(),
+        amount: "5000".to_owned(),
+        token: None,
+    };
+    let (status, outputs) = dapp
+        .advance(
+            METAMASK_WALLET,
+            game_request_payload(DazzleOperation::TransferBalance, &req),
+        )
+        .await;
+
+    assert!(matches!(status, FinishStatus::Reject));
+    assert!(outputs.vouchers.is_empty());
+    assert_eq!(outputs.reports.len(), 1);
+
+    let from_address = Address::from_str(METAMASK_WALLET).unwrap();
+    assert_eq!(
+        dapp.state
+            .balance_manager
+            .get_balance(&ETHER, &from_address),
+        Some(&U256::from(1_000))
+    );
+}
+
+//#NOTE: a rollup server failing in one way, the outputs are recorded otherwise
+enum Fault {
+    FailNotices,
+    PanicOnNotices,
+    //#NOTE: numbers vouchers across inputs, like a rollup server which doesn't restart at 0
+    MisnumberVouchers,
+}
+
+struct FaultyOutputs {
+    recorder: OutputRecorder,
+    fault: Fault,
+}
+
+impl FaultyOutputs {
+    fn new(fault: Fault) -> Self {
+        FaultyOutputs {
+            recorder: OutputRecorder::new(),
+            fault,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl RollupOutputs for FaultyOutputs {
+    async fn add_notice(&mut self, notice: Notice) -> Result<u64, DazzleError> {
+        match self.fault {
+            Fault::FailNotices => Err(ServerError::FailedToSendNotice.into()),
+            Fault::PanicOnNotices => panic!("notice handler exploded"),
+            Fault::MisnumberVouchers => self.recorder.add_notice(notice).await,
+        }
+    }
+
+    async fn add_report(&mut self, report: Report) -> Result<(), DazzleError> {
+        self.recorder.add_report(report).await
+    }
+
+    async fn add_voucher(&mut self, voucher: Voucher) -> Result<u64, DazzleError> {
+        let index = self.recorder.add_voucher(voucher).await?;
+        match self.fault {
+            Fault::MisnumberVouchers => Ok(index + 1),
+            _ => Ok(index),
+        }
+    }
+
+    async fn add_exception(&mut self, exception: Exception) -> Result<(), DazzleError> {
+        self.recorder.add_exception(exception).await
+    }
+
+    fn next_indices(&self) -> OutputIndices {
+        self.recorder.next_indices()
+    }
+}
+
+#[tokio::test]
+async fn failed_input_rolls_back_state() {
+    let mut dapp = TestDapp::new();
+    let request = AdvanceRequest {
+        metadata: Some(AdvanceMetadata {
+            msg_sender: ETHER_PORTAL.to_owned(),
+            input_index: 0,
+            block_number: 0,
+            timestamp: 1_700_000_000,
+        }),
+        payload: deposit_payload(METAMASK_WALLET, 1_000),
+    };
+
+    let status = process_advance_state(
+        request,
+        &mut dapp.state,
+        &mut FaultyOutputs::new(Fault::FailNotices),
+        &test_config(),
+        DAPP_ADDRESS,
+    )
+    .await;
+
+    assert!(matches!(status, FinishStatus::Reject));
+    let depositor = Address::from_str(METAMASK_WALLET).unwrap();
+    assert!(dapp
+        .state
+        .balance_manager
+        .get_balance(&ETHER, &depositor)
+        .is_none());
+}
+
+#[tokio::test]
+async fn panicking_input_is_reported_and_rejected() {
+    let mut dapp = TestDapp::new();
+    let mut outputs = FaultyOutputs::new(Fault::PanicOnNotices);
+    let request = AdvanceRequest {
+        metadata: Some(AdvanceMetadata {
+            msg_sender: ETHER_PORTAL.to_owned(),
+            input_index: 7,
+            block_number: 7,
+            timestamp: 1_700_000_000,
+        }),
+        payload: deposit_payload(METAMASK_WALLET, 1_000),
+    };
+
+    let status = process_advance_state(
+        request,
+        &mut dapp.state,
+        &mut outputs,
+        &test_config(),
+        DAPP_ADDRESS,
+    )
+    .await;
+
+    assert!(matches!(status, FinishStatus::Reject));
+    let depositor = Address::from_str(METAMASK_WALLET).unwrap();
+    assert!(dapp
+        .state
+        .balance_manager
+        .get_balance(&ETHER, &depositor)
+        .is_none());
+
+    //#NOTE: /exception would end the dapp
+    assert!(outputs.recorder.exceptions.is_empty());
+    let exception: DazzleException =
+        serde_json::from_str(&outputs.recorder.report_payloads()[0]).unwrap();
+    assert_eq!(exception.input_index, Some(7));
+    assert_eq!(exception.error_message, "notice handler exploded");
+}
+
+#[tokio::test]
+async fn inspect_balance_reports_single_balance() {
+    let mut dapp = TestDapp::new();
+    dapp.advance(ETHER_PORTAL, deposit_payload(METAMASK_WALLET, 1_000))
+        .await;
+
+    let (status, outputs) = dapp.inspect(&format!("balance/{}", METAMASK_WALLET)).await;
+
+    assert!(matches!(status, FinishStatus::Accept));
+    let reports = outputs.report_payloads();
+    assert_eq!(reports.len(), 1);
+    let balance: BalanceResponse = serde_json::from_str(&reports[0]).unwrap();
+    assert_eq!(balance.balance, "1000");
+}
+
+#[tokio::test]
+async fn unknown_inspect_route_gets_error_report() {
+    let dapp = TestDapp::new();
+    let (status, outputs) = dapp.inspect("everything").await;
+
+    assert!(matches!(status, FinishStatus::Reject));
+    let reports = outputs.report_payloads();
+    assert_eq!(reports.len(), 1);
+    let report: DazzleReport = serde_json::from_str(&reports[0]).unwrap();
+    assert!(report.error_message.contains("Invalid inspect query"));
+}
+
+#[tokio::test]
+async fn large_report_is_chunked_and_reassembled() {
+    //#NOTE: multi-byte characters make sure chunks never cut through one
+    let content = "é-dazzle-".repeat(MAX_REPORT_CONTENT_SIZE / 4);
+    let mut outputs = OutputRecorder::new();
+    send_chunked_report(&mut outputs, &content).await.unwrap();
+
+    assert!(outputs.reports.len() > 1);
+    let first: ReportChunk = serde_json::from_str(&outputs.report_payloads()[0]).unwrap();
+    assert_eq!(first.sequence, 0);
+    assert_eq!(first.total as usize, outputs.reports.len());
+
+    let mut reports = outputs.reports.clone();
+    reports.reverse();
+    assert_eq!(reassemble_reports(&reports).unwrap(), content);
+
+    reports.pop();
+    assert!(matches!(
+        reassemble_reports(&reports),
+        Err(ServerError::IncompleteReportChunks(_, _))
+    ));
+}
+
+#[tokio::test]
+async fn erc20_deposit_credits_token_balance() {
+    let mut dapp = TestDapp::new();
+    let (status, outputs) = dapp
+        .advance(
+            ERC20_PORTAL,
+            erc20_deposit_payload(STABLECOIN, METAMASK_WALLET, 2_000),
+        )
+        .await;
+
+    assert!(matches!(status, FinishStatus::Accept));
+    let notices = outputs.dazzle_notices();
+    assert_eq!(notices.len(), 1);
+    assert!(matches!(notices[0].notice_type, NoticeType::Erc20Deposit));
+    assert_eq!(notices[0].balance.as_deref(), Some("2000"));
+
+    let token = Address::from_str(STABLECOIN).unwrap();
+    let owner = Address::from_str(METAMASK_WALLET).unwrap();
+    assert_eq!(
+        dapp.state.balance_manager.get_balance(&token, &owner),
+        Some(&U256::from(2_000))
+    );
+    assert!(dapp
+        .state
+        .balance_manager
+        .get_balance(&ETHER, &owner)
+        .is_none());
+}
+
+#[tokio::test]
+async fn erc20_withdraw_emits_token_transfer_voucher() {
+    let mut dapp = TestDapp::new();
+    dapp.advance(
+        ERC20_PORTAL,
+        erc20_deposit_payload(STABLECOIN, METAMASK_WALLET, 2_000),
+    )
+    .await;
+
+    let req = WithdrawRequest {
+        amount: "500".to_owned(),
+        token: Some(STABLECOIN.to_owned()),
+    };
+    let (status, outputs) = dapp
+        .advance(
+            METAMASK_WALLET,
+            versioned_payload(DazzleRequest::Withdraw(req)),
+        )
+        .await;
+
+    assert!(matches!(status, FinishStatus::Accept));
+    assert_eq!(outputs.vouchers.len(), 1);
+    assert_eq!(outputs.vouchers[0].destination, STABLECOIN);
+
+    let selector = short_signature("transfer", &[ParamType::Address, ParamType::Uint(256)]);
+    assert!(outputs.vouchers[0]
+        .payload
+        .starts_with(&format!("0x{}", hex::encode(selector))));
+
+    let notices = outputs.dazzle_notices();
+    assert!(matches!(notices[0].notice_type, NoticeType::Withdraw));
+    assert_eq!(notices[0].balance.as_deref(), Some("1500"));
+}
+
+#[tokio::test]
+async fn ingame_wallet_withdraws_owner_balance() {
+    let mut dapp = TestDapp::new();
+    dapp.advance(ETHER_PORTAL, deposit_payload(METAMASK_WALLET, 1_000))
+        .await;
+    dapp.advance(
+        METAMASK_WALLET,
+        versioned_payload(DazzleRequest::AttachIngameWallet(attach_request(
+            INGAME_WALLET,
+        ))),
+    )
+    .await;
+
+    let req = WithdrawRequest {
+        amount: "400".to_owned(),
+        token: None,
+    };
+    let (status, outputs) = dapp
+        .advance(
+            INGAME_WALLET,
+            versioned_payload(DazzleRequest::Withdraw(req)),
+        )
+        .await;
+
+    assert!(matches!(status, FinishStatus::Accept));
+    assert_eq!(outputs.vouchers.len(), 1);
+    assert_eq!(outputs.vouchers[0].destination, DAPP_ADDRESS);
+
+    let owner = Address::from_str(METAMASK_WALLET).unwrap();
+    let (_, expected_payload) = withdrawal_voucher(&ETHER, owner, U256::from(400), DAPP_ADDRESS);
+    assert_eq!(
+        outputs.vouchers[0].payload,
+        format!("0x{}", hex::encode(expected_payload))
+    );
+
+    assert_eq!(
+        dapp.state.balance_manager.get_balance(&ETHER, &owner),
+        Some(&U256::from(600))
+    );
+    assert_eq!(
+        dapp.state
+            .balance_manager
+            .get_voucher_meta(&owner)
+            .map(|metas| metas.len()),
+        Some(1)
+    );
+}
+
+#[tokio::test]
+async fn transfer_of_foreign_funds_is_rejected() {
+    let mut dapp = TestDapp::new();
+    dapp.advance(ETHER_PORTAL, deposit_payload(METAMASK_WALLET, 1_000))
+        .await;
+
+    let req = TransferRequest {
+        from_address: METAMASK_WALLET.to_owned(),
+        to_address: INGAME_WALLET.to_owned(),
+        amount: "1000".to_owned(),
+        token: None,
+    };
+    let (status, outputs) = dapp
+        .advance(
+            INGAME_WALLET,
+            versioned_payload(DazzleRequest::TransferBalance(req)),
+        )
+        .await;
+
+    assert!(matches!(status, FinishStatus::Reject));
+    let report: DazzleReport = serde_json::from_str(&outputs.report_payloads()[0]).unwrap();
+    assert!(report.error_message.contains("doesn't own the funds"));
+    assert_eq!(
+        dapp.state
+            .balance_manager
+            .get_balance(&ETHER, &Address::from_str(METAMASK_WALLET).unwrap()),
+        Some(&U256::from(1_000))
+    );
+}
+
+#[tokio::test]
+async fn transfer_balance_pays_recipient_on_l1() {
+    let mut dapp = TestDapp::new();
+    dapp.advance(ETHER_PORTAL, deposit_payload(METAMASK_WALLET, 1_000))
+        .await;
+
+    let req = TransferRequest {
+        from_address: METAMASK_WALLET.to_owned(),
+        to_address: INGAME_WALLET.to_owned(),
+        amount: "400".to_owned(),
+        token: None,
+    };
+    let (status, outputs) = dapp
+        .advance(
+            METAMASK_WALLET,
+            game_request_payload(DazzleOperation::TransferBalance, &req),
+        )
+        .await;
+
+    assert!(matches!(status, FinishStatus::Accept));
+    let recipient = Address::from_str(INGAME_WALLET).unwrap();
+    let (_, expected_payload) =
+        withdrawal_voucher(&ETHER, recipient, U256::from(400), DAPP_ADDRESS);
+    assert_eq!(outputs.vouchers.len(), 1);
+    assert_eq!(
+        outputs.vouchers[0].payload,
+        format!("0x{}", hex::encode(expected_payload))
+    );
+
+    let notices = outputs.dazzle_notices();
+    assert!(notices
+        .iter()
+        .all(|notice| matches!(notice.notice_type, NoticeType::Transfer)));
+    assert_eq!(ether_balance(&dapp, METAMASK_WALLET), U256::from(600));
+    assert_eq!(ether_balance(&dapp, INGAME_WALLET), U256::zero());
+    assert_eq!(
+        dapp.state
+            .balance_manager
+            .get_voucher_meta(&recipient)
+            .map(|metas| metas.len()),
+        Some(1)
+    );
+    dapp.state.balance_manager.check_accounting().unwrap();
+}
+
+#[tokio::test]
+async fn erc721_deposit_records_character_in_inventory() {
+    let mut dapp = TestDapp::new();
+    //#NOTE: stats sent along with the deposit are ignored
+    let forged = CharacterV2::roll_new(1, &GameplayConfigManager::new());
+    let payload = erc721_deposit_payload(CHARACTER_COLLECTION, METAMASK_WALLET, 7);
+    let mut bz = hex::decode(payload.trim_start_matches("0x")).unwrap();
+    bz.truncate(20 + 20 + 32);
+    bz.append(&mut encode(&[
+        Token::Bytes(vec![]),
+        Token::Bytes(serde_json::to_vec(&forged).unwrap()),
+    ]));
+    let (status, outputs) = dapp
+        .advance(ERC721_PORTAL, format!("0x{}", hex::encode(bz)))
+        .await;
+
+    assert!(matches!(status, FinishStatus::Accept));
+    let notices = outputs.dazzle_notices();
+    assert_eq!(notices.len(), 1);
+    assert!(matches!(notices[0].notice_type, NoticeType::NftDeposit));
+    assert_eq!(notices[0].user, METAMASK_WALLET);
+
+    let (status, outputs) = dapp
+        .inspect(&format!("inventory/{}", METAMASK_WALLET))
+        .await;
+    assert!(matches!(status, FinishStatus::Accept));
+    let inventory: InventoryResponse = serde_json::from_str(&outputs.report_payloads()[0]).unwrap();
+    assert_eq!(inventory.characters.len(), 1);
+    assert_eq!(inventory.characters[0].collection, CHARACTER_COLLECTION);
+    assert_eq!(inventory.characters[0].token_id, "7");
+    let expected = roll_nft_character(
+        &Address::from_str(CHARACTER_COLLECTION).unwrap(),
+        U256::from(7),
+    );
+    assert_eq!(
+        serde_json::to_value(&inventory.characters[0].character).unwrap(),
+        serde_json::to_value(Some(expected)).unwrap()
+    );
+}
+
+#[tokio::test]
+async fn erc721_deposit_of_other_collection_is_rejected() {
+    let mut dapp = TestDapp::new();
+    let (status, outputs) = dapp
+        .advance(
+            ERC721_PORTAL,
+            erc721_deposit_payload(STABLECOIN, METAMASK_WALLET, 7),
+        )
+        .await;
+
+    assert!(matches!(status, FinishStatus::Reject));
+    assert!(outputs.report_payloads()[0].contains("holds no characters"));
+    assert!(dapp
+        .state
+        .nft_inventory_manager
+        .get_inventory(&Address::from_str(METAMASK_WALLET).unwrap())
+        .is_empty());
+}
+
+#[cfg(not(feature = "raw-characters"))]
+#[tokio::test]
+async fn raw_character_list_is_rejected() {
+    let mut dapp = TestDapp::new();
+    fund_player(&mut dapp, METAMASK_WALLET, INGAME_WALLET).await;
+    let character_list = serde_json::to_vec(&vec![CharacterV2::roll_new(
+        1,
+        &GameplayConfigManager::new(),
+    )])
+    .unwrap();
+    let create_room = DazzleRequest::CreatePrivateRoom(CreatePrivateRoomRequest {
+        user: INGAME_WALLET.to_owned(),
+        base64_character_list: general_purpose::STANDARD.encode(character_list),
+        nft_characters: vec![],
+        stake_token: None,
+        stake: None,
+    });
+    let (status, outputs) = dapp
+        .advance(INGAME_WALLET, versioned_payload(create_room))
+        .await;
+
+    assert!(matches!(status, FinishStatus::Reject));
+    assert!(outputs.report_payloads()[0].contains("deposited NFT characters only"));
+    assert!(dapp
+        .state
+        .room_manager
+        .get_uuid_by_player(INGAME_WALLET)
+        .is_none());
+}
+
+#[tokio::test]
+async fn create_room_with_owned_nft_characters() {
+    let mut dapp = TestDapp::new();
+    dapp.advance(
+        ETHER_PORTAL,
+        deposit_payload(METAMASK_WALLET, STAKE.parse().unwrap()),
+    )
+    .await;
+    dapp.advance(
+        ERC721_PORTAL,
+        erc721_deposit_payload(CHARACTER_COLLECTION, METAMASK_WALLET, 7),
+    )
+    .await;
+    dapp.advance(
+        METAMASK_WALLET,
+        versioned_payload(DazzleRequest::AttachIngameWallet(attach_request(
+            INGAME_WALLET,
+        ))),
+    )
+    .await;
+
+    let create_room = |token_id| {
+        versioned_payload(DazzleRequest::CreatePrivateRoom(CreatePrivateRoomRequest {
+            user: INGAME_WALLET.to_owned(),
+            base64_character_list: String::new(),
+            nft_characters: vec![nft_character_id(token_id)],
+            stake_token: None,
+            stake: None,
+        }))
+    };
+
+    //#NOTE: token 8 was never deposited
+    let (status, outputs) = dapp.advance(INGAME_WALLET, create_room(8)).await;
+    assert!(matches!(status, FinishStatus::Reject));
+    let report: DazzleReport = serde_json::from_str(&outputs.report_payloads()[0]).unwrap();
+    assert!(report.error_message.contains("not owned"));
+
+    let (status, _) = dapp.advance(INGAME_WALLET, create_room(7)).await;
+    assert!(matches!(status, FinishStatus::Accept));
+    assert!(dapp
+        .state
+        .room_manager
+        .get_uuid_by_player(INGAME_WALLET)
+        .is_some());
+
+    //#NOTE: the character stays in the dapp while its owner plays with it
+    let withdraw = DazzleRequest::WithdrawNft(WithdrawNftRequest {
+        collection: CHARACTER_COLLECTION.to_owned(),
+        token_id: "7".to_owned(),
+    });
+    let (status, outputs) = dapp
+        .advance(METAMASK_WALLET, versioned_payload(withdraw))
+        .await;
+    assert!(matches!(status, FinishStatus::Reject));
+    assert!(outputs.vouchers.is_empty());
+}
+
+#[tokio::test]
+async fn withdraw_nft_emits_safe_transfer_voucher() {
+    let mut dapp = TestDapp::new();
+    dapp.advance(
+        ERC721_PORTAL,
+        erc721_deposit_payload(CHARACTER_COLLECTION, METAMASK_WALLET, 7),
+    )
+    .await;
+
+    let withdraw = || {
+        versioned_payload(DazzleRequest::WithdrawNft(WithdrawNftRequest {
+            collection: CHARACTER_COLLECTION.to_owned(),
+            token_id: "7".to_owned(),
+        }))
+    };
+
+    let (status, outputs) = dapp.advance(INGAME_WALLET, withdraw()).await;
+    assert!(matches!(status, FinishStatus::Reject));
+    assert!(outputs.vouchers.is_empty());
+
+    let (status, outputs) = dapp.advance(METAMASK_WALLET, withdraw()).await;
+    assert!(matches!(status, FinishStatus::Accept));
+    assert_eq!(outputs.vouchers.len(), 1);
+    assert_eq!(outputs.vouchers[0].destination, CHARACTER_COLLECTION);
+
+    let selector = short_signature(
+        "safeTransferFrom",
+        &[ParamType::Address, ParamType::Address, ParamType::Uint(256)],
+    );
+    assert!(outputs.vouchers[0]
+        .payload
+        .starts_with(&format!("0x{}", hex::encode(selector))));
+
+    let notices = outputs.dazzle_notices();
+    assert!(matches!(notices[0].notice_type, NoticeType::NftWithdraw));
+    assert!(dapp
+        .state
+        .nft_inventory_manager
+        .get_inventory(&Address::from_str(METAMASK_WALLET).unwrap())
+        .is_empty());
+}
+
+//#NOTE: the stake and the party are deposited to the metamask wallet, the in-game wallet stakes and plays them as a session key
+async fn fund_player(dapp: &mut TestDapp, metamask_wallet: &str, ingame_wallet: &str) {
+    deposit_party(dapp, metamask_wallet).await;
+    dapp.advance(
+        ETHER_PORTAL,
+        deposit_payload(metamask_wallet, STAKE.parse().unwrap()),
+    )
+    .await;
+    dapp.advance(
+        metamask_wallet,
+        versioned_payload(DazzleRequest::AttachIngameWallet(attach_request(
+            ingame_wallet,
+        ))),
+    )
+    .await;
+}
+
+fn ether_balance(dapp: &TestDapp, owner: &str) -> U256 {
+    dapp.state
+        .balance_manager
+        .get_balance(&ETHER, &Address::from_str(owner).unwrap())
+        .copied()
+        .unwrap_or_default()
+}
+
+async fn create_funded_room(dapp: &mut TestDapp) -> Uuid {
+    fund_player(dapp, METAMASK_WALLET, INGAME_WALLET).await;
+    let create_room = DazzleRequest::CreatePrivateRoom(CreatePrivateRoomRequest {
+        user: INGAME_WALLET.to_owned(),
+        base64_character_list: String::new(),
+        nft_characters: party(METAMASK_WALLET),
+        stake_token: None,
+        stake: None,
+    });
+    let (status, outputs) = dapp
+        .advance(INGAME_WALLET, versioned_payload(create_room))
+        .await;
+    assert!(matches!(status, FinishStatus::Accept));
+    assert!(matches!(
+        outputs.dazzle_notices()[0].notice_type,
+        NoticeType::StakeLocked
+    ));
+
+    *dapp
+        .state
+        .room_manager
+        .get_uuid_by_player(INGAME_WALLET)
+        .unwrap()
+}
+
+#[tokio::test]
+async fn room_stakes_are_escrowed_and_paid_to_winner() {
+    let mut dapp = TestDapp::new();
+    let room_id = create_funded_room(&mut dapp).await;
+    assert_eq!(ether_balance(&dapp, METAMASK_WALLET), U256::zero());
+
+    fund_player(&mut dapp, OPPONENT_METAMASK_WALLET, OPPONENT_INGAME_WALLET).await;
+    let private_code = dapp
+        .state
+        .room_manager
+        .get_room(&room_id)
+        .unwrap()
+        .private_code
+        .clone();
+    let join_room = DazzleRequest::JoinPrivateRoom(JoinPrivateRoomRequest {
+        user: OPPONENT_INGAME_WALLET.to_owned(),
+        private_code,
+        base64_character_list: String::new(),
+        nft_characters: party(OPPONENT_METAMASK_WALLET),
+    });
+    let (status, _) = dapp
+        .advance(OPPONENT_INGAME_WALLET, versioned_payload(join_room))
+        .await;
+    assert!(matches!(status, FinishStatus::Accept));
+    assert_eq!(ether_balance(&dapp, OPPONENT_METAMASK_WALLET), U256::zero());
+
+    let stake = U256::from_dec_str(STAKE).unwrap();
+    let escrow = dapp.state.balance_manager.get_escrow(&room_id).unwrap();
+    assert_eq!(escrow.total(), stake * 2);
+
+    //#NOTE: the creator quits, so the payout doesn't land on the admin wallet, which is METAMASK_WALLET
+    let quit_game = DazzleRequest::QuitGame(QuitGameRequest {
+        user: INGAME_WALLET.to_owned(),
+    });
+    let (status, outputs) = dapp
+        .advance(INGAME_WALLET, versioned_payload(quit_game))
+        .await;
+    assert!(matches!(status, FinishStatus::Accept));
+
+    let pot = stake * 2;
+    let platform_fee = pot * U256::from(test_config().platform_fee_bps) / U256::from(BPS_UNIT);
+    let notices = outputs.dazzle_notices();
+    assert!(matches!(notices[0].notice_type, NoticeType::StakePayout));
+    assert_eq!(notices[0].user, OPPONENT_METAMASK_WALLET);
+    assert!(matches!(notices[1].notice_type, NoticeType::PlatformFee));
+    assert_eq!(
+        ether_balance(&dapp, OPPONENT_METAMASK_WALLET),
+        pot - platform_fee
+    );
+    assert_eq!(ether_balance(&dapp, ADMIN_WALLET_ADDRESS), platform_fee);
+    assert!(dapp.state.balance_manager.get_escrow(&room_id).is_none());
+
+    let game_over_result = dapp
+        .state
+        .room_manager
+        .get_room(&room_id)
+        .unwrap()
+        .get_game_over_result()
+        .unwrap();
+    assert_eq!(game_over_result.platform_fee, platform_fee.to_string());
+    assert_eq!(
+        game_over_result.winner_reward,
+        (pot - platform_fee).to_string()
+    );
+
+    //#NOTE: the fee is an ordinary balance of the admin wallet, withdrawn like any other
+    let withdraw = DazzleRequest::Withdraw(WithdrawRequest {
+        amount: platform_fee.to_string(),
+        token: None,
+    });
+    let (status, outputs) = dapp
+        .advance(ADMIN_WALLET_ADDRESS, versioned_payload(withdraw))
+        .await;
+    assert!(matches!(status, FinishStatus::Accept));
+    assert_eq!(outputs.vouchers.len(), 1);
+    assert_eq!(ether_balance(&dapp, ADMIN_WALLET_ADDRESS), U256::zero());
+}
+
+#[tokio::test]
+async fn session_key_stakes_and_withdraws_for_its_metamask_wallet() {
+    let mut dapp = TestDapp::new();
+    let room_id = start_pvp_room(&mut dapp).await;
+
+    //#NOTE: the stakes are taken from the metamask wallets, the session keys never hold funds
+    let escrow = dapp.state.balance_manager.get_escrow(&room_id).unwrap();
+    let payers = escrow
+        .stakes
+        .iter()
+        .map(|stake| format!("{:#x}", stake.payer))
+        .collect::<Vec<_>>();
+    assert_eq!(payers, vec![METAMASK_WALLET, OPPONENT_METAMASK_WALLET]);
+
+    let quit_game = DazzleRequest::QuitGame(QuitGameRequest {
+        user: INGAME_WALLET.to_owned(),
+    });
+    let (status, _) = dapp
+        .advance(INGAME_WALLET, versioned_payload(quit_game))
+        .await;
+    assert!(matches!(status, FinishStatus::Accept));
+
+    let pot = U256::from_dec_str(STAKE).unwrap() * 2;
+    let reward = pot - pot * U256::from(test_config().platform_fee_bps) / U256::from(BPS_UNIT);
+    assert_eq!(ether_balance(&dapp, OPPONENT_METAMASK_WALLET), reward);
+    assert_eq!(ether_balance(&dapp, OPPONENT_INGAME_WALLET), U256::zero());
+
+    let withdraw = DazzleRequest::Withdraw(WithdrawRequest {
+        amount: reward.to_string(),
+        token: None,
+    });
+    let (status, outputs) = dapp
+        .advance(OPPONENT_INGAME_WALLET, versioned_payload(withdraw))
+        .await;
+    assert!(matches!(status, FinishStatus::Accept));
+    let (_, expected_payload) = withdrawal_voucher(
+        &ETHER,
+        Address::from_str(OPPONENT_METAMASK_WALLET).unwrap(),
+        reward,
+        DAPP_ADDRESS,
+    );
+    assert_eq!(outputs.vouchers.len(), 1);
+    assert_eq!(
+        outputs.vouchers[0].payload,
+        format!("0x{}", hex::encode(expected_payload))
+    );
+    assert_eq!(ether_balance(&dapp, OPPONENT_METAMASK_WALLET), U256::zero());
+    dapp.state.balance_manager.check_accounting().unwrap();
+}
+
+#[tokio::test]
+async fn reconnect_does_not_need_stake_balance() {
+    let mut dapp = TestDapp::new();
+    let room_id = create_funded_room(&mut dapp).await;
+    assert_eq!(ether_balance(&dapp, METAMASK_WALLET), U256::zero());
+
+    let create_room = DazzleRequest::CreatePrivateRoom(CreatePrivateRoomRequest {
+        user: INGAME_WALLET.to_owned(),
+        base64_character_list: String::new(),
+        nft_characters: party(METAMASK_WALLET),
+        stake_token: None,
+        stake: None,
+    });
+    let (status, outputs) = dapp
+        .advance(INGAME_WALLET, versioned_payload(create_room))
+        .await;
+    assert!(matches!(status, FinishStatus::Accept));
+    assert!(outputs.reports.is_empty());
+    assert_eq!(
+        dapp.state.room_manager.get_uuid_by_player(INGAME_WALLET),
+        Some(&room_id)
+    );
+}
+
+#[tokio::test]
+async fn cancelled_room_refunds_stake() {
+    let mut dapp = TestDapp::new();
+    let room_id = create_funded_room(&mut dapp).await;
+
+    let cancel_room = DazzleRequest::CancelRoom(CancelRoomRequest {
+        user: INGAME_WALLET.to_owned(),
+    });
+    let (status, outputs) = dapp
+        .advance(INGAME_WALLET, versioned_payload(cancel_room))
+        .await;
+    assert!(matches!(status, FinishStatus::Accept));
+
+    let notices = outputs.dazzle_notices();
+    assert!(matches!(notices[0].notice_type, NoticeType::StakeRefund));
+    let content = general_purpose::STANDARD
+        .decode(&notices[0].base64_content)
+        .unwrap();
+    let refund: StakeNotice = serde_json::from_slice(&content).unwrap();
+    assert_eq!(refund.room_id, room_id);
+    assert_eq!(refund.amount, STAKE);
+    assert_eq!(
+        ether_balance(&dapp, METAMASK_WALLET),
+        U256::from_dec_str(STAKE).unwrap()
+    );
+    assert!(dapp.state.balance_manager.get_escrow(&room_id).is_none());
+}
+
+#[tokio::test]
+async fn room_stake_is_chosen_by_creator_within_limits() {
+    let mut dapp = TestDapp::new();
+    fund_player(&mut dapp, METAMASK_WALLET, INGAME_WALLET).await;
+    dapp.advance(
+        ETHER_PORTAL,
+        deposit_payload(METAMASK_WALLET, STAKE.parse().unwrap()),
+    )
+    .await;
+
+    let create_room = |stake: &str| {
+        versioned_payload(DazzleRequest::CreatePrivateRoom(CreatePrivateRoomRequest {
+            user: INGAME_WALLET.to_owned(),
+            base64_character_list: String::new(),
+            nft_characters: party(METAMASK_WALLET),
+            stake_token: None,
+            stake: Some(stake.to_owned()),
+        }))
+    };
+
+    let too_high = (U256::from_dec_str(MAX_STAKE).unwrap() + 1).to_string();
+    let (status, outputs) = dapp.advance(INGAME_WALLET, create_room(&too_high)).await;
+    assert!(matches!(status, FinishStatus::Reject));
+    let report: DazzleReport = serde_json::from_str(&outputs.report_payloads()[0]).unwrap();
+    assert!(report.error_message.contains("out of the allowed range"));
+
+    let double_stake = (U256::from_dec_str(STAKE).unwrap() * 2).to_string();
+    let (status, _) = dapp
+        .advance(INGAME_WALLET, create_room(&double_stake))
+        .await;
+    assert!(matches!(status, FinishStatus::Accept));
+    assert_eq!(ether_balance(&dapp, METAMASK_WALLET), U256::zero());
+
+    let room_id = *dapp
+        .state
+        .room_manager
+        .get_uuid_by_player(INGAME_WALLET)
+        .unwrap();
+    let room = dapp.state.room_manager.get_room(&room_id).unwrap();
+    assert_eq!(room.stake, double_stake);
+    assert_eq!(serde_json::to_value(room).unwrap()["stake"], double_stake);
+    let private_code = room.private_code.clone();
+
+    //#NOTE: the opponent only holds STAKE, half of what the room asks for
+    fund_player(&mut dapp, OPPONENT_METAMASK_WALLET, OPPONENT_INGAME_WALLET).await;
+    let join_room = DazzleRequest::JoinPrivateRoom(JoinPrivateRoomRequest {
+        user: OPPONENT_INGAME_WALLET.to_owned(),
+        private_code,
+        base64_character_list: String::new(),
+        nft_characters: party(OPPONENT_METAMASK_WALLET),
+    });
+    let (status, outputs) = dapp
+        .advance(OPPONENT_INGAME_WALLET, versioned_payload(join_room))
+        .await;
+    assert!(matches!(status, FinishStatus::Reject));
+    let report: DazzleReport = serde_json::from_str(&outputs.report_payloads()[0]).unwrap();
+    assert!(report.error_message.contains("Insufficient tournament fee"));
+}
+
+#[tokio::test]
+async fn withdrawal_vouchers_are_indexed_and_marked_executed() {
+    let mut dapp = TestDapp::new();
+    dapp.advance(ETHER_PORTAL, deposit_payload(METAMASK_WALLET, 1_000))
+        .await;
+
+    let withdraw = |amount: &str| {
+        DazzleRequest::Withdraw(WithdrawRequest {
+            amount: amount.to_owned(),
+            token: None,
+        })
+    };
+    let batch = DazzleRequest::Batch(vec![withdraw("100"), withdraw("200")]);
+    let (status, _) = dapp
+        .advance(METAMASK_WALLET, versioned_payload(batch))
+        .await;
+    assert!(matches!(status, FinishStatus::Accept));
+
+    let owner = Address::from_str(METAMASK_WALLET).unwrap();
+    let metas = dapp.state.balance_manager.get_voucher_meta(&owner).unwrap();
+    assert_eq!(
+        metas
+            .iter()
+            .map(|meta| (meta.input_index.as_str(), meta.voucher_index))
+            .collect::<Vec<_>>(),
+        vec![("1", 0), ("1", 1)]
+    );
+
+    let (_, outputs) = dapp.inspect("outputs/1").await;
+    let indexed: InputOutputs = serde_json::from_str(&outputs.report_payloads()[0]).unwrap();
+    assert_eq!(
+        indexed
+            .vouchers
+            .iter()
+            .map(|voucher| voucher.index)
+            .collect::<Vec<_>>(),
+        vec![0, 1]
+    );
+    assert_eq!(
+        indexed
+            .notices
+            .iter()
+            .map(|notice| notice.index)
+            .collect::<Vec<_>>(),
+        vec![0, 1]
+    );
+
+    let mark_executed = || {
+        versioned_payload(DazzleRequest::MarkVoucherExecuted(
+            MarkVoucherExecutedRequest {
+                input_index: 1,
+                voucher_index: 1,
+            },
+        ))
+    };
+    let (status, outputs) = dapp.advance(METAMASK_WALLET, mark_executed()).await;
+    assert!(matches!(status, FinishStatus::Accept));
+    assert!(matches!(
+        outputs.dazzle_notices()[0].notice_type,
+        NoticeType::VoucherExecuted
+    ));
+    let metas = dapp.state.balance_manager.get_voucher_meta(&owner).unwrap();
+    assert_eq!(metas.len(), 1);
+    assert_eq!(metas[0].voucher_index, 0);
+
+    let (status, outputs) = dapp.advance(METAMASK_WALLET, mark_executed()).await;
+    assert!(matches!(status, FinishStatus::Reject));
+    let report: DazzleReport = serde_json::from_str(&outputs.report_payloads()[0]).unwrap();
+    assert!(report.error_message.contains("No pending voucher"));
+}
+
+#[tokio::test]
+async fn deposit_request_outputs_follow_the_deposit_notice() {
+    let mut dapp = TestDapp::new();
+    let request = DazzleRequest::Withdraw(WithdrawRequest {
+        amount: "400".to_owned(),
+        token: None,
+    });
+    let (status, _) = dapp
+        .advance(
+            ETHER_PORTAL,
+            deposit_and_act_payload(METAMASK_WALLET, 1_000, request),
+        )
+        .await;
+    assert!(matches!(status, FinishStatus::Accept));
+
+    let indexed = dapp.state.output_index_manager.get_outputs(0).unwrap();
+    assert!(matches!(
+        indexed.notices[0].notice_type,
+        NoticeType::Deposit
+    ));
+    assert!(matches!(
+        indexed.notices[1].notice_type,
+        NoticeType::Withdraw
+    ));
+    assert_eq!(indexed.notices[1].index, 1);
+    assert_eq!(indexed.vouchers[0].index, 0);
+
+    let owner = Address::from_str(METAMASK_WALLET).unwrap();
+    let metas = dapp.state.balance_manager.get_voucher_meta(&owner).unwrap();
+    assert_eq!(metas[0].voucher_index, 0);
+}
+
+fn withdraw_input(input_index: u64) -> AdvanceRequest {
+    AdvanceRequest {
+        metadata: Some(AdvanceMetadata {
+            msg_sender: METAMASK_WALLET.to_owned(),
+            input_index,
+            block_number: input_index,
+            timestamp: 1_700_000_000,
+        }),
+        payload: versioned_payload(DazzleRequest::Withdraw(WithdrawRequest {
+            amount: "400".to_owned(),
+            token: None,
+        })),
+    }
+}
+
+#[tokio::test]
+async fn output_indices_come_from_the_rollup_server() {
+    let mut dapp = TestDapp::new();
+    dapp.advance(ETHER_PORTAL, deposit_payload(METAMASK_WALLET, 1_000))
+        .await;
+
+    //#NOTE: the server already holds a notice of this input, so the dapp's outputs are numbered after it
+    let mut outputs = OutputRecorder::new();
+    send_notice(&mut outputs, NoticeType::Error, "", DAPP_ADDRESS, None)
+        .await
+        .unwrap();
+    let status = process_advance_state(
+        withdraw_input(1),
+        &mut dapp.state,
+        &mut outputs,
+        &test_config(),
+        DAPP_ADDRESS,
+    )
+    .await;
+    assert!(matches!(status, FinishStatus::Accept));
+    let indexed = dapp.state.output_index_manager.get_outputs(1).unwrap();
+    assert_eq!(indexed.notices[0].index, 1);
+    assert_eq!(indexed.vouchers[0].index, 0);
+
+    let mut outputs = FaultyOutputs::new(Fault::MisnumberVouchers);
+    let status = process_advance_state(
+        withdraw_input(2),
+        &mut dapp.state,
+        &mut outputs,
+        &test_config(),
+        DAPP_ADDRESS,
+    )
+    .await;
+    assert!(matches!(status, FinishStatus::Reject));
+    assert!(dapp.state.output_index_manager.get_outputs(2).is_none());
+    //#NOTE: replayed to a snapshot including them, the inputs finish as they did the first time
+    assert!(matches!(
+        dapp.state.output_index_manager.get_finish_status(1),
+        FinishStatus::Accept
+    ));
+    assert!(matches!(
+        dapp.state.output_index_manager.get_finish_status(2),
+        FinishStatus::Reject
+    ));
+    let owner = Address::from_str(METAMASK_WALLET).unwrap();
+    assert_eq!(
+        dapp.state
+            .balance_manager
+            .get_voucher_meta(&owner)
+            .unwrap()
+            .len(),
+        1
+    );
+    assert_eq!(ether_balance(&dapp, METAMASK_WALLET), U256::from(600));
+}
+
+#[tokio::test]
+async fn ledger_inspect_reports_every_balance_change() {
+    let mut dapp = TestDapp::new();
+    dapp.advance(ETHER_PORTAL, deposit_payload(METAMASK_WALLET, 1_000))
+        .await;
+    let transfer = DazzleRequest::InternalTransfer(TransferRequest {
+        from_address: METAMASK_WALLET.to_owned(),
+        to_address: INGAME_WALLET.to_owned(),
+        amount: "400".to_owned(),
+        token: None,
+    });
+    dapp.advance(METAMASK_WALLET, versioned_payload(transfer))
+        .await;
+    let withdraw = DazzleRequest::Withdraw(WithdrawRequest {
+        amount: "100".to_owned(),
+        token: None,
+    });
+    dapp.advance(METAMASK_WALLET, versioned_payload(withdraw))
+        .await;
+
+    let (_, outputs) = dapp.inspect(&format!("ledger/{}", METAMASK_WALLET)).await;
+    let ledger: LedgerResponse = serde_json::from_str(&outputs.report_payloads()[0]).unwrap();
+    assert_eq!(ledger.total, 3);
+    assert_eq!(
+        ledger
+            .entries
+            .iter()
+            .map(|entry| (entry.kind, entry.input_index, entry.balance.as_str()))
+            .collect::<Vec<_>>(),
+        vec![
+            (LedgerEntryKind::Deposit, 0, "1000"),
+            (LedgerEntryKind::TransferOut, 1, "600"),
+            (LedgerEntryKind::Withdraw, 2, "500"),
+        ]
+    );
+    assert_eq!(ledger.entries[0].counterparty, ETHER_PORTAL);
+    assert_eq!(ledger.entries[1].counterparty, INGAME_WALLET);
+    assert_eq!(ledger.entries[2].counterparty, DAPP_ADDRESS);
+
+    //#NOTE: a rejected input leaves no trace in the ledger
+    let overdraw = DazzleRequest::Withdraw(WithdrawRequest {
+        amount: "10000".to_owned(),
+        token: None,
+    });
+    let (status, _) = dapp
+        .advance(METAMASK_WALLET, versioned_payload(overdraw))
+        .await;
+    assert!(matches!(status, FinishStatus::Reject));
+    let owner = Address::from_str(METAMASK_WALLET).unwrap();
+    assert_eq!(dapp.state.balance_manager.get_ledger(&owner, 0, 10).0, 3);
+}
+
+const PLAYERS: [(&str, &str); 2] = [
+    (METAMASK_WALLET, INGAME_WALLET),
+    (OPPONENT_METAMASK_WALLET, OPPONENT_INGAME_WALLET),
+];
+const FUNDED_WALLETS: [&str; 4] = [
+    METAMASK_WALLET,
+    INGAME_WALLET,
+    OPPONENT_METAMASK_WALLET,
+    OPPONENT_INGAME_WALLET,
+];
+
+#[derive(Debug, Clone, Copy)]
+enum FundsOperation {
+    Deposit(usize, u64),
+    InternalTransfer(usize, usize, u64),
+    TransferBalance(usize, usize, u64),
+    Withdraw(usize, u64),
+    CreateRoom(usize, Option<u64>),
+    JoinRoom(usize),
+    CancelRoom(usize),
+    QuitGame(usize),
+}
+
+fn funds_operation() -> impl Strategy<Value = FundsOperation> {
+    let stake: u64 = STAKE.parse().unwrap();
+    let max_stake: u64 = MAX_STAKE.parse().unwrap();
+    let wallet = 0..FUNDED_WALLETS.len();
+    let player = 0..PLAYERS.len();
+    let amount = 1..3 * stake;
+    prop_oneof![
+        (wallet.clone(), amount.clone()).prop_map(|(w, a)| FundsOperation::Deposit(w, a)),
+        (wallet.clone(), wallet.clone(), amount.clone())
+            .prop_map(|(from, to, a)| FundsOperation::InternalTransfer(from, to, a)),
+        (wallet.clone(), wallet.clone(), amount.clone())
+            .prop_map(|(from, to, a)| FundsOperation::TransferBalance(from, to, a)),
+        (wallet, amount).prop_map(|(w, a)| FundsOperation::Withdraw(w, a)),
+        //#NOTE: the stakes go past both bounds, so rooms are refused too
+        (player.clone(), prop::option::of(0..2 * max_stake))
+            .prop_map(|(p, stake)| FundsOperation::CreateRoom(p, stake)),
+        player.clone().prop_map(FundsOperation::JoinRoom),
+        player.clone().prop_map(FundsOperation::CancelRoom),
+        player.prop_map(FundsOperation::QuitGame),
+    ]
+}
+
+fn funds_operation_request(dapp: &TestDapp, operation: &FundsOperation) -> (String, String) {
+    match *operation {
+        FundsOperation::Deposit(wallet, amount) => (
+            ETHER_PORTAL.to_owned(),
+            deposit_payload(FUNDED_WALLETS[wallet], amount),
+        ),
+        FundsOperation::InternalTransfer(from, to, amount) => (
+            FUNDED_WALLETS[from].to_owned(),
+            versioned_payload(DazzleRequest::InternalTransfer(TransferRequest {
+                from_address: FUNDED_WALLETS[from].to_owned(),
+                to_address: FUNDED_WALLETS[to].to_owned(),
+                amount: amount.to_string(),
+                token: None,
+            })),
+        ),
+        FundsOperation::TransferBalance(from, to, amount) => (
+            FUNDED_WALLETS[from].to_owned(),
+            versioned_payload(DazzleRequest::TransferBalance(TransferRequest {
+                from_address: FUNDED_WALLETS[from].to_owned(),
+                to_address: FUNDED_WALLETS[to].to_owned(),
+                amount: amount.to_string(),
+                token: None,
+            })),
+        ),
+        FundsOperation::Withdraw(wallet, amount) => (
+            FUNDED_WALLETS[wallet].to_owned(),
+            versioned_payload(DazzleRequest::Withdraw(WithdrawRequest {
+                amount: amount.to_string(),
+                token: None,
+            })),
+        ),
+        FundsOperation::CreateRoom(player, stake) => {
+            let (metamask_wallet, ingame_wallet) = PLAYERS[player];
+            let create_room = DazzleRequest::CreatePrivateRoom(CreatePrivateRoomRequest {
+                user: ingame_wallet.to_owned(),
+                base64_character_list: String::new(),
+                nft_characters: party(metamask_wallet),
+                stake_token: None,
+                stake: stake.map(|stake| stake.to_string()),
+            });
+            (ingame_wallet.to_owned(), versioned_payload(create_room))
+        }
+        FundsOperation::JoinRoom(player) => {
+            let (metamask_wallet, ingame_wallet) = PLAYERS[player];
+            let (_, other_wallet) = PLAYERS[(player + 1) % PLAYERS.len()];
+            //#NOTE: without a room of the other player the code is unknown and the join is refused
+            let private_code = dapp
+                .state
+                .room_manager
+                .get_uuid_by_player(other_wallet)
+                .and_then(|uuid| dapp.state.room_manager.get_room(uuid))
+                .map_or_else(|| "000000".to_owned(), |room| room.private_code.clone());
+            let join_room = DazzleRequest::JoinPrivateRoom(JoinPrivateRoomRequest {
+                user: ingame_wallet.to_owned(),
+                private_code,
+                base64_character_list: String::new(),
+                nft_characters: party(metamask_wallet),
+            });
+            (ingame_wallet.to_owned(), versioned_payload(join_room))
+        }
+        FundsOperation::CancelRoom(player) => {
+            let (_, ingame_wallet) = PLAYERS[player];
+            let cancel_room = DazzleRequest::CancelRoom(CancelRoomRequest {
+                user: ingame_wallet.to_owned(),
+            });
+            (ingame_wallet.to_owned(), versioned_payload(cancel_room))
+        }
+        FundsOperation::QuitGame(player) => {
+            let (_, ingame_wallet) = PLAYERS[player];
+            let quit_game = DazzleRequest::QuitGame(QuitGameRequest {
+                user: ingame_wallet.to_owned(),
+            });
+            (ingame_wallet.to_owned(), versioned_payload(quit_game))
+        }
+    }
+}
+
+fn voucher_amount(voucher: &Voucher) -> U256 {
+    let payload = hex::decode(voucher.payload.trim_start_matches("0x")).unwrap();
+    U256::from_big_endian(&payload[payload.len() - 32..])
+}
+
+//#NOTE: every wei is either on a balance, held in the escrow of a room, or paid out with a voucher
+fn held_funds(dapp: &TestDapp) -> U256 {
+    let owners = FUNDED_WALLETS
+        .iter()
+        .chain([ADMIN_WALLET_ADDRESS].iter())
+        .map(|owner| Address::from_str(owner).unwrap())
+        .collect::<HashSet<_>>();
+    let balances = owners.iter().fold(U256::zero(), |acc, owner| {
+        acc + dapp
+            .state
+            .balance_manager
+            .get_balance(&ETHER, owner)
+            .copied()
+            .unwrap_or_default()
+    });
+    dapp.state
+        .room_manager
+        .iter_rooms()
+        .filter_map(|room| dapp.state.balance_manager.get_escrow(&room.uuid))
+        .fold(balances, |acc, escrow| acc + escrow.total())
+}
+
+async fn run_funds_operations(operations: Vec<FundsOperation>) {
+    let mut dapp = TestDapp::new();
+    for (metamask_wallet, ingame_wallet) in PLAYERS {
+        deposit_party(&mut dapp, metamask_wallet).await;
+        let (status, _) = attach(&mut dapp, metamask_wallet, attach_request(ingame_wallet)).await;
+        assert!(matches!(status, FinishStatus::Accept));
+    }
+    let mut deposited = U256::zero();
+    let mut paid_out = U256::zero();
+
+    for operation in operations {
+        let before = full_state(&dapp.state);
+        let sender_balance = match operation {
+            FundsOperation::InternalTransfer(from, _, _) => {
+                Some(ether_balance(&dapp, FUNDED_WALLETS[from]))
+            }
+            _ => None,
+        };
+        let (msg_sender, payload) = funds_operation_request(&dapp, &operation);
+        let (status, outputs) = dapp.advance(&msg_sender, payload).await;
+
+        match operation {
+            FundsOperation::Deposit(..) => assert!(matches!(status, FinishStatus::Accept)),
+            FundsOperation::InternalTransfer(_, _, amount) => assert_eq!(
+                matches!(status, FinishStatus::Accept),
+                U256::from(amount) <= sender_balance.unwrap(),
+                "{:?}",
+                operation
+            ),
+            _ => (),
+        }
+        match status {
+            FinishStatus::Accept => {
+                if let FundsOperation::Deposit(_, amount) = operation {
+                    deposited += U256::from(amount);
+                }
+                paid_out = outputs
+                    .vouchers
+                    .iter()
+                    .fold(paid_out, |acc, voucher| acc + voucher_amount(voucher));
+            }
+            FinishStatus::Reject => {
+                assert_eq!(full_state(&dapp.state), before, "{:?}", operation)
+            }
+        }
+
+        dapp.state.balance_manager.check_accounting().unwrap();
+        assert_eq!(held_funds(&dapp), deposited - paid_out, "{:?}", operation);
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn random_funds_operations_conserve_funds(
+        operations in prop::collection::vec(funds_operation(), 1..40)
+    ) {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(run_funds_operations(operations));
+    }
+}
+
+#[tokio::test]
+async fn room_request_for_another_user_is_rejected() {
+    let mut dapp = TestDapp::new();
+    let room_id = create_funded_room(&mut dapp).await;
+    fund_player(&mut dapp, OPPONENT_METAMASK_WALLET, OPPONENT_INGAME_WALLET).await;
+
+    //#NOTE: an attached wallet can't quit the game of another player
+    let quit_game = DazzleRequest::QuitGame(QuitGameRequest {
+        user: INGAME_WALLET.to_owned(),
+    });
+    let (status, outputs) = dapp
+        .advance(OPPONENT_INGAME_WALLET, versioned_payload(quit_game))
+        .await;
+    assert!(matches!(status, FinishStatus::Reject));
+    assert!(outputs.report_payloads()[0].contains("can't act as user"));
+    assert!(dapp.state.room_manager.get_room(&room_id).is_some());
+    assert_eq!(
+        dapp.state.room_manager.get_uuid_by_player(INGAME_WALLET),
+        Some(&room_id)
+    );
+}
+
+#[tokio::test]
+async fn metamask_wallet_acts_as_its_ingame_wallet() {
+    let mut dapp = TestDapp::new();
+    let room_id = create_funded_room(&mut dapp).await;
+
+    //#NOTE: the metamask wallet may not name itself, its player is the attached in-game wallet
+    let quit_as_metamask = DazzleRequest::QuitGame(QuitGameRequest {
+        user: METAMASK_WALLET.to_owned(),
+    });
+    let (status, _) = dapp
+        .advance(METAMASK_WALLET, versioned_payload(quit_as_metamask))
+        .await;
+    assert!(matches!(status, FinishStatus::Reject));
+
+    let cancel_room = DazzleRequest::CancelRoom(CancelRoomRequest {
+        user: INGAME_WALLET.to_uppercase().replacen("0X", "0x", 1),
+    });
+    let (status, _) = dapp
+        .advance(METAMASK_WALLET, versioned_payload(cancel_room))
+        .await;
+    assert!(matches!(status, FinishStatus::Accept));
+    assert!(dapp.state.room_manager.get_room(&room_id).is_none());
+}
+
+async fn attach(
+    dapp: &mut TestDapp,
+    metamask_wallet: &str,
+    req: AttachIngameWalletRequest,
+) -> (FinishStatus, OutputRecorder) {
+    dapp.advance(
+        metamask_wallet,
+        versioned_payload(DazzleRequest::AttachIngameWallet(req)),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn attach_requires_signature_of_ingame_wallet() {
+    let mut dapp = TestDapp::new();
+    //#NOTE: signed by the opponent's in-game key, so it proves nothing about INGAME_WALLET
+    let signed_by_other_key = AttachIngameWalletRequest {
+        ingame_wallet_address: INGAME_WALLET.to_owned(),
+        nonce: 0,
+        signature: OPPONENT_INGAME_TO_METAMASK_SIGNATURE.to_owned(),
+        ..attach_request(INGAME_WALLET)
+    };
+    let signed_for_other_nonce = AttachIngameWalletRequest {
+        nonce: 1,
+        ..attach_request(INGAME_WALLET)
+    };
+    let malformed = AttachIngameWalletRequest {
+        signature: "0x1234".to_owned(),
+        ..attach_request(INGAME_WALLET)
+    };
+
+    for req in [signed_by_other_key, signed_for_other_nonce, malformed] {
+        let (status, outputs) = attach(&mut dapp, METAMASK_WALLET, req).await;
+        assert!(matches!(status, FinishStatus::Reject));
+        assert!(outputs.report_payloads()[0].contains("Invalid signature"));
+    }
+    //#NOTE: the signature is bound to the metamask wallet it was made for
+    let (status, _) = attach(
+        &mut dapp,
+        OPPONENT_METAMASK_WALLET,
+        attach_request(INGAME_WALLET),
+    )
+    .await;
+    assert!(matches!(status, FinishStatus::Reject));
+    assert!(!dapp
+        .state
+        .ingame_wallet_manager
+        .is_ingame_wallet_attached(&Address::from_str(INGAME_WALLET).unwrap()));
+}
+
+fn rotate_to_opponent_key() -> DazzleRequest {
+    DazzleRequest::RotateSessionKey(AttachIngameWalletRequest {
+        ingame_wallet_address: OPPONENT_INGAME_WALLET.to_owned(),
+        signature: OPPONENT_INGAME_TO_METAMASK_SIGNATURE.to_owned(),
+        ..attach_request(INGAME_WALLET)
+    })
+}
+
+async fn inspect_session(dapp: &TestDapp) -> Option<SessionKeyState> {
+    let (_, outputs) = dapp.inspect(&format!("wallet/{}", METAMASK_WALLET)).await;
+    let wallet: WalletResponse = serde_json::from_str(&outputs.report_payloads()[0]).unwrap();
+    wallet.session
+}
+
+#[tokio::test]
+async fn session_key_stops_acting_once_expired() {
+    let mut dapp = TestDapp::new();
+    dapp.advance(ETHER_PORTAL, deposit_payload(METAMASK_WALLET, 1_000))
+        .await;
+    let expires_at = dapp.timestamp + 60;
+    let req = AttachIngameWalletRequest {
+        expires_at: Some(expires_at),
+        ..attach_request(INGAME_WALLET)
+    };
+    let (status, _) = attach(&mut dapp, METAMASK_WALLET, req).await;
+    assert!(matches!(status, FinishStatus::Accept));
+
+    let session = inspect_session(&dapp).await.unwrap();
+    assert_eq!(session.ingame_wallet, INGAME_WALLET);
+    assert_eq!(session.scope, SessionScope::GameplayAndStake);
+    assert_eq!(session.expires_at, expires_at);
+
+    dapp.timestamp = expires_at;
+    let create_room = DazzleRequest::CreatePrivateRoom(CreatePrivateRoomRequest {
+        user: INGAME_WALLET.to_owned(),
+        base64_character_list: String::new(),
+        nft_characters: party(METAMASK_WALLET),
+        stake_token: None,
+        stake: None,
+    });
+    let (status, outputs) = dapp
+        .advance(INGAME_WALLET, versioned_payload(create_room))
+        .await;
+    assert!(matches!(status, FinishStatus::Reject));
+    assert!(outputs.report_payloads()[0].contains("Invalid ingame-wallet"));
+    assert!(inspect_session(&dapp).await.is_none());
+
+    //#NOTE: the expired key can be attached again with a new nonce, but not with its old signature
+    let (status, outputs) = attach(&mut dapp, METAMASK_WALLET, attach_request(INGAME_WALLET)).await;
+    assert!(matches!(status, FinishStatus::Reject));
+    assert!(outputs.report_payloads()[0].contains("Nonce 0 was already used"));
+}
+
+#[tokio::test]
+async fn session_expiry_is_bounded() {
+    let mut dapp = TestDapp::new();
+    let now = dapp.timestamp;
+    for expires_at in [now, now + MAX_SESSION_DURATION + 1] {
+        let req = AttachIngameWalletRequest {
+            expires_at: Some(expires_at),
+            ..attach_request(INGAME_WALLET)
+        };
+        let (status, outputs) = attach(&mut dapp, METAMASK_WALLET, req).await;
+        assert!(matches!(status, FinishStatus::Reject));
+        assert!(outputs.report_payloads()[0].contains("Session expiry"));
+    }
+
+    let (status, _) = attach(&mut dapp, METAMASK_WALLET, attach_request(INGAME_WALLET)).await;
+    assert!(matches!(status, FinishStatus::Accept));
+    assert_eq!(
+        inspect_session(&dapp).await.unwrap().expires_at,
+        now + SESSION_DURATION
+    );
+}
+
+#[tokio::test]
+async fn gameplay_session_key_cannot_stake_or_move_funds() {
+    let mut dapp = TestDapp::new();
+    dapp.advance(ETHER_PORTAL, deposit_payload(METAMASK_WALLET, 1_000))
+        .await;
+    dapp.advance(
+        ETHER_PORTAL,
+        deposit_payload(INGAME_WALLET, STAKE.parse().unwrap()),
+    )
+    .await;
+    let req = AttachIngameWalletRequest {
+        scope: SessionScope::Gameplay,
+        ..attach_request(INGAME_WALLET)
+    };
+    attach(&mut dapp, METAMASK_WALLET, req).await;
+
+    let create_room = DazzleRequest::CreatePrivateRoom(CreatePrivateRoomRequest {
+        user: INGAME_WALLET.to_owned(),
+        base64_character_list: String::new(),
+        nft_characters: party(METAMASK_WALLET),
+        stake_token: None,
+        stake: None,
+    });
+    let (status, outputs) = dapp
+        .advance(INGAME_WALLET, versioned_payload(create_room))
+        .await;
+    assert!(matches!(status, FinishStatus::Reject));
+    assert!(outputs.report_payloads()[0].contains("isn't allowed to stake"));
+
+    let transfer = DazzleRequest::TransferBalance(TransferRequest {
+        from_address: METAMASK_WALLET.to_owned(),
+        to_address: INGAME_WALLET.to_owned(),
+        amount: "1000".to_owned(),
+        token: None,
+    });
+    let (status, _) = dapp
+        .advance(INGAME_WALLET, versioned_payload(transfer))
+        .await;
+    assert!(matches!(status, FinishStatus::Reject));
+    assert_eq!(ether_balance(&dapp, METAMASK_WALLET), U256::from(1_000));
+}
+
+#[tokio::test]
+async fn session_key_is_revoked_by_owner_or_itself() {
+    let mut dapp = TestDapp::new();
+    attach(&mut dapp, METAMASK_WALLET, attach_request(INGAME_WALLET)).await;
+    let revoke = || {
+        versioned_payload(DazzleRequest::RevokeSessionKey(RevokeSessionKeyRequest {
+            ingame_wallet_address: INGAME_WALLET.to_owned(),
+        }))
+    };
+
+    let (status, _) = dapp.advance(OPPONENT_INGAME_WALLET, revoke()).await;
+    assert!(matches!(status, FinishStatus::Reject));
+    assert!(inspect_session(&dapp).await.is_some());
+
+    let (status, outputs) = dapp.advance(INGAME_WALLET, revoke()).await;
+    assert!(matches!(status, FinishStatus::Accept));
+    let notices = outputs.dazzle_notices();
+    assert!(matches!(
+        notices[0].notice_type,
+        NoticeType::SessionKeyRevoked
+    ));
+    assert_eq!(notices[0].user, METAMASK_WALLET);
+    assert!(inspect_session(&dapp).await.is_none());
+    assert!(!dapp
+        .state
+        .ingame_wallet_manager
+        .is_ingame_wallet_attached(&Address::from_str(INGAME_WALLET).unwrap()));
+
+    let (status, _) = dapp.advance(METAMASK_WALLET, revoke()).await;
+    assert!(matches!(status, FinishStatus::Reject));
+}
+
+#[tokio::test]
+async fn session_key_rotation() {
+    let mut dapp = TestDapp::new();
+    //#NOTE: attach only starts the first session, later keys are rotated in
+    let (status, _) = dapp
+        .advance(METAMASK_WALLET, versioned_payload(rotate_to_opponent_key()))
+        .await;
+    assert!(matches!(status, FinishStatus::Reject));
+
+    create_funded_room(&mut dapp).await;
+    let (status, outputs) = attach(
+        &mut dapp,
+        METAMASK_WALLET,
+        AttachIngameWalletRequest {
+            ingame_wallet_address: OPPONENT_INGAME_WALLET.to_owned(),
+            signature: OPPONENT_INGAME_TO_METAMASK_SIGNATURE.to_owned(),
+            ..attach_request(INGAME_WALLET)
+        },
+    )
+    .await;
+    assert!(matches!(status, FinishStatus::Reject));
+    assert!(outputs.report_payloads()[0].contains("already has a session key"));
+
+    //#NOTE: the room of the current key can't be taken over by the new one
+    let (status, outputs) = dapp
+        .advance(METAMASK_WALLET, versioned_payload(rotate_to_opponent_key()))
+        .await;
+    assert!(matches!(status, FinishStatus::Reject));
+    assert!(outputs.report_payloads()[0].contains("is playing in a room"));
+
+    let cancel_room = DazzleRequest::CancelRoom(CancelRoomRequest {
+        user: INGAME_WALLET.to_owned(),
+    });
+    dapp.advance(INGAME_WALLET, versioned_payload(cancel_room))
+        .await;
+    let (status, _) = dapp
+        .advance(METAMASK_WALLET, versioned_payload(rotate_to_opponent_key()))
+        .await;
+    assert!(matches!(status, FinishStatus::Accept));
+    assert_eq!(
+        inspect_session(&dapp).await.unwrap().ingame_wallet,
+        OPPONENT_INGAME_WALLET
+    );
+    assert!(!dapp
+        .state
+        .ingame_wallet_manager
+        .is_ingame_wallet_attached(&Address::from_str(INGAME_WALLET).unwrap()));
+}
+
+#[tokio::test]
+async fn signatures_for_another_chain_are_refused() {
+    let mut dapp = TestDapp::new();
+    let other_chain = AttachIngameWalletRequest {
+        signature: INGAME_ATTACH_OTHER_CHAIN_SIGNATURE.to_owned(),
+        ..attach_request(INGAME_WALLET)
+    };
+    let (status, outputs) = attach(&mut dapp, METAMASK_WALLET, other_chain).await;
+    assert!(matches!(status, FinishStatus::Reject));
+    assert!(outputs.report_payloads()[0].contains("Invalid signature"));
+
+    let room_id = start_pvp_room(&mut dapp).await;
+    let turn = room_turn(&dapp, &room_id);
+    let next_move = legal_move(&dapp, &room_id);
+    let key = player_key(&next_move.user);
+    let signed = relay_on_chain(RelayedAction::Move(next_move), turn, 1, key, 1);
+    let (status, outputs) = dapp.advance(RELAYER, signed).await;
+    assert!(matches!(status, FinishStatus::Reject));
+    assert!(outputs.report_payloads()[0].contains("Invalid signature"));
+    assert_eq!(room_turn(&dapp, &room_id), turn);
+}
+
+fn relay(action: RelayedAction, turn: u64, nonce: u64, key: &str) -> String {
+    relay_on_chain(action, turn, nonce, key, CHAIN_ID)
+}
+
+fn relay_on_chain(
+    action: RelayedAction,
+    turn: u64,
+    nonce: u64,
+    key: &str,
+    chain_id: u64,
+) -> String {
+    let mut req = RelayedRequest {
+        action,
+        turn,
+        nonce,
+        signature: String::new(),
+    };
+    let typed_data =
+        relayed_action_typed_data(&Address::from_str(DAPP_ADDRESS).unwrap(), chain_id, &req)
+            .unwrap();
+    let signing_key = SigningKey::from_bytes(&hex::decode(key).unwrap()).unwrap();
+    let signature: recoverable::Signature = signing_key.sign(&typed_data);
+    let bz = signature.as_ref();
+    req.signature = format!("0x{}{:02x}", hex::encode(&bz[..64]), bz[64] + 27);
+
+    versioned_payload(DazzleRequest::Relayed(req))
+}
+
+fn player_key(player: &str) -> &'static str {
+    match player {
+        INGAME_WALLET => INGAME_KEY,
+        OPPONENT_INGAME_WALLET => OPPONENT_INGAME_KEY,
+        _ => unreachable!("no key for {}", player),
+    }
+}
+
+async fn start_pvp_room(dapp: &mut TestDapp) -> Uuid {
+    let room_id = create_funded_room(dapp).await;
+    fund_player(dapp, OPPONENT_METAMASK_WALLET, OPPONENT_INGAME_WALLET).await;
+    let private_code = dapp
+        .state
+        .room_manager
+        .get_room(&room_id)
+        .unwrap()
+        .private_code
+        .clone();
+    let join_room = DazzleRequest::JoinPrivateRoom(JoinPrivateRoomRequest {
+        user: OPPONENT_INGAME_WALLET.to_owned(),
+        private_code,
+        base64_character_list: String::new(),
+        nft_characters: party(OPPONENT_METAMASK_WALLET),
+    });
+    let (status, _) = dapp
+        .advance(OPPONENT_INGAME_WALLET, versioned_payload(join_room))
+        .await;
+    assert!(matches!(status, FinishStatus::Accept));
+    room_id
+}
+
+fn room_turn(dapp: &TestDapp, room_id: &Uuid) -> u64 {
+    dapp.state.room_manager.get_room(room_id).unwrap().game.turn as u64
+}
+
+//#NOTE: tried on a copy of the room manager, with the rng seed of the next input
+fn legal_move(dapp: &TestDapp, room_id: &Uuid) -> MoveRequest {
+    let mut room_manager = dapp.state.room_manager.clone();
+    let room = room_manager
+        .update_room_rng(room_id, dapp.timestamp + dapp.input_index)
+        .unwrap();
+    let mover = room.game.current_active_player_idx;
+    let directions = [
+        Direction::Up,
+        Direction::Down,
+        Direction::Left,
+        Direction::Right,
+    ];
+
+    for x in 0..BOARD_WIDTH {
+        for y in 0..BOARD_HEIGHT {
+            for direction in directions {
+                let req = MoveRequest {
+                    room_id: *room_id,
+                    user: room.gamers[mover].id.clone(),
+                    action: MoveAction { x, y, direction },
+                    attacker_id: room.gamers[mover].character_uuid_list[0],
+                    defender_id: room.gamers[1 - mover].character_uuid_list[0],
+                };
+                if room_manager
+                    .clone()
+                    .move_action(
+                        room_id,
+                        &req.user,
+                        &req.action,
+                        &req.attacker_id,
+                        &req.defender_id,
+                    )
+                    .is_ok()
+                {
+                    return req;
+                }
+            }
+        }
+    }
+    panic!("no legal move in room {}", room_id);
+}
+
+#[tokio::test]
+async fn relayer_submits_signed_moves() {
+    let mut dapp = TestDapp::new();
+    let room_id = start_pvp_room(&mut dapp).await;
+
+    let turn = room_turn(&dapp, &room_id);
+    let first_move = legal_move(&dapp, &room_id);
+    let first_player = first_move.user.clone();
+    let signed = relay(
+        RelayedAction::Move(first_move.clone()),
+        turn,
+        1,
+        player_key(&first_player),
+    );
+    let (status, outputs) = dapp.advance(RELAYER, signed.clone()).await;
+    assert!(matches!(status, FinishStatus::Accept));
+    assert!(matches!(
+        outputs.dazzle_notices()[0].notice_type,
+        NoticeType::Room
+    ));
+    assert_eq!(room_turn(&dapp, &room_id), turn + 1);
+
+    //#NOTE: the same signed move can't be replayed, its turn is over
+    let (status, outputs) = dapp.advance(RELAYER, signed).await;
+    assert!(matches!(status, FinishStatus::Reject));
+    assert!(outputs.report_payloads()[0].contains("signed for turn"));
+
+    //#NOTE: nonces are kept per player
+    let second_move = legal_move(&dapp, &room_id);
+    assert_ne!(second_move.user, first_player);
+    let key = player_key(&second_move.user);
+    let (status, _) = dapp
+        .advance(
+            RELAYER,
+            relay(RelayedAction::Move(second_move), turn + 1, 1, key),
+        )
+        .await;
+    assert!(matches!(status, FinishStatus::Accept));
+
+    let third_move = legal_move(&dapp, &room_id);
+    let (status, outputs) = dapp
+        .advance(
+            RELAYER,
+            relay(
+                RelayedAction::Move(third_move),
+                turn + 2,
+                1,
+                player_key(&first_player),
+            ),
+        )
+        .await;
+    assert!(matches!(status, FinishStatus::Reject));
+    assert!(outputs.report_payloads()[0].contains("Nonce 1 was already used"));
+
+    let third_move = legal_move(&dapp, &room_id);
+    let (status, _) = dapp
+        .advance(
+            RELAYER,
+            relay(
+                RelayedAction::Move(third_move),
+                turn + 2,
+                2,
+                player_key(&first_player),
+            ),
+        )
+        .await;
+    assert!(matches!(status, FinishStatus::Accept));
+    assert_eq!(room_turn(&dapp, &room_id), turn + 3);
+}
+
+#[tokio::test]
+async fn relayed_action_must_be_signed_by_its_player() {
+    let mut dapp = TestDapp::new();
+    let room_id = start_pvp_room(&mut dapp).await;
+    let turn = room_turn(&dapp, &room_id);
+    let next_move = legal_move(&dapp, &room_id);
+    let other_key = match next_move.user.as_str() {
+        INGAME_WALLET => OPPONENT_INGAME_KEY,
+        _ => INGAME_KEY,
+    };
+
+    let skill = ActiveSkillsRequest {
+        room_id,
+        user: next_move.user.clone(),
+        caster_id: next_move.attacker_id,
+        ally_target_id: next_move.attacker_id,
+        rival_target_id: None,
+    };
+    let (status, outputs) = dapp
+        .advance(
+            RELAYER,
+            relay(RelayedAction::ActivateSkill(skill), turn, 1, other_key),
+        )
+        .await;
+    assert!(matches!(status, FinishStatus::Reject));
+    assert!(outputs.report_payloads()[0].contains("Invalid signature"));
+
+    //#NOTE: the signature covers every field of the action
+    let signed = relay(
+        RelayedAction::Move(next_move.clone()),
+        turn,
+        1,
+        player_key(&next_move.user),
+    );
+    let mut tampered: VersionedRequest =
+        serde_json::from_slice(&hex::decode(signed.trim_start_matches("0x")).unwrap()).unwrap();
+    if let DazzleRequest::Relayed(RelayedRequest {
+        action: RelayedAction::Move(req),
+        ..
+    }) = &mut tampered.request
+    {
+        req.defender_id = req.attacker_id;
+    }
+    let tampered = format!("0x{}", hex::encode(serde_json::to_vec(&tampered).unwrap()));
+    let (status, outputs) = dapp.advance(RELAYER, tampered).await;
+    assert!(matches!(status, FinishStatus::Reject));
+    assert!(outputs.report_payloads()[0].contains("Invalid signature"));
+    assert_eq!(room_turn(&dapp, &room_id), turn);
+
+    let next_move = legal_move(&dapp, &room_id);
+    let key = player_key(&next_move.user);
+    let signed = relay(RelayedAction::Move(next_move), turn, 1, key);
+    let (status, _) = dapp.advance(RELAYER, signed).await;
+    assert!(matches!(status, FinishStatus::Accept));
+    assert_eq!(room_turn(&dapp, &room_id), turn + 1);
+}
+
+//#NOTE: the skill of the mover's first character on the rival's first one, if it can be cast with the rng seed of the next input
+fn castable_skill(dapp: &TestDapp, room_id: &Uuid) -> Option<ActiveSkillsRequest> {
+    let mut room_manager = dapp.state.room_manager.clone();
+    let room = room_manager
+        .update_room_rng(room_id, dapp.timestamp + dapp.input_index)
+        .unwrap();
+    let mover = room.game.current_active_player_idx;
+    let req = ActiveSkillsRequest {
+        room_id: *room_id,
+        user: room.gamers[mover].id.clone(),
+        caster_id: room.gamers[mover].character_uuid_list[0],
+        ally_target_id: room.gamers[mover].character_uuid_list[0],
+        rival_target_id: Some(room.gamers[1 - mover].character_uuid_list[0]),
+    };
+
+    room_manager
+        .skill_action(
+            room_id,
+            &req.user,
+            req.caster_id,
+            req.ally_target_id,
+            req.rival_target_id,
+        )
+        .ok()
+        .map(|_| req)
+}
+
+#[tokio::test]
+async fn skill_transcript_replays_identically() {
+    let mut dapp = TestDapp::new();
+    let room_id = start_pvp_room(&mut dapp).await;
+
+    let mut skill_cast = false;
+    for _ in 0..200 {
+        let (user, request, is_skill) = match castable_skill(&dapp, &room_id) {
+            Some(skill) => (
+                skill.user.clone(),
+                DazzleRequest::ActivateSkill(skill),
+                true,
+            ),
+            None => {
+                let next_move = legal_move(&dapp, &room_id);
+                (
+                    next_move.user.clone(),
+                    DazzleRequest::Move(next_move),
+                    false,
+                )
+            }
+        };
+        let (status, _) = dapp.advance(&user, versioned_payload(request)).await;
+        skill_cast |= is_skill && matches!(status, FinishStatus::Accept);
+
+        let room = dapp.state.room_manager.get_room(&room_id).unwrap();
+        if skill_cast || room.game_over_result.is_some() {
+            break;
+        }
+    }
+    assert!(skill_cast);
+
+    let inputs = dapp
+        .inputs
+        .iter()
+        .map(|data| RollupResponse {
+            request_type: RequestType::AdvanceState.to_string(),
+            data: data.clone(),
+        })
+        .collect::<Vec<_>>();
+    let replay = || async {
+        let transcript = replay_inputs(inputs.clone(), &test_config(), INGAME_WALLET)
+            .await
+            .unwrap();
+        serde_json::to_value(&transcript).unwrap()
+    };
+
+    let first = replay().await;
+    assert_eq!(first, replay().await);
+    assert_eq!(first.as_array().unwrap().len(), dapp.inputs.len());
+}
+
+fn full_state(state: &DappState) -> Value {
+    serde_json::to_value(state).unwrap()
+}
+
+#[tokio::test]
+async fn restored_snapshot_plays_on_identically() {
+    let mut dapp = TestDapp::new();
+    let room_id = start_pvp_room(&mut dapp).await;
+    dapp.advance(
+        ERC721_PORTAL,
+        erc721_deposit_payload(CHARACTER_COLLECTION, METAMASK_WALLET, 7),
+    )
+    .await;
+    let next_move = legal_move(&dapp, &room_id);
+    let player = next_move.user.clone();
+    let (status, _) = dapp
+        .advance(&player, versioned_payload(DazzleRequest::Move(next_move)))
+        .await;
+    assert!(matches!(status, FinishStatus::Accept));
+
+    let path = env::temp_dir().join(format!("dazzle-snapshot-{}.json", Uuid::new_v4()));
+    write_snapshot(&path, dapp.input_index - 1, DAPP_ADDRESS, &dapp.state).unwrap();
+    let snapshot = read_snapshot(&path).unwrap().unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let mut restored = TestDapp {
+        state: snapshot.state,
+        input_index: dapp.input_index,
+        timestamp: dapp.timestamp,
+        inputs: vec![],
+    };
+
+    //#NOTE: both play on identically, down to the rng and the hidden character stats
+    let next_move = legal_move(&dapp, &room_id);
+    let player = next_move.user.clone();
+    for dapp in [&mut dapp, &mut restored] {
+        let (status, _) = dapp
+            .advance(
+                &player,
+                versioned_payload(DazzleRequest::Move(next_move.clone())),
+            )
+            .await;
+        assert!(matches!(status, FinishStatus::Accept));
+    }
+    assert_eq!(full_state(&restored.state), full_state(&dapp.state));
+}
+
+#[tokio::test]
+async fn unreadable_snapshot_refuses_inputs_with_an_exception() {
+    let path = env::temp_dir().join(format!("dazzle-snapshot-{}.json", Uuid::new_v4()));
+    std::fs::write(&path, "not a snapshot").unwrap();
+    let config = RollupConfig {
+        snapshot_path: Some(path.to_string_lossy().into_owned()),
+        ..test_config()
+    };
+    let restored = restore_state(&config);
+    //#NOTE: the snapshot is left as it was
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "not a snapshot");
+    std::fs::remove_file(&path).unwrap();
+    let reason = match restored {
+        Err(e) => e.to_string(),
+        Ok(_) => panic!("an unreadable snapshot was restored"),
+    };
+
+    let rollup = RollupResponse {
+        request_type: RequestType::AdvanceState.to_string(),
+        data: AdvanceRequest {
+            metadata: Some(AdvanceMetadata {
+                msg_sender: METAMASK_WALLET.to_owned(),
+                input_index: 3,
+                block_number: 3,
+                timestamp: 1_700_000_000,
+            }),
+            payload: String::from("0x"),
+        },
+    };
+    let mut outputs = OutputRecorder::new();
+    let status = refuse_input(&mut outputs, &rollup, &reason).await;
+    assert!(matches!(status, FinishStatus::Reject));
+
+    assert_eq!(outputs.exceptions.len(), 1);
+    let payload = hex::decode(outputs.exceptions[0].payload.trim_start_matches("0x")).unwrap();
+    let exception: DazzleException = serde_json::from_slice(&payload).unwrap();
+    assert_eq!(exception.input_index, Some(3));
+    assert_eq!(exception.error_message, reason);
+}
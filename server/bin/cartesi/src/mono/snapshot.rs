@@ -0,0 +1,229 @@
+use super::dapp_state::DappState;
+use domain::game_core::ServerError;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::fs;
+use std::path::Path;
+
+pub const SNAPSHOT_VERSION: u32 = 1;
+//#NOTE: every snapshot writes the whole state, game histories included, so writing one per input would cost more than
+//the input itself once rooms pile up. In exchange a restart loses up to 99 inputs unless the rollup node replays them
+pub const DEFAULT_SNAPSHOT_INTERVAL: u64 = 100;
+
+/*
+    Full dapp state as written to disk in host mode, json
+    Example: {"version":1,"input_index":41,"dapp_address":"0x70ac...","state":{"room_manager":{...},...}}
+*/
+#[derive(Debug, Serialize)]
+struct SnapshotRef<'a> {
+    version: u32,
+    input_index: u64,
+    dapp_address: &'a str,
+    state: &'a DappState,
+}
+
+//#NOTE: its `version` is checked before the rest is parsed
+#[derive(Debug, Deserialize)]
+pub struct Snapshot {
+    //#NOTE: index of the last advance input the state includes
+    pub input_index: u64,
+    //#NOTE: the address relay input isn't sent again after a restart
+    pub dapp_address: String,
+    pub state: DappState,
+}
+
+/// Write the snapshot to a temporary file first, so a crash while writing keeps the previous one
+pub fn write_snapshot(
+    path: &Path,
+    input_index: u64,
+    dapp_address: &str,
+    state: &DappState,
+) -> Result<(), ServerError> {
+    let snapshot = SnapshotRef {
+        version: SNAPSHOT_VERSION,
+        input_index,
+        dapp_address,
+        state,
+    };
+    let bz =
+        serde_json::to_vec(&snapshot).map_err(|e| ServerError::InvalidSnapshot(e.to_string()))?;
+
+    let file_error =
+        |e: std::io::Error| ServerError::SnapshotFile(path.display().to_string(), e.to_string());
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, bz).map_err(file_error)?;
+    fs::rename(&tmp_path, path).map_err(file_error)
+}
+
+/// Read the snapshot at `path`, `None` when there is none yet
+pub fn read_snapshot(path: &Path) -> Result<Option<Snapshot>, ServerError> {
+    let bz = match fs::read(path) {
+        Ok(bz) => bz,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(ServerError::SnapshotFile(
+                path.display().to_string(),
+                e.to_string(),
+            ))
+        }
+    };
+
+    let value: Value =
+        serde_json::from_slice(&bz).map_err(|e| ServerError::InvalidSnapshot(e.to_string()))?;
+    let version = value
+        .get("version")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| ServerError::InvalidSnapshot(String::from("missing version")))?;
+    if version != SNAPSHOT_VERSION as u64 {
+        return Err(ServerError::UnsupportedSnapshotVersion(
+            version.try_into().unwrap_or(u32::MAX),
+        ));
+    }
+
+    serde_json::from_value(value)
+        .map(Some)
+        .map_err(|e| ServerError::InvalidSnapshot(e.to_string()))
+}
+
+/// Whether the advance input `input_index` still has to be applied to a restored state waiting for `next_input_index`.
+/// The inputs the state already includes are skipped, a later one means inputs were lost between the snapshot and now
+pub fn is_new_input(next_input_index: u64, input_index: u64) -> Result<bool, ServerError> {
+    match input_index.cmp(&next_input_index) {
+        Ordering::Less => Ok(false),
+        Ordering::Equal => Ok(true),
+        Ordering::Greater => Err(ServerError::MissingInputs(next_input_index, input_index)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mono::balance_manager::ETHER;
+    use crate::mono::ingame_wallet_manager::SessionKey;
+    use atb_types::prelude::uuid::Uuid;
+    use domain::cartesi::{LedgerEntryKind, SessionScope};
+    use domain::game_core::character::CharacterV2;
+    use domain::game_core::config::{GameplayConfigManager, STAKE};
+    use domain::game_core::room_manager::GameMode;
+    use ethers_core::types::{Address, U256};
+    use std::str::FromStr;
+
+    const DAPP_ADDRESS: &str = "0x70ac08179605af2d9e75782b8decdd3c22aa4d0c";
+    const OWNER: &str = "0x2af645839ea4ca82452afd195e210420e7cc1f90";
+    const PLAYER: &str = "0x70997970c51812dc3a010c7d01b50e0d17dc79c8";
+    const OPPONENT: &str = "0x90f79bf6eb2c4f870365e785982e1f101e93b906";
+    const COLLECTION: &str = "0xe7f1725e7734ce288f8367e1bb143e90bb3f0512";
+
+    fn snapshot_path() -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("dazzle-snapshot-{}.json", Uuid::new_v4()))
+    }
+
+    fn full_state(state: &DappState) -> Value {
+        serde_json::to_value(state).unwrap()
+    }
+
+    //#NOTE: a running room, a balance with its ledger, a session key and a deposited NFT
+    fn populated_state() -> DappState {
+        let mut state = DappState::new();
+        let owner = Address::from_str(OWNER).unwrap();
+        let character = CharacterV2::roll_new(1, &GameplayConfigManager::new());
+
+        let room_status = state
+            .room_manager
+            .create_private_room(
+                PLAYER,
+                &[character.clone()],
+                None,
+                GameMode::Cartesi,
+                Some(7),
+                None,
+                STAKE,
+            )
+            .unwrap();
+        state
+            .room_manager
+            .join_private_room(
+                OPPONENT,
+                &room_status.private_code,
+                &[character.clone()],
+                None,
+                None,
+            )
+            .unwrap();
+        state.balance_manager.deposit(
+            &ETHER,
+            &owner,
+            U256::from(1_000),
+            LedgerEntryKind::Deposit,
+            "",
+        );
+        let session = SessionKey {
+            ingame_wallet: Address::from_str(PLAYER).unwrap(),
+            scope: SessionScope::GameplayAndStake,
+            expires_at: 1_700_000_000,
+        };
+        state
+            .ingame_wallet_manager
+            .attach_ingame_wallet(&owner, session, 0)
+            .unwrap();
+        state.nft_inventory_manager.deposit(
+            &owner,
+            &Address::from_str(COLLECTION).unwrap(),
+            U256::from(7),
+            Some(character),
+        );
+        state
+    }
+
+    #[test]
+    fn snapshot_restores_every_field() {
+        let state = populated_state();
+        let path = snapshot_path();
+        write_snapshot(&path, 41, DAPP_ADDRESS, &state).unwrap();
+        let snapshot = read_snapshot(&path).unwrap().unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(snapshot.input_index, 41);
+        assert_eq!(snapshot.dapp_address, DAPP_ADDRESS);
+        let written = full_state(&state);
+        assert_eq!(full_state(&snapshot.state), written);
+
+        //#NOTE: the client view of a room leaves the rng out, the snapshot can't
+        let (room_id, room) = written["room_manager"]["room_map"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .next()
+            .unwrap();
+        assert!(room["game"].get("rng").is_some());
+        let room_id = Uuid::from_str(room_id).unwrap();
+        let client_view = serde_json::to_value(state.room_manager.get_room(&room_id)).unwrap();
+        assert!(client_view["game"].get("rng").is_none());
+    }
+
+    #[test]
+    fn snapshot_of_another_version_is_refused() {
+        let path = snapshot_path();
+        assert!(read_snapshot(&path).unwrap().is_none());
+
+        fs::write(&path, serde_json::json!({ "version": 2 }).to_string()).unwrap();
+        let result = read_snapshot(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            result,
+            Err(ServerError::UnsupportedSnapshotVersion(2))
+        ));
+    }
+
+    #[test]
+    fn resumed_inputs_are_skipped_until_the_next_one() {
+        assert!(!is_new_input(42, 0).unwrap());
+        assert!(!is_new_input(42, 41).unwrap());
+        assert!(is_new_input(42, 42).unwrap());
+        assert!(matches!(
+            is_new_input(42, 44),
+            Err(ServerError::MissingInputs(42, 44))
+        ));
+    }
+}
@@ -0,0 +1,115 @@
+use base64::{engine::general_purpose, Engine as _};
+use domain::cartesi::{
+    AdvanceMetadata, AdvanceRequest, DazzleNotice, NoticeType, RequestType, RollupResponse,
+};
+use domain::game_core::ServerError;
+use ethers_core::utils::hex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+/// One line of an input file.
+///
+/// Either a full `/finish` response as returned by the rollup http server, or a bare advance request.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum InputRecord {
+    Rollup(RollupResponse),
+    Advance(AdvanceRequest),
+}
+
+impl From<InputRecord> for RollupResponse {
+    fn from(record: InputRecord) -> Self {
+        match record {
+            InputRecord::Rollup(rollup) => rollup,
+            InputRecord::Advance(data) => RollupResponse {
+                request_type: RequestType::AdvanceState.to_string(),
+                data,
+            },
+        }
+    }
+}
+
+/// Read a JSONL input file, or stdin when `path` is None. Blank lines are skipped.
+pub fn read_inputs(path: Option<&Path>) -> Result<Vec<RollupResponse>, ServerError> {
+    let reader: Box<dyn BufRead> = match path {
+        Some(p) => Box::new(BufReader::new(
+            File::open(p).map_err(|_| ServerError::InvalidFilePath)?,
+        )),
+        None => Box::new(BufReader::new(std::io::stdin())),
+    };
+
+    let mut inputs = vec![];
+    for (line_no, line) in reader.lines().enumerate() {
+        let line = line.map_err(|_| ServerError::InvalidFilePath)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let record: InputRecord = serde_json::from_str(&line).map_err(|e| {
+            log::error!("Failed to parse input at line {}: {}", line_no + 1, e);
+            ServerError::InvalidJson
+        })?;
+        inputs.push(record.into());
+    }
+
+    Ok(inputs)
+}
+
+/// Advance inputs recorded without metadata get the next input index and a default sender
+pub fn fill_metadata(
+    rollup: &mut RollupResponse,
+    input_index: u64,
+    msg_sender: &str,
+    timestamp: u64,
+) {
+    if rollup.data.metadata.is_none() {
+        rollup.data.metadata = Some(AdvanceMetadata {
+            msg_sender: msg_sender.to_owned(),
+            input_index,
+            block_number: input_index,
+            timestamp,
+        });
+    }
+}
+
+/// `DazzleNotice` with its base64 content unpacked
+#[derive(Debug, Clone, Serialize)]
+pub struct DecodedNotice {
+    pub notice_type: NoticeType,
+    pub user: String,
+    pub balance: Option<String>,
+    pub content: Value,
+}
+
+pub fn decode_hex_payload(payload: &str) -> Result<Vec<u8>, ServerError> {
+    hex::decode(payload.trim_start_matches("0x")).map_err(|_| ServerError::InvalidHex)
+}
+
+/// Content is shown as JSON when possible, as plain text otherwise (e.g. the attached wallet address)
+fn to_readable(bz: &[u8]) -> Value {
+    serde_json::from_slice(bz)
+        .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(bz).into_owned()))
+}
+
+/// hex -> `DazzleNotice` json -> base64 content
+pub fn decode_notice(payload: &str) -> Result<DecodedNotice, ServerError> {
+    let bz = decode_hex_payload(payload)?;
+    let notice: DazzleNotice = serde_json::from_slice(&bz).map_err(|_| ServerError::InvalidJson)?;
+    let content_bz = general_purpose::STANDARD
+        .decode(&notice.base64_content)
+        .map_err(|_| ServerError::InvalidRequest)?;
+
+    Ok(DecodedNotice {
+        notice_type: notice.notice_type,
+        user: notice.user,
+        balance: notice.balance,
+        content: to_readable(&content_bz),
+    })
+}
+
+pub fn decode_report(payload: &str) -> Result<Value, ServerError> {
+    Ok(to_readable(&decode_hex_payload(payload)?))
+}
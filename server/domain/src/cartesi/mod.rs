@@ -1,7 +1,17 @@
+pub mod portals;
+
+use crate::game_core::board::MoveAction;
+use crate::game_core::character::CharacterV2;
 use crate::game_core::game::Room;
+use crate::game_core::room_manager::GameMode;
+use crate::game_core::ServerError;
 use atb_types::prelude::uuid::Uuid;
+use ethers_core::abi::{encode, Token};
+use ethers_core::types::Address;
+use ethers_core::utils::{hex, keccak256};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::str::FromStr;
 use strum_macros::{Display as StrumDisplay, EnumString};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -32,6 +42,92 @@ pub struct DazzleReport {
     pub error_message: String,
 }
 
+//#NOTE: inspect results larger than this are split into several `ReportChunk` reports
+pub const MAX_REPORT_CONTENT_SIZE: usize = 64 * 1024;
+
+/// One part of a report too large to be sent at once, `sequence` starts from 0
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportChunk {
+    pub sequence: u32,
+    pub total: u32,
+    pub content: String,
+}
+
+/// Split `content` into chunks of at most `max_size` bytes, never cutting through a utf-8 character
+pub fn split_report_content(content: &str, max_size: usize) -> Vec<ReportChunk> {
+    let mut parts = vec![];
+    let mut rest = content;
+    while !rest.is_empty() {
+        let mut end = max_size.min(rest.len());
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        let (part, remaining) = rest.split_at(end);
+        parts.push(part);
+        rest = remaining;
+    }
+
+    let total = parts.len() as u32;
+    parts
+        .into_iter()
+        .enumerate()
+        .map(|(sequence, part)| ReportChunk {
+            sequence: sequence as u32,
+            total,
+            content: part.to_owned(),
+        })
+        .collect()
+}
+
+/// Rebuild the report content from the reports of a single inspect call.
+///
+/// Reports that are not `ReportChunk`s are returned as is, so small results can go through this as well.
+pub fn reassemble_reports(reports: &[Report]) -> Result<String, ServerError> {
+    let decoded = reports
+        .iter()
+        .map(|report| {
+            let bz = hex::decode(report.payload.trim_start_matches("0x"))
+                .map_err(|_| ServerError::InvalidHex)?;
+            String::from_utf8(bz).map_err(|_| ServerError::InvalidRequest)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut chunks = match decoded
+        .iter()
+        .map(|content| serde_json::from_str::<ReportChunk>(content))
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(chunks) if !chunks.is_empty() => chunks,
+        _ => return Ok(decoded.concat()),
+    };
+
+    chunks.sort_by_key(|chunk| chunk.sequence);
+    let total = chunks[0].total as usize;
+    let complete = chunks.len() == total
+        && chunks
+            .iter()
+            .enumerate()
+            .all(|(i, chunk)| chunk.sequence as usize == i && chunk.total as usize == total);
+    if !complete {
+        return Err(ServerError::IncompleteReportChunks(chunks.len(), total));
+    }
+
+    Ok(chunks.into_iter().map(|chunk| chunk.content).collect())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Exception {
+    pub payload: String,
+}
+
+/// Reported when handling a request panicked
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DazzleException {
+    pub request_type: String,
+    pub input_index: Option<u64>,
+    pub error_message: String,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Voucher {
     //#NOTE: this should be the contract address to handle the voucher
@@ -44,6 +140,12 @@ pub struct VoucherMeta {
     pub timestamp: u64,
     pub input_index: String,
     pub amount: String,
+    //#NOTE: ERC-20 token address, None for Ether
+    #[serde(default)]
+    pub token: Option<String>,
+    //#NOTE: together with `input_index`, locates the voucher to execute on L1
+    #[serde(default)]
+    pub voucher_index: u64,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -66,16 +168,346 @@ pub struct AdvanceRequest {
 }
 
 /*
-    Example: {"operation":"create_private_room","data":"{\"user\":\"Test\"}"}
+    Legacy input format, still accepted until every client sends `VersionedRequest`
+    Example: {"operation":"create_private_room","data":"eyJ1c2VyIjoiVGVzdCJ9"}
 */
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct GameRequest {
     //#NOTE: must be one of the DazzleOperation
     pub operation: String,
-    //#NOTE: hex-encoded Dazzle json request (FindRoomRequest, CreatePrivateRoomRequest, JoinPrivateRoomRequest, CancelRoomRequest, ActiveSkillsRequest, MoveRequest, WithdrawRequest)
+    //#NOTE: base64-encoded Dazzle json request (FindRoomRequest, CreatePrivateRoomRequest, JoinPrivateRoomRequest, CancelRoomRequest, ActiveSkillsRequest, MoveRequest, WithdrawRequest)
     pub data: String,
 }
 
+pub const DAZZLE_REQUEST_VERSION: u32 = 1;
+
+/*
+    Current input format, hex-encoded json with the request inlined
+    Example: {"version":1,"operation":"cancel_room","data":{"user":"0x70997970c51812dc3a010c7d01b50e0d17dc79c8"}}
+*/
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct VersionedRequest {
+    pub version: u32,
+    #[serde(flatten)]
+    pub request: DazzleRequest,
+}
+
+impl VersionedRequest {
+    pub fn new(request: DazzleRequest) -> Self {
+        VersionedRequest {
+            version: DAZZLE_REQUEST_VERSION,
+            request,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "operation", content = "data", rename_all = "snake_case")]
+pub enum DazzleRequest {
+    CreatePrivateRoom(CreatePrivateRoomRequest),
+    JoinPrivateRoom(JoinPrivateRoomRequest),
+    CancelRoom(CancelRoomRequest),
+    GameOver(FindRoomRequest),
+    Move(MoveRequest),
+    ActivateSkill(ActiveSkillsRequest),
+    QuitGame(QuitGameRequest),
+    TransferBalance(TransferRequest),
+    Withdraw(WithdrawRequest),
+    //#NOTE: same data as TransferBalance, but credits `to_address` in the dapp instead of paying it on L1
+    InternalTransfer(TransferRequest),
+    AttachIngameWallet(AttachIngameWalletRequest),
+    //#NOTE: replaces the session key of the sender, same data as AttachIngameWallet
+    RotateSessionKey(AttachIngameWalletRequest),
+    RevokeSessionKey(RevokeSessionKeyRequest),
+    WithdrawNft(WithdrawNftRequest),
+    MarkVoucherExecuted(MarkVoucherExecutedRequest),
+    //#NOTE: a move or skill signed by its player, sent by any relayer
+    Relayed(RelayedRequest),
+    //#NOTE: runs the operations in order within one input, any rejected operation rejects them all
+    Batch(Vec<DazzleRequest>),
+}
+
+impl DazzleRequest {
+    pub fn operation(&self) -> DazzleOperation {
+        match self {
+            DazzleRequest::CreatePrivateRoom(_) => DazzleOperation::CreatePrivateRoom,
+            DazzleRequest::JoinPrivateRoom(_) => DazzleOperation::JoinPrivateRoom,
+            DazzleRequest::CancelRoom(_) => DazzleOperation::CancelRoom,
+            DazzleRequest::GameOver(_) => DazzleOperation::GameOver,
+            DazzleRequest::Move(_) => DazzleOperation::Move,
+            DazzleRequest::ActivateSkill(_) => DazzleOperation::ActivateSkill,
+            DazzleRequest::QuitGame(_) => DazzleOperation::QuitGame,
+            DazzleRequest::TransferBalance(_) => DazzleOperation::TransferBalance,
+            DazzleRequest::Withdraw(_) => DazzleOperation::Withdraw,
+            DazzleRequest::InternalTransfer(_) => DazzleOperation::InternalTransfer,
+            DazzleRequest::AttachIngameWallet(_) => DazzleOperation::AttachIngameWallet,
+            DazzleRequest::RotateSessionKey(_) => DazzleOperation::RotateSessionKey,
+            DazzleRequest::RevokeSessionKey(_) => DazzleOperation::RevokeSessionKey,
+            DazzleRequest::WithdrawNft(_) => DazzleOperation::WithdrawNft,
+            DazzleRequest::MarkVoucherExecuted(_) => DazzleOperation::MarkVoucherExecuted,
+            DazzleRequest::Relayed(_) => DazzleOperation::Relayed,
+            DazzleRequest::Batch(_) => DazzleOperation::Batch,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FindRoomRequest {
+    pub user: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CreatePrivateRoomRequest {
+    pub user: String,
+    //#NOTE: only used when `nft_characters` is empty
+    #[serde(default)]
+    pub base64_character_list: String,
+    //#NOTE: deposited ERC-721 characters owned by the sender, in party order
+    #[serde(default)]
+    pub nft_characters: Vec<NftCharacterId>,
+    //#NOTE: ERC-20 token the room is staked in, Ether when omitted
+    #[serde(default)]
+    pub stake_token: Option<String>,
+    //#NOTE: stake of every player in wei (or token units), within MIN_STAKE and MAX_STAKE, STAKE when omitted
+    #[serde(default)]
+    pub stake: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct JoinPrivateRoomRequest {
+    pub user: String,
+    pub private_code: String,
+    #[serde(default)]
+    pub base64_character_list: String,
+    #[serde(default)]
+    pub nft_characters: Vec<NftCharacterId>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct NftCharacterId {
+    pub collection: String,
+    //#NOTE: decimal uint256
+    pub token_id: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct QuitGameRequest {
+    pub user: String,
+}
+
+//#NOTE: `from_address` must be the sender or the metamask wallet it is attached to
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TransferRequest {
+    pub from_address: String,
+    pub to_address: String,
+    pub amount: String,
+    //#NOTE: ERC-20 token to transfer, Ether when omitted
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+//#NOTE: withdraws the sender's own balance to L1 through a voucher
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WithdrawRequest {
+    pub amount: String,
+    //#NOTE: ERC-20 token to withdraw, Ether when omitted
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AttachIngameWalletRequest {
+    pub ingame_wallet_address: String,
+    //#NOTE: above the last nonce the in-game wallet was attached with
+    pub nonce: u64,
+    //#NOTE: hex EIP-191 signature of `attach_ingame_wallet_message` by the in-game key, proving the sender controls it
+    pub signature: String,
+    //#NOTE: unix timestamp the session key expires at, SESSION_DURATION after the input when omitted
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+    #[serde(default)]
+    pub scope: SessionScope,
+}
+
+//#NOTE: sent by the metamask wallet or by the session key itself
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RevokeSessionKeyRequest {
+    pub ingame_wallet_address: String,
+}
+
+/// What an in-game wallet (session key) may do on behalf of its metamask wallet
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionScope {
+    //#NOTE: room operations which don't lock a stake
+    #[default]
+    Gameplay,
+    //#NOTE: also create and join staked rooms, and move or withdraw the funds of the metamask wallet
+    GameplayAndStake,
+}
+
+/// The message an in-game wallet signs with `personal_sign` to be attached to `metamask_wallet` (lowercase hex).
+/// It names the dapp (lowercase hex) and the chain, so the signature can't be replayed on another deployment
+pub fn attach_ingame_wallet_message(
+    metamask_wallet: &str,
+    nonce: u64,
+    dapp_address: &Address,
+    chain_id: u64,
+) -> String {
+    format!(
+        "Attach Dazzle in-game wallet to {} with nonce {} for dapp {:#x} on chain {}",
+        metamask_wallet, nonce, dapp_address, chain_id
+    )
+}
+
+/// A move or skill signed offline with the player's in-game key, so any sender can relay it and pay its gas
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RelayedRequest {
+    pub action: RelayedAction,
+    //#NOTE: turn of the room the action was signed for, it can't be replayed on another turn
+    pub turn: u64,
+    //#NOTE: above the last nonce relayed for the player
+    pub nonce: u64,
+    //#NOTE: hex EIP-712 signature of `relayed_action_typed_data` by the `user` of the action
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "operation", content = "data", rename_all = "snake_case")]
+pub enum RelayedAction {
+    Move(MoveRequest),
+    ActivateSkill(ActiveSkillsRequest),
+}
+
+impl RelayedAction {
+    pub fn room_id(&self) -> &Uuid {
+        match self {
+            RelayedAction::Move(req) => &req.room_id,
+            RelayedAction::ActivateSkill(req) => &req.room_id,
+        }
+    }
+
+    pub fn user(&self) -> &str {
+        match self {
+            RelayedAction::Move(req) => &req.user,
+            RelayedAction::ActivateSkill(req) => &req.user,
+        }
+    }
+}
+
+impl From<RelayedAction> for DazzleRequest {
+    fn from(action: RelayedAction) -> Self {
+        match action {
+            RelayedAction::Move(req) => DazzleRequest::Move(req),
+            RelayedAction::ActivateSkill(req) => DazzleRequest::ActivateSkill(req),
+        }
+    }
+}
+
+const EIP712_DOMAIN_TYPE: &str =
+    "EIP712Domain(string name,string version,uint256 chainId,address verifyingContract)";
+const RELAYED_MOVE_TYPE: &str = "Move(address user,string roomId,uint256 turn,uint256 nonce,uint32 x,uint32 y,uint8 direction,string attackerId,string defenderId)";
+const RELAYED_SKILL_TYPE: &str = "ActivateSkill(address user,string roomId,uint256 turn,uint256 nonce,string casterId,string allyTargetId,string rivalTargetId)";
+
+fn hash_string(value: &str) -> Token {
+    Token::FixedBytes(keccak256(value.as_bytes()).to_vec())
+}
+
+/// The EIP-712 encoding (`0x1901 ‖ domainSeparator ‖ hashStruct`) of a relayed request in the domain `Dazzle` version `1` of the dapp
+/// on `chain_id`, its keccak256 is what `eth_signTypedData_v4` signs.
+///
+/// The action is typed `Move` or `ActivateSkill`, uuids are their hyphenated strings and a missing `rivalTargetId` is empty
+pub fn relayed_action_typed_data(
+    dapp_address: &Address,
+    chain_id: u64,
+    req: &RelayedRequest,
+) -> Result<Vec<u8>, ServerError> {
+    let user = Address::from_str(req.action.user())
+        .map_err(|_| ServerError::InvalidAddress(req.action.user().to_owned()))?;
+    let mut fields = vec![
+        Token::Address(user),
+        hash_string(&req.action.room_id().to_string()),
+        Token::Uint(req.turn.into()),
+        Token::Uint(req.nonce.into()),
+    ];
+    let type_hash = match &req.action {
+        RelayedAction::Move(req) => {
+            fields.extend([
+                Token::Uint(req.action.x.into()),
+                Token::Uint(req.action.y.into()),
+                Token::Uint((req.action.direction as u8).into()),
+                hash_string(&req.attacker_id.to_string()),
+                hash_string(&req.defender_id.to_string()),
+            ]);
+            keccak256(RELAYED_MOVE_TYPE)
+        }
+        RelayedAction::ActivateSkill(req) => {
+            let rival_target_id = req.rival_target_id.map(|id| id.to_string());
+            fields.extend([
+                hash_string(&req.caster_id.to_string()),
+                hash_string(&req.ally_target_id.to_string()),
+                hash_string(rival_target_id.as_deref().unwrap_or_default()),
+            ]);
+            keccak256(RELAYED_SKILL_TYPE)
+        }
+    };
+    fields.insert(0, Token::FixedBytes(type_hash.to_vec()));
+
+    let domain_separator = keccak256(encode(&[
+        Token::FixedBytes(keccak256(EIP712_DOMAIN_TYPE).to_vec()),
+        hash_string("Dazzle"),
+        hash_string("1"),
+        Token::Uint(chain_id.into()),
+        Token::Address(*dapp_address),
+    ]));
+    let struct_hash = keccak256(encode(&fields));
+
+    Ok([&[0x19, 0x01][..], &domain_separator, &struct_hash].concat())
+}
+
+//#NOTE: sent by the owner once the voucher is executed on L1, the dapp can't observe the execution itself
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MarkVoucherExecutedRequest {
+    pub input_index: u64,
+    pub voucher_index: u64,
+}
+
+//#NOTE: the NFT is sent back to its owner on L1
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WithdrawNftRequest {
+    pub collection: String,
+    pub token_id: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CancelRoomRequest {
+    pub user: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MoveRequest {
+    pub room_id: Uuid,
+    pub user: String,
+    pub action: MoveAction,
+    pub attacker_id: Uuid,
+    pub defender_id: Uuid,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct GetRoomEventRequest {
+    pub room_id: Uuid,
+    pub current_state_len: usize,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ActiveSkillsRequest {
+    pub room_id: Uuid,
+    pub user: String,
+    pub caster_id: Uuid,
+    pub ally_target_id: Uuid,
+    pub rival_target_id: Option<Uuid>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, StrumDisplay, EnumString)]
 #[strum(serialize_all = "snake_case")]
 pub enum DazzleOperation {
@@ -87,7 +519,15 @@ pub enum DazzleOperation {
     ActivateSkill,
     QuitGame,
     TransferBalance,
+    Withdraw,
+    InternalTransfer,
     AttachIngameWallet,
+    RotateSessionKey,
+    RevokeSessionKey,
+    WithdrawNft,
+    MarkVoucherExecuted,
+    Relayed,
+    Batch,
 }
 
 #[derive(Debug, Clone, Serialize, StrumDisplay, EnumString, Deserialize)]
@@ -97,18 +537,274 @@ pub enum NoticeType {
     CancelRoom,
     GameResult,
     Deposit,
+    Erc20Deposit,
     Transfer,
+    Withdraw,
+    InternalTransfer,
     AttachIngameWallet,
+    NftDeposit,
+    NftWithdraw,
+    StakeLocked,
+    StakePayout,
+    StakeRefund,
+    PlatformFee,
+    VoucherExecuted,
+    SessionKeyRevoked,
     Error, //#TODO: we'll generate ErrorNotice to record that there is error occurred in Cartesi dapp, but we need to accpet all the input
 }
 
+//#NOTE: content of the StakeLocked / StakePayout / StakeRefund notices, `balance` of the notice is the new balance of `user`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StakeNotice {
+    pub room_id: Uuid,
+    //#NOTE: ERC-20 token of the stake, None for Ether
+    pub token: Option<String>,
+    pub amount: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InspectResponse {
     pub user_to_room: HashMap<String, Uuid>,
     pub balance: HashMap<String, String>,
+    //#NOTE: owner -> ERC-20 token -> balance, `balance` only holds Ether
+    #[serde(default)]
+    pub token_balances: HashMap<String, HashMap<String, String>>,
     pub voucher_meta: HashMap<String, Vec<VoucherMeta>>,
     pub room_data: HashMap<Uuid, Room>,
     pub ingame_wallets: HashMap<String, String>,
+    //#NOTE: owner -> deposited ERC-721 characters
+    #[serde(default)]
+    pub nft_inventory: HashMap<String, Vec<NftCharacter>>,
+}
+
+/// An ERC-721 token held by the dapp on behalf of `owner`.
+/// `character` holds the stats carried by the deposit, the token can't be played without them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NftCharacter {
+    pub collection: String,
+    pub token_id: String,
+    pub owner: String,
+    pub character: Option<CharacterV2>,
+}
+
+/*
+    Inspect payload is the hex-encoded query path, e.g. `rooms?mode=cartesi&status=waiting`
+    An empty path still returns the full `InspectResponse`
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub enum InspectQuery {
+    FullState,
+    Room(Uuid),
+    PlayerRoom(String),
+    Balance(String),
+    Vouchers(String),
+    Wallet(String),
+    Inventory(String),
+    Outputs(u64),
+    Ledger {
+        address: String,
+        offset: usize,
+        limit: usize,
+    },
+    Rooms {
+        mode: Option<GameMode>,
+        status: Option<RoomPhase>,
+    },
+}
+
+impl FromStr for InspectQuery {
+    type Err = ServerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().trim_matches('/');
+        let (path, query) = s.split_once('?').unwrap_or((s, ""));
+        let segments: Vec<&str> = path.split('/').filter(|seg| !seg.is_empty()).collect();
+
+        match segments.as_slice() {
+            [] => Ok(InspectQuery::FullState),
+            ["room", uuid] => Uuid::parse_str(uuid)
+                .map(InspectQuery::Room)
+                .map_err(|_| ServerError::InvalidInspectQuery(s.to_owned())),
+            ["player", address, "room"] => Ok(InspectQuery::PlayerRoom(address.to_string())),
+            ["balance", address] => Ok(InspectQuery::Balance(address.to_string())),
+            ["vouchers", address] => Ok(InspectQuery::Vouchers(address.to_string())),
+            ["wallet", address] => Ok(InspectQuery::Wallet(address.to_string())),
+            ["inventory", address] => Ok(InspectQuery::Inventory(address.to_string())),
+            ["outputs", input_index] => input_index
+                .parse()
+                .map(InspectQuery::Outputs)
+                .map_err(|_| ServerError::InvalidInspectQuery(s.to_owned())),
+            ["ledger", address] => {
+                let mut offset = 0;
+                let mut limit = LEDGER_PAGE_SIZE;
+                for param in query.split('&').filter(|param| !param.is_empty()) {
+                    let invalid = || ServerError::InvalidInspectQuery(s.to_owned());
+                    match param.split_once('=').ok_or_else(invalid)? {
+                        ("offset", value) => offset = value.parse().map_err(|_| invalid())?,
+                        ("limit", value) => limit = value.parse().map_err(|_| invalid())?,
+                        _ => return Err(invalid()),
+                    }
+                }
+                Ok(InspectQuery::Ledger {
+                    address: address.to_string(),
+                    offset,
+                    limit: limit.min(LEDGER_PAGE_SIZE),
+                })
+            }
+            ["rooms"] => {
+                let mut mode = None;
+                let mut status = None;
+                for param in query.split('&').filter(|param| !param.is_empty()) {
+                    let invalid = || ServerError::InvalidInspectQuery(s.to_owned());
+                    match param.split_once('=').ok_or_else(invalid)? {
+                        ("mode", value) => {
+                            mode = Some(GameMode::from_string(value).ok_or_else(invalid)?)
+                        }
+                        ("status", value) => status = Some(value.parse().map_err(|_| invalid())?),
+                        _ => return Err(invalid()),
+                    }
+                }
+                Ok(InspectQuery::Rooms { mode, status })
+            }
+            _ => Err(ServerError::InvalidInspectQuery(s.to_owned())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, StrumDisplay, EnumString)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum RoomPhase {
+    Waiting,
+    Playing,
+    Finished,
+}
+
+impl RoomPhase {
+    pub fn of(room: &Room) -> Self {
+        if room.is_finished() {
+            RoomPhase::Finished
+        } else if room.gamers.len() < 2 {
+            RoomPhase::Waiting
+        } else {
+            RoomPhase::Playing
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerRoomResponse {
+    pub user: String,
+    pub room_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BalanceResponse {
+    pub address: String,
+    pub balance: String,
+    pub tokens: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VouchersResponse {
+    pub address: String,
+    pub vouchers: Vec<VoucherMeta>,
+}
+
+/// A notice of an input, `index` is its position among the notices of that input
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NoticeOutput {
+    pub index: u64,
+    pub notice_type: NoticeType,
+    pub user: String,
+}
+
+/// A voucher of an input, `index` is its position among the vouchers of that input
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoucherOutput {
+    pub index: u64,
+    pub destination: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InputOutputs {
+    pub input_index: u64,
+    pub notices: Vec<NoticeOutput>,
+    pub vouchers: Vec<VoucherOutput>,
+}
+
+//#NOTE: upper bound of the ledger entries reported by a single inspect call
+pub const LEDGER_PAGE_SIZE: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LedgerEntryKind {
+    Deposit,
+    StakeLock,
+    StakePayout,
+    StakeRefund,
+    PlatformFee,
+    TransferIn,
+    TransferOut,
+    Withdraw,
+}
+
+/// One change of an address' balance, `balance` is the balance right after it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    pub input_index: u64,
+    pub block_number: u64,
+    pub kind: LedgerEntryKind,
+    //#NOTE: ERC-20 token address, None for Ether
+    pub token: Option<String>,
+    pub amount: String,
+    //#NOTE: portal of a deposit, room id of a stake, other wallet of a transfer, voucher destination of a withdrawal
+    pub counterparty: String,
+    pub balance: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LedgerResponse {
+    pub address: String,
+    //#NOTE: number of entries of the address, to page through them with `offset`
+    pub total: usize,
+    pub offset: usize,
+    pub entries: Vec<LedgerEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WalletResponse {
+    pub metamask_wallet: String,
+    pub ingame_wallet: Option<String>,
+    pub session: Option<SessionKeyState>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionKeyState {
+    pub ingame_wallet: String,
+    pub scope: SessionScope,
+    pub expires_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InventoryResponse {
+    pub owner: String,
+    pub characters: Vec<NftCharacter>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoomSummary {
+    pub uuid: Uuid,
+    pub game_mode: GameMode,
+    pub status: RoomPhase,
+    pub gamers: Vec<String>,
+    pub stake_token: Option<String>,
+    pub stake: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoomsResponse {
+    pub rooms: Vec<RoomSummary>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -138,3 +834,87 @@ pub struct RollupResponse {
     pub request_type: String,
     pub data: AdvanceRequest,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_core::board::Direction;
+
+    const DAPP_ADDRESS: &str = "0x70ac08179605AF2D9e75782b8DEcDD3c22aA4D0C";
+    const CHAIN_ID: u64 = 31337;
+    const METAMASK_WALLET: &str = "0x2af645839ea4ca82452afd195e210420e7cc1f90";
+    const INGAME_WALLET: &str = "0x70997970c51812dc3a010c7d01b50e0d17dc79c8";
+
+    #[test]
+    fn inspect_query_parses_routes() {
+        assert_eq!("".parse::<InspectQuery>().unwrap(), InspectQuery::FullState);
+        assert_eq!(
+            format!("balance/{}", METAMASK_WALLET)
+                .parse::<InspectQuery>()
+                .unwrap(),
+            InspectQuery::Balance(METAMASK_WALLET.to_owned())
+        );
+        assert_eq!(
+            format!("/player/{}/room", INGAME_WALLET)
+                .parse::<InspectQuery>()
+                .unwrap(),
+            InspectQuery::PlayerRoom(INGAME_WALLET.to_owned())
+        );
+        assert_eq!(
+            "rooms?mode=cartesi&status=waiting"
+                .parse::<InspectQuery>()
+                .unwrap(),
+            InspectQuery::Rooms {
+                mode: Some(GameMode::Cartesi),
+                status: Some(RoomPhase::Waiting),
+            }
+        );
+        assert_eq!(
+            "outputs/12".parse::<InspectQuery>().unwrap(),
+            InspectQuery::Outputs(12)
+        );
+        assert!("outputs/latest".parse::<InspectQuery>().is_err());
+        assert_eq!(
+            format!("ledger/{}?offset=10&limit=1000", METAMASK_WALLET)
+                .parse::<InspectQuery>()
+                .unwrap(),
+            InspectQuery::Ledger {
+                address: METAMASK_WALLET.to_owned(),
+                offset: 10,
+                limit: LEDGER_PAGE_SIZE,
+            }
+        );
+        assert!("room/not-a-uuid".parse::<InspectQuery>().is_err());
+        assert!("rooms?status=sleeping".parse::<InspectQuery>().is_err());
+        assert!("everything".parse::<InspectQuery>().is_err());
+    }
+
+    #[test]
+    fn relayed_action_typed_data_follows_eip712() {
+        let req = RelayedRequest {
+            action: RelayedAction::Move(MoveRequest {
+                room_id: Uuid::from_u128(1),
+                user: INGAME_WALLET.to_owned(),
+                action: MoveAction {
+                    x: 1,
+                    y: 2,
+                    direction: Direction::Right,
+                },
+                attacker_id: Uuid::from_u128(2),
+                defender_id: Uuid::from_u128(3),
+            }),
+            turn: 4,
+            nonce: 5,
+            signature: String::new(),
+        };
+        let typed_data =
+            relayed_action_typed_data(&Address::from_str(DAPP_ADDRESS).unwrap(), CHAIN_ID, &req)
+                .unwrap();
+
+        //#NOTE: digest of the same typed data computed apart from the dapp, as eth_signTypedData_v4 hashes it
+        assert_eq!(
+            hex::encode(keccak256(typed_data)),
+            "7c13aab099bea655307b31aa2334551fbadca0db9e4d6844effaeb05d7165922"
+        );
+    }
+}
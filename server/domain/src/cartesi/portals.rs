@@ -0,0 +1,425 @@
+/*
+    Decoders for the inputs sent by the Cartesi Rollups v1.0 portals, every portal prepends its own packed header:
+
+    EtherPortal          abi.encodePacked(address sender, uint256 value, bytes execLayerData)
+    ERC20Portal          abi.encodePacked(bool success, address token, address sender, uint256 amount, bytes execLayerData)
+    ERC721Portal         abi.encodePacked(address token, address sender, uint256 tokenId, abi.encode(bytes baseLayerData, bytes execLayerData))
+    ERC1155SinglePortal  abi.encodePacked(address token, address sender, uint256 tokenId, uint256 value, abi.encode(bytes baseLayerData, bytes execLayerData))
+    ERC1155BatchPortal   abi.encodePacked(address token, address sender, abi.encode(uint256[] tokenIds, uint256[] values, bytes baseLayerData, bytes execLayerData))
+*/
+use crate::game_core::ServerError;
+use ethers_core::abi::{decode, ParamType, Token};
+use ethers_core::types::{Address, U256};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EtherDeposit {
+    pub sender: Address,
+    pub value: U256,
+    pub exec_layer_data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Erc20Deposit {
+    //#NOTE: result of the token's transferFrom, nothing was deposited when false
+    pub success: bool,
+    pub token: Address,
+    pub sender: Address,
+    pub amount: U256,
+    pub exec_layer_data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Erc721Deposit {
+    pub token: Address,
+    pub sender: Address,
+    pub token_id: U256,
+    pub base_layer_data: Vec<u8>,
+    pub exec_layer_data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Erc1155SingleDeposit {
+    pub token: Address,
+    pub sender: Address,
+    pub token_id: U256,
+    pub value: U256,
+    pub base_layer_data: Vec<u8>,
+    pub exec_layer_data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Erc1155BatchDeposit {
+    pub token: Address,
+    pub sender: Address,
+    pub token_ids: Vec<U256>,
+    pub values: Vec<U256>,
+    pub base_layer_data: Vec<u8>,
+    pub exec_layer_data: Vec<u8>,
+}
+
+pub fn decode_ether_deposit(payload: &[u8]) -> Result<EtherDeposit, ServerError> {
+    let mut reader = PackedReader::new("Ether", payload);
+    Ok(EtherDeposit {
+        sender: reader.address("sender")?,
+        value: reader.uint("value")?,
+        exec_layer_data: reader.rest().to_vec(),
+    })
+}
+
+pub fn decode_erc20_deposit(payload: &[u8]) -> Result<Erc20Deposit, ServerError> {
+    let mut reader = PackedReader::new("ERC-20", payload);
+    let success = match reader.take(1, "success")?[0] {
+        0 => false,
+        1 => true,
+        flag => return Err(reader.invalid(format!("success flag must be 0 or 1, got {}", flag))),
+    };
+
+    Ok(Erc20Deposit {
+        success,
+        token: reader.address("token")?,
+        sender: reader.address("sender")?,
+        amount: reader.uint("amount")?,
+        exec_layer_data: reader.rest().to_vec(),
+    })
+}
+
+pub fn decode_erc721_deposit(payload: &[u8]) -> Result<Erc721Deposit, ServerError> {
+    let mut reader = PackedReader::new("ERC-721", payload);
+    let token = reader.address("token")?;
+    let sender = reader.address("sender")?;
+    let token_id = reader.uint("token id")?;
+    let mut data = reader.abi_tail(&[ParamType::Bytes, ParamType::Bytes])?;
+
+    Ok(Erc721Deposit {
+        token,
+        sender,
+        token_id,
+        base_layer_data: take_bytes(&reader, &mut data, "base layer data")?,
+        exec_layer_data: take_bytes(&reader, &mut data, "exec layer data")?,
+    })
+}
+
+pub fn decode_erc1155_single_deposit(payload: &[u8]) -> Result<Erc1155SingleDeposit, ServerError> {
+    let mut reader = PackedReader::new("ERC-1155 single", payload);
+    let token = reader.address("token")?;
+    let sender = reader.address("sender")?;
+    let token_id = reader.uint("token id")?;
+    let value = reader.uint("value")?;
+    let mut data = reader.abi_tail(&[ParamType::Bytes, ParamType::Bytes])?;
+
+    Ok(Erc1155SingleDeposit {
+        token,
+        sender,
+        token_id,
+        value,
+        base_layer_data: take_bytes(&reader, &mut data, "base layer data")?,
+        exec_layer_data: take_bytes(&reader, &mut data, "exec layer data")?,
+    })
+}
+
+pub fn decode_erc1155_batch_deposit(payload: &[u8]) -> Result<Erc1155BatchDeposit, ServerError> {
+    let mut reader = PackedReader::new("ERC-1155 batch", payload);
+    let token = reader.address("token")?;
+    let sender = reader.address("sender")?;
+    let uint_array = ParamType::Array(Box::new(ParamType::Uint(256)));
+    let mut data = reader.abi_tail(&[
+        uint_array.clone(),
+        uint_array,
+        ParamType::Bytes,
+        ParamType::Bytes,
+    ])?;
+
+    let token_ids = take_uints(&reader, &mut data, "token ids")?;
+    let values = take_uints(&reader, &mut data, "values")?;
+    if token_ids.len() != values.len() {
+        return Err(reader.invalid(format!(
+            "{} token ids but {} values",
+            token_ids.len(),
+            values.len()
+        )));
+    }
+
+    Ok(Erc1155BatchDeposit {
+        token,
+        sender,
+        token_ids,
+        values,
+        base_layer_data: take_bytes(&reader, &mut data, "base layer data")?,
+        exec_layer_data: take_bytes(&reader, &mut data, "exec layer data")?,
+    })
+}
+
+/// Reads the packed header of a portal input field by field
+struct PackedReader<'a> {
+    portal: &'static str,
+    payload: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> PackedReader<'a> {
+    fn new(portal: &'static str, payload: &'a [u8]) -> Self {
+        PackedReader {
+            portal,
+            payload,
+            offset: 0,
+        }
+    }
+
+    fn invalid(&self, reason: String) -> ServerError {
+        ServerError::InvalidPortalPayload(self.portal.to_owned(), reason)
+    }
+
+    fn take(&mut self, len: usize, field: &str) -> Result<&'a [u8], ServerError> {
+        let end = self.offset + len;
+        if self.payload.len() < end {
+            return Err(self.invalid(format!(
+                "{} bytes is too short to hold the {} at bytes {}..{}",
+                self.payload.len(),
+                field,
+                self.offset,
+                end
+            )));
+        }
+
+        let bz = &self.payload[self.offset..end];
+        self.offset = end;
+        Ok(bz)
+    }
+
+    fn address(&mut self, field: &str) -> Result<Address, ServerError> {
+        self.take(20, field).map(Address::from_slice)
+    }
+
+    fn uint(&mut self, field: &str) -> Result<U256, ServerError> {
+        self.take(32, field).map(U256::from_big_endian)
+    }
+
+    fn rest(&self) -> &'a [u8] {
+        &self.payload[self.offset..]
+    }
+
+    /// Decode the abi-encoded part after the packed header, returned in reverse order to be popped
+    fn abi_tail(&self, params: &[ParamType]) -> Result<Vec<Token>, ServerError> {
+        if self.rest().is_empty() {
+            return Err(self.invalid(String::from("missing abi-encoded layer data")));
+        }
+
+        let mut tokens = decode(params, self.rest())
+            .map_err(|e| self.invalid(format!("malformed abi-encoded layer data: {}", e)))?;
+        tokens.reverse();
+        Ok(tokens)
+    }
+}
+
+fn take_bytes(
+    reader: &PackedReader,
+    tokens: &mut Vec<Token>,
+    field: &str,
+) -> Result<Vec<u8>, ServerError> {
+    tokens
+        .pop()
+        .and_then(Token::into_bytes)
+        .ok_or_else(|| reader.invalid(format!("{} is not bytes", field)))
+}
+
+fn take_uints(
+    reader: &PackedReader,
+    tokens: &mut Vec<Token>,
+    field: &str,
+) -> Result<Vec<U256>, ServerError> {
+    tokens
+        .pop()
+        .and_then(Token::into_array)
+        .and_then(|array| array.into_iter().map(Token::into_uint).collect())
+        .ok_or_else(|| reader.invalid(format!("{} is not a uint256 array", field)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers_core::utils::hex;
+    use std::str::FromStr;
+
+    //#NOTE: fixtures are the inputs the v1.0 portals emit for the hardhat test accounts, with execLayerData "dazzle"
+    const SENDER: &str = "0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266";
+    const EXEC_LAYER_DATA: &[u8] = b"dazzle";
+
+    const ETHER_FIXTURE: &str = concat!(
+        "f39fd6e51aad88f6f4ce6ab8827279cfffb92266",
+        "0000000000000000000000000000000000000000000000000de0b6b3a7640000",
+        "64617a7a6c65",
+    );
+
+    const ERC20_FIXTURE: &str = concat!(
+        "01",
+        "5fbdb2315678afecb367f032d93f642f64180aa3",
+        "f39fd6e51aad88f6f4ce6ab8827279cfffb92266",
+        "0000000000000000000000000000000000000000000000056bc75e2d63100000",
+        "64617a7a6c65",
+    );
+
+    const ERC721_FIXTURE: &str = concat!(
+        "e7f1725e7734ce288f8367e1bb143e90bb3f0512",
+        "f39fd6e51aad88f6f4ce6ab8827279cfffb92266",
+        "0000000000000000000000000000000000000000000000000000000000000001",
+        "0000000000000000000000000000000000000000000000000000000000000040",
+        "0000000000000000000000000000000000000000000000000000000000000060",
+        "0000000000000000000000000000000000000000000000000000000000000000",
+        "0000000000000000000000000000000000000000000000000000000000000006",
+        "64617a7a6c650000000000000000000000000000000000000000000000000000",
+    );
+
+    const ERC1155_SINGLE_FIXTURE: &str = concat!(
+        "9fe46736679d2d9a65f0992f2272de9f3c7fa6e0",
+        "f39fd6e51aad88f6f4ce6ab8827279cfffb92266",
+        "0000000000000000000000000000000000000000000000000000000000000001",
+        "000000000000000000000000000000000000000000000000000000000000000a",
+        "0000000000000000000000000000000000000000000000000000000000000040",
+        "0000000000000000000000000000000000000000000000000000000000000060",
+        "0000000000000000000000000000000000000000000000000000000000000000",
+        "0000000000000000000000000000000000000000000000000000000000000006",
+        "64617a7a6c650000000000000000000000000000000000000000000000000000",
+    );
+
+    const ERC1155_BATCH_FIXTURE: &str = concat!(
+        "9fe46736679d2d9a65f0992f2272de9f3c7fa6e0",
+        "f39fd6e51aad88f6f4ce6ab8827279cfffb92266",
+        "0000000000000000000000000000000000000000000000000000000000000080",
+        "00000000000000000000000000000000000000000000000000000000000000e0",
+        "0000000000000000000000000000000000000000000000000000000000000140",
+        "0000000000000000000000000000000000000000000000000000000000000160",
+        "0000000000000000000000000000000000000000000000000000000000000002",
+        "0000000000000000000000000000000000000000000000000000000000000001",
+        "0000000000000000000000000000000000000000000000000000000000000002",
+        "0000000000000000000000000000000000000000000000000000000000000002",
+        "000000000000000000000000000000000000000000000000000000000000000a",
+        "0000000000000000000000000000000000000000000000000000000000000014",
+        "0000000000000000000000000000000000000000000000000000000000000000",
+        "0000000000000000000000000000000000000000000000000000000000000006",
+        "64617a7a6c650000000000000000000000000000000000000000000000000000",
+    );
+
+    fn fixture(hex_payload: &str) -> Vec<u8> {
+        hex::decode(hex_payload).unwrap()
+    }
+
+    fn address(address: &str) -> Address {
+        Address::from_str(address).unwrap()
+    }
+
+    fn assert_invalid(result: Result<impl std::fmt::Debug, ServerError>, reason: &str) {
+        match result {
+            Err(ServerError::InvalidPortalPayload(_, message)) => {
+                assert!(message.contains(reason), "unexpected reason: {}", message)
+            }
+            other => panic!("expected InvalidPortalPayload, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn decodes_ether_deposit() {
+        let deposit = decode_ether_deposit(&fixture(ETHER_FIXTURE)).unwrap();
+
+        assert_eq!(deposit.sender, address(SENDER));
+        assert_eq!(deposit.value, U256::exp10(18));
+        assert_eq!(deposit.exec_layer_data, EXEC_LAYER_DATA);
+    }
+
+    #[test]
+    fn decodes_erc20_deposit() {
+        let deposit = decode_erc20_deposit(&fixture(ERC20_FIXTURE)).unwrap();
+
+        assert!(deposit.success);
+        assert_eq!(
+            deposit.token,
+            address("0x5fbdb2315678afecb367f032d93f642f64180aa3")
+        );
+        assert_eq!(deposit.sender, address(SENDER));
+        assert_eq!(deposit.amount, U256::from(100) * U256::exp10(18));
+        assert_eq!(deposit.exec_layer_data, EXEC_LAYER_DATA);
+    }
+
+    #[test]
+    fn decodes_erc721_deposit() {
+        let deposit = decode_erc721_deposit(&fixture(ERC721_FIXTURE)).unwrap();
+
+        assert_eq!(
+            deposit.token,
+            address("0xe7f1725e7734ce288f8367e1bb143e90bb3f0512")
+        );
+        assert_eq!(deposit.sender, address(SENDER));
+        assert_eq!(deposit.token_id, U256::one());
+        assert!(deposit.base_layer_data.is_empty());
+        assert_eq!(deposit.exec_layer_data, EXEC_LAYER_DATA);
+    }
+
+    #[test]
+    fn decodes_erc1155_single_deposit() {
+        let deposit = decode_erc1155_single_deposit(&fixture(ERC1155_SINGLE_FIXTURE)).unwrap();
+
+        assert_eq!(
+            deposit.token,
+            address("0x9fe46736679d2d9a65f0992f2272de9f3c7fa6e0")
+        );
+        assert_eq!(deposit.sender, address(SENDER));
+        assert_eq!(deposit.token_id, U256::one());
+        assert_eq!(deposit.value, U256::from(10));
+        assert!(deposit.base_layer_data.is_empty());
+        assert_eq!(deposit.exec_layer_data, EXEC_LAYER_DATA);
+    }
+
+    #[test]
+    fn decodes_erc1155_batch_deposit() {
+        let deposit = decode_erc1155_batch_deposit(&fixture(ERC1155_BATCH_FIXTURE)).unwrap();
+
+        assert_eq!(deposit.sender, address(SENDER));
+        assert_eq!(deposit.token_ids, vec![U256::from(1), U256::from(2)]);
+        assert_eq!(deposit.values, vec![U256::from(10), U256::from(20)]);
+        assert!(deposit.base_layer_data.is_empty());
+        assert_eq!(deposit.exec_layer_data, EXEC_LAYER_DATA);
+    }
+
+    #[test]
+    fn exec_layer_data_is_optional_for_ether_and_erc20() {
+        let ether = fixture(ETHER_FIXTURE);
+        let deposit = decode_ether_deposit(&ether[..52]).unwrap();
+        assert!(deposit.exec_layer_data.is_empty());
+
+        let erc20 = fixture(ERC20_FIXTURE);
+        let deposit = decode_erc20_deposit(&erc20[..73]).unwrap();
+        assert!(deposit.exec_layer_data.is_empty());
+    }
+
+    #[test]
+    fn truncated_header_is_refused() {
+        let ether = fixture(ETHER_FIXTURE);
+        assert_invalid(decode_ether_deposit(&ether[..40]), "value");
+
+        let erc20 = fixture(ERC20_FIXTURE);
+        assert_invalid(decode_erc20_deposit(&erc20[..30]), "sender");
+        assert_invalid(decode_erc20_deposit(&[]), "success");
+
+        let erc721 = fixture(ERC721_FIXTURE);
+        assert_invalid(decode_erc721_deposit(&erc721[..60]), "token id");
+    }
+
+    #[test]
+    fn invalid_erc20_success_flag_is_refused() {
+        let mut erc20 = fixture(ERC20_FIXTURE);
+        erc20[0] = 2;
+        assert_invalid(decode_erc20_deposit(&erc20), "success flag");
+    }
+
+    #[test]
+    fn malformed_layer_data_is_refused() {
+        let erc721 = fixture(ERC721_FIXTURE);
+        assert_invalid(decode_erc721_deposit(&erc721[..72]), "missing");
+        assert_invalid(
+            decode_erc721_deposit(&erc721[..erc721.len() - 40]),
+            "malformed",
+        );
+
+        let batch = fixture(ERC1155_BATCH_FIXTURE);
+        assert_invalid(decode_erc1155_batch_deposit(&batch[..200]), "malformed");
+    }
+}
@@ -1,7 +1,7 @@
 use atb::prelude::*;
 use atb_types::Uuid;
-use rand::rngs::StdRng;
 use rand::Rng;
+use rand_chacha::ChaCha12Rng;
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 use strum::IntoEnumIterator;
@@ -15,6 +15,9 @@ use crate::game_core::GameError;
 
 const MASK_OFFSET: u32 = 1;
 
+//#NOTE: the generator behind StdRng, same streams, but its state can be serialized into state snapshots
+pub type GameRng = ChaCha12Rng;
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub enum BoardState {
     ClearState {
@@ -122,7 +125,7 @@ pub struct BoardData {
 }
 
 impl Board {
-    pub fn new(rng: &mut StdRng, num_colors: u32, width: u32, height: u32) -> Self {
+    pub fn new(rng: &mut GameRng, num_colors: u32, width: u32, height: u32) -> Self {
         let board_field_mask = !(u32::MAX << (width + MASK_OFFSET) | 1);
         let wall_mask = 1 << (width + MASK_OFFSET) | 1;
         let row = vec![0; height as usize * 2];
@@ -161,7 +164,7 @@ impl Board {
     pub fn simulate(
         &mut self,
         _move: &MoveAction,
-        rng: &mut StdRng,
+        rng: &mut GameRng,
     ) -> Result<Vec<BoardState>, GameError> {
         //update board move
         let mut next_board = self.clone();
@@ -239,7 +242,7 @@ impl Board {
     pub fn element_explosion(
         &mut self,
         target_element: Element,
-        rng: &mut StdRng,
+        rng: &mut GameRng,
     ) -> Result<Vec<BoardState>, GameError> {
         let target_bead = Bead::from(target_element);
         let target_color_board = self.board_data.board[target_bead as usize].clone();
@@ -285,7 +288,7 @@ impl Board {
         &mut self,
         clear_pattern: ClearPattern,
         line_num: u32,
-        rng: &mut StdRng,
+        rng: &mut GameRng,
     ) -> Result<Vec<BoardState>, GameError> {
         let board_clear_mask = Self::compose_line_mask(clear_pattern, line_num);
 
@@ -382,7 +385,7 @@ impl Board {
         }
     }
 
-    fn refresh_reserved_block(&mut self, starting_row: u32, rng: &mut StdRng) {
+    fn refresh_reserved_block(&mut self, starting_row: u32, rng: &mut GameRng) {
         let rows = self.board_data.board[0].len();
 
         // At (i,j) position, fill in a random color.
@@ -702,7 +705,7 @@ impl Board {
         }
     }
 
-    fn process_falling_and_filling_result(&mut self, rng: &mut StdRng) -> Vec<BoardState> {
+    fn process_falling_and_filling_result(&mut self, rng: &mut GameRng) -> Vec<BoardState> {
         let mut states = vec![];
         loop {
             match self.eval_clear_result(true) {
@@ -751,13 +754,13 @@ impl Board {
 
 #[cfg(test)]
 mod test {
-    use super::{Board, BoardState, Direction, MoveAction};
-    use rand::{rngs::StdRng, SeedableRng};
+    use super::{Board, BoardState, Direction, GameRng, MoveAction};
+    use rand::SeedableRng;
     use serde_json::Value;
 
     #[test]
     fn unit_test() {
-        let mut rng = StdRng::seed_from_u64(777777u64);
+        let mut rng = GameRng::seed_from_u64(777777u64);
         let mut init_board = Board::new(&mut rng, 5, 6, 6);
         let f_m = MoveAction {
             x: 0,
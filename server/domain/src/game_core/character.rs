@@ -1,7 +1,8 @@
 use atb::prelude::*;
 use atb_types::Uuid;
 use rand::Rng;
-use serde::{Deserialize, Serialize};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
 use std::cmp;
 use std::collections::HashMap;
 use std::str::FromStr;
@@ -9,6 +10,7 @@ use strum::IntoEnumIterator;
 use strum_macros::EnumString;
 
 use super::skill::PassiveName;
+use crate::game_core::board::GameRng;
 use crate::game_core::character_mod::accessory_module::{AccPart, AccessoryModule};
 use crate::game_core::character_mod::attribute::{Attribute, SpecialTile};
 use crate::game_core::character_mod::base_body_module::BaseBodyModule;
@@ -16,6 +18,8 @@ use crate::game_core::config::{
     ClearPattern, Element, GameplayConfigManager, BOSS_ENEMY_STRING, ELITE_ENEMY_STRING,
     NORMAL_ENEMY_STRING, RATE_UNIT,
 };
+use crate::game_core::full_state::FullState;
+use crate::game_core::probability_mod::{RandomNumHolder, LOCK_POISONED, RANDOM_NUM_HOLDER};
 use crate::game_core::skill::{ActivatingBuff, BuffInfo, CharacterSkill, SkillInfo};
 use crate::game_core::GameError;
 
@@ -23,24 +27,59 @@ use crate::game_core::GameError;
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct CharacterLogicData {
     pub id: Uuid,
-    #[serde(skip)]
+    #[serde(default, skip_serializing)]
     pub max_hp: u32,
     pub current_hp: u32,
-    #[serde(skip)]
+    #[serde(default, skip_serializing)]
     pub atk: u32,
-    #[serde(skip)]
+    #[serde(default, skip_serializing)]
     pub def: u32,
     pub element: Element,
-    #[serde(skip)]
+    #[serde(default, skip_serializing)]
     pub special_tile: SpecialTile,
     pub skill: CharacterSkill,
-    #[serde(skip)]
+    #[serde(default, skip_serializing)]
     pub passive: PassiveName,
     pub buff_states: Vec<ActivatingBuff>,
-    #[serde(skip)]
+    #[serde(default, skip_serializing)]
     pub assist_nerf_modifier: u32,
 }
 
+impl Serialize for FullState<'_, CharacterLogicData> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let CharacterLogicData {
+            id,
+            max_hp,
+            current_hp,
+            atk,
+            def,
+            element,
+            special_tile,
+            skill,
+            passive,
+            buff_states,
+            assist_nerf_modifier,
+        } = self.0;
+
+        let mut character = serializer.serialize_struct("CharacterLogicData", 11)?;
+        character.serialize_field("id", id)?;
+        character.serialize_field("max_hp", max_hp)?;
+        character.serialize_field("current_hp", current_hp)?;
+        character.serialize_field("atk", atk)?;
+        character.serialize_field("def", def)?;
+        character.serialize_field("element", element)?;
+        character.serialize_field("special_tile", special_tile)?;
+        character.serialize_field("skill", skill)?;
+        character.serialize_field("passive", passive)?;
+        character.serialize_field("buff_states", buff_states)?;
+        character.serialize_field("assist_nerf_modifier", assist_nerf_modifier)?;
+        character.end()
+    }
+}
+
 impl CharacterLogicData {
     pub fn update_hp(&mut self, hp: i32) {
         self.update_current_hp(cmp::min(self.max_hp, cmp::max(0, hp) as u32));
@@ -334,10 +373,20 @@ pub struct CharacterV2 {
 
 impl CharacterV2 {
     pub fn roll_new(tier_lv: usize, config: &GameplayConfigManager) -> Self {
-        let attribute = Attribute::roll_attribute(tier_lv, config);
+        let mut rand_holder = RANDOM_NUM_HOLDER.write().expect(LOCK_POISONED);
+        Self::roll_with(tier_lv, config, &mut rand_holder)
+    }
+
+    /// Roll a character from the given holder, so the same seed rolls the same character
+    pub fn roll_with(
+        tier_lv: usize,
+        config: &GameplayConfigManager,
+        rand_holder: &mut RandomNumHolder,
+    ) -> Self {
+        let attribute = Attribute::roll_attribute(tier_lv, config, rand_holder);
         let new_char = Self {
-            accessory_module: AccessoryModule::roll_accessory(&attribute, config),
-            body_module: BaseBodyModule::roll_base_body_module(),
+            accessory_module: AccessoryModule::roll_accessory(&attribute, config, rand_holder),
+            body_module: BaseBodyModule::roll_base_body_module(rand_holder),
             rarity: Attribute::get_char_rarity(&attribute, config),
             attribute,
         };
@@ -351,15 +400,17 @@ impl CharacterV2 {
         element: Element,
         config: &GameplayConfigManager,
     ) -> Self {
-        let mut attribute = Attribute::roll_attribute(1, config);
+        let mut rand_holder = RANDOM_NUM_HOLDER.write().expect(LOCK_POISONED);
+        let mut attribute = Attribute::roll_attribute(1, config, &mut rand_holder);
 
         // The accessory is highly coupled with the accessory module, need to clone attribute to initialize the accessory first
-        let accessory_module = AccessoryModule::roll_accessory(&attribute.clone(), config);
+        let accessory_module =
+            AccessoryModule::roll_accessory(&attribute.clone(), config, &mut rand_holder);
 
         attribute.set_element(element);
 
         if is_player {
-            attribute.set_skill_meta(SkillInfo::Damage);
+            attribute.set_skill_meta(SkillInfo::Damage, &mut rand_holder);
         } else {
             attribute.set_max_hp(10);
         }
@@ -367,7 +418,7 @@ impl CharacterV2 {
         let new_char = Self {
             // Note: The modules can be manually assigned in the future.
             accessory_module,
-            body_module: BaseBodyModule::roll_base_body_module(),
+            body_module: BaseBodyModule::roll_base_body_module(&mut rand_holder),
             rarity: 1,
             attribute,
         };
@@ -451,6 +502,7 @@ impl CharacterV2 {
             req_skill_param_clear_pattern,
         );
 
+        let mut rand_holder = RANDOM_NUM_HOLDER.write().expect(LOCK_POISONED);
         let attribute = Attribute::_debug_specify_roll_attribute(
             tier_lv,
             &assigned_element,
@@ -458,11 +510,12 @@ impl CharacterV2 {
             &assigned_skill_param_elem,
             &assigned_skill_param_clear_pattern,
             config,
+            &mut rand_holder,
         );
 
         let new_char = Self {
-            accessory_module: AccessoryModule::roll_accessory(&attribute, config),
-            body_module: BaseBodyModule::roll_base_body_module(), // Base body is already rolled evenly in currennt SPEC
+            accessory_module: AccessoryModule::roll_accessory(&attribute, config, &mut rand_holder),
+            body_module: BaseBodyModule::roll_base_body_module(&mut rand_holder), // Base body is already rolled evenly in currennt SPEC
             rarity: Attribute::get_char_rarity(&attribute, config),
             attribute,
         };
@@ -652,7 +705,12 @@ pub struct EnemyScriptMap {
 }
 
 impl EnemyScriptMap {
-    pub fn get_command(&self, script_name: &str, turn: usize) -> Result<Command, GameError> {
+    pub fn get_command(
+        &self,
+        script_name: &str,
+        turn: usize,
+        rng: &mut GameRng,
+    ) -> Result<Command, GameError> {
         let script = self
             .script_map
             .get(script_name)
@@ -665,7 +723,7 @@ impl EnemyScriptMap {
             CommandType::Random => {
                 let skill_info = SkillInfo::from(
                     // The random method will be replace in the future. Not optimized for now.
-                    rand::thread_rng().gen_range(SkillInfo::random_enemy_command_range()),
+                    rng.gen_range(SkillInfo::random_enemy_command_range()),
                 );
                 let ran_command = if skill_info == SkillInfo::NpcAttack {
                     Command {
@@ -59,23 +59,40 @@ pub struct AccessoryModule {
 }
 
 impl AccessoryModule {
-    pub fn roll_accessory(attribute: &Attribute, config: &GameplayConfigManager) -> Self {
+    pub fn roll_accessory(
+        attribute: &Attribute,
+        config: &GameplayConfigManager,
+        rand_holder: &mut RandomNumHolder,
+    ) -> Self {
         let mut accessory_list = vec![];
 
-        accessory_list.extend(Self::roll_hp_accessory(attribute.get_max_hp(), config));
-        accessory_list.extend(Self::roll_def_accessory(attribute.get_def(), config));
-        accessory_list.extend(Self::roll_atk_accessory(attribute.get_atk(), config));
+        accessory_list.extend(Self::roll_hp_accessory(
+            attribute.get_max_hp(),
+            config,
+            rand_holder,
+        ));
+        accessory_list.extend(Self::roll_def_accessory(
+            attribute.get_def(),
+            config,
+            rand_holder,
+        ));
+        accessory_list.extend(Self::roll_atk_accessory(
+            attribute.get_atk(),
+            config,
+            rand_holder,
+        ));
         accessory_list.extend(Self::roll_mono_spc_accessory(
             attribute.get_special_tile(),
             config,
+            rand_holder,
         ));
         accessory_list.extend(Self::roll_dual_spc_accessory(
             attribute.get_special_tile(),
             config,
+            rand_holder,
         ));
 
         /*
-        let rand_holder = RANDOM_NUM_HOLDER.read().expect(LOCK_POISONED);
         log::debug!(
             "   ### Accessories used_bit:{}, rand_consumed: {}",
             rand_holder.bit_consumed,
@@ -87,7 +104,11 @@ impl AccessoryModule {
     }
 
     /// Decide (Head, Face, Neck)
-    fn roll_hp_accessory(hp: u32, config: &GameplayConfigManager) -> Vec<u32> {
+    fn roll_hp_accessory(
+        hp: u32,
+        config: &GameplayConfigManager,
+        rand_holder: &mut RandomNumHolder,
+    ) -> Vec<u32> {
         let attr_config = config.get_char_attr_config();
         let rarity_lv_cap = Self::get_rarity_lv_cap(
             hp,
@@ -102,15 +123,20 @@ impl AccessoryModule {
             AccPart::Neck as usize,
         ];
 
-        let pick_num = roll_possess_amount(ProbGroup::HP_head_face_neck);
-        let result_acc_list = Self::pick_accessories(pick_num, rarity_lv_cap, &mut remain_pool);
+        let pick_num = roll_possess_amount(ProbGroup::HP_head_face_neck, rand_holder);
+        let result_acc_list =
+            Self::pick_accessories(pick_num, rarity_lv_cap, &mut remain_pool, rand_holder);
 
         // [Head, Face, Neck]
         result_acc_list
     }
 
     /// Decide (Body, Waist, Arm, Foot)
-    fn roll_def_accessory(def: u32, config: &GameplayConfigManager) -> Vec<u32> {
+    fn roll_def_accessory(
+        def: u32,
+        config: &GameplayConfigManager,
+        rand_holder: &mut RandomNumHolder,
+    ) -> Vec<u32> {
         let attr_config = config.get_char_attr_config();
         let rarity_lv_cap = Self::get_rarity_lv_cap(
             def,
@@ -122,21 +148,28 @@ impl AccessoryModule {
         // Init premium part pool
         let mut remain_pool = vec![AccPart::Body as usize, AccPart::Waist as usize];
 
-        let pick_num = roll_possess_amount(ProbGroup::DEF_body_waist);
-        let mut result_acc_list = Self::pick_accessories(pick_num, rarity_lv_cap, &mut remain_pool);
+        let pick_num = roll_possess_amount(ProbGroup::DEF_body_waist, rand_holder);
+        let mut result_acc_list =
+            Self::pick_accessories(pick_num, rarity_lv_cap, &mut remain_pool, rand_holder);
 
         // Roll Arm accessory
-        result_acc_list.push(if roll_possess(ProbGroup::DEF_arm) {
-            let lv = Self::roll_lv(1, rarity_lv_cap);
-            Self::compose_to_byte_array(lv, Self::roll_item_index(AccPartFileName::arm, lv))
+        result_acc_list.push(if roll_possess(ProbGroup::DEF_arm, rand_holder) {
+            let lv = Self::roll_lv(1, rarity_lv_cap, rand_holder);
+            Self::compose_to_byte_array(
+                lv,
+                Self::roll_item_index(AccPartFileName::arm, lv, rand_holder),
+            )
         } else {
             0
         });
 
         // Roll Foot Accessory
-        result_acc_list.push(if roll_possess(ProbGroup::DEF_foot) {
-            let lv = Self::roll_lv(1, rarity_lv_cap);
-            Self::compose_to_byte_array(lv, Self::roll_item_index(AccPartFileName::foot, lv))
+        result_acc_list.push(if roll_possess(ProbGroup::DEF_foot, rand_holder) {
+            let lv = Self::roll_lv(1, rarity_lv_cap, rand_holder);
+            Self::compose_to_byte_array(
+                lv,
+                Self::roll_item_index(AccPartFileName::foot, lv, rand_holder),
+            )
         } else {
             0
         });
@@ -146,7 +179,11 @@ impl AccessoryModule {
     }
 
     /// Decide (Eyes, Weapon, Sidearms)
-    fn roll_atk_accessory(atk: u32, config: &GameplayConfigManager) -> Vec<u32> {
+    fn roll_atk_accessory(
+        atk: u32,
+        config: &GameplayConfigManager,
+        rand_holder: &mut RandomNumHolder,
+    ) -> Vec<u32> {
         let attr_config = config.get_char_attr_config();
         let rarity_lv_cap = Self::get_rarity_lv_cap(
             atk,
@@ -162,34 +199,37 @@ impl AccessoryModule {
             Self::compose_to_byte_array(PrimitiveEyes::Origin as usize, 1)
         } else {
             // Roll high lv eyes
-            let lv = Self::roll_eyes(rarity_lv_cap);
+            let lv = Self::roll_eyes(rarity_lv_cap, rand_holder);
             Self::compose_to_byte_array(
                 lv, // If rolled eyes lv large than 10, ignore item index field (using primitive race eyes)
-                Self::roll_item_index(AccPartFileName::eye, lv),
+                Self::roll_item_index(AccPartFileName::eye, lv, rand_holder),
             )
         });
 
         // Roll weapon
-        result_acc_list.push(if roll_possess(ProbGroup::ATK_weapon) {
+        result_acc_list.push(if roll_possess(ProbGroup::ATK_weapon, rand_holder) {
             // Top rarity weapon has only 33% chance to acquire
-            let weapon_lv = if roll_possess(ProbGroup::ATK_weapon_in_top_rarity) {
+            let weapon_lv = if roll_possess(ProbGroup::ATK_weapon_in_top_rarity, rand_holder) {
                 rarity_lv_cap
             } else {
-                Self::roll_lv(1, rarity_lv_cap - 1)
+                Self::roll_lv(1, rarity_lv_cap - 1, rand_holder)
             };
 
             Self::compose_to_byte_array(
                 weapon_lv,
-                Self::roll_item_index(AccPartFileName::weapon, weapon_lv),
+                Self::roll_item_index(AccPartFileName::weapon, weapon_lv, rand_holder),
             )
         } else {
             0
         });
 
         // Roll sidearms
-        result_acc_list.push(if roll_possess(ProbGroup::ATK_sidearms) {
-            let lv = Self::roll_lv(1, rarity_lv_cap);
-            Self::compose_to_byte_array(lv, Self::roll_item_index(AccPartFileName::sidearms, lv))
+        result_acc_list.push(if roll_possess(ProbGroup::ATK_sidearms, rand_holder) {
+            let lv = Self::roll_lv(1, rarity_lv_cap, rand_holder);
+            Self::compose_to_byte_array(
+                lv,
+                Self::roll_item_index(AccPartFileName::sidearms, lv, rand_holder),
+            )
         } else {
             0
         });
@@ -202,6 +242,7 @@ impl AccessoryModule {
     fn roll_mono_spc_accessory(
         special_tile: &SpecialTile,
         config: &GameplayConfigManager,
+        rand_holder: &mut RandomNumHolder,
     ) -> Vec<u32> {
         let mut result_acc_list = vec![0, 0, 0];
         let element = special_tile.element1;
@@ -220,18 +261,18 @@ impl AccessoryModule {
         );
 
         // Roll floating item
-        let floatinf_item_lv = if roll_possess(ProbGroup::MONO_SPC_FI) {
+        let floatinf_item_lv = if roll_possess(ProbGroup::MONO_SPC_FI, rand_holder) {
             // Top rarity item has only 66% chance to accquare
             rarity_lv_cap
         } else {
             // Not top rarity items will rolled evenly
-            Self::roll_lv(1, rarity_lv_cap - 1)
+            Self::roll_lv(1, rarity_lv_cap - 1, rand_holder)
         };
         let enum_offset = AccPart::FloatingItem1 as usize;
         result_acc_list[AccPart::FloatingItem1 as usize - enum_offset] =
             Self::compose_to_byte_array(
                 floatinf_item_lv,
-                Self::roll_item_index(AccPartFileName::floatingItem, floatinf_item_lv),
+                Self::roll_item_index(AccPartFileName::floatingItem, floatinf_item_lv, rand_holder),
             );
 
         // Roll ground item & bg effect, only special tile boost val >= 120 has chance to roll
@@ -240,17 +281,17 @@ impl AccessoryModule {
             result_acc_list[AccPart::GroundItem1 as usize - enum_offset] =
                 Self::compose_to_byte_array(
                     rarity_lv_cap,
-                    Self::roll_item_index(AccPartFileName::groundItem, rarity_lv_cap),
+                    Self::roll_item_index(AccPartFileName::groundItem, rarity_lv_cap, rand_holder),
                 );
 
             // Roll bg effect
-            if roll_possess(ProbGroup::MONO_SPC_BE) {
-                let lv = Self::roll_lv(1, MAX_RARITY_LV);
+            if roll_possess(ProbGroup::MONO_SPC_BE, rand_holder) {
+                let lv = Self::roll_lv(1, MAX_RARITY_LV, rand_holder);
                 // Backgound can acquire all rairity lv from pool
                 result_acc_list[AccPart::BackgroundEffect1 as usize - enum_offset] =
                     Self::compose_to_byte_array(
                         lv,
-                        Self::roll_item_index(AccPartFileName::backgroundEffect, lv),
+                        Self::roll_item_index(AccPartFileName::backgroundEffect, lv, rand_holder),
                     )
             };
         };
@@ -263,6 +304,7 @@ impl AccessoryModule {
     fn roll_dual_spc_accessory(
         special_tile: &SpecialTile,
         config: &GameplayConfigManager,
+        rand_holder: &mut RandomNumHolder,
     ) -> Vec<u32> {
         let mut result_acc_list = vec![0; 4];
 
@@ -279,15 +321,15 @@ impl AccessoryModule {
         let rarity_lv_cap = Self::get_dual_spc_rarity_lv_cap(val_elem2_boost, config);
 
         // Roll ground effect. This is 100% guarenteed to acquired so only roll the rarity lv
-        let ground_effect_lv = if roll_possess(ProbGroup::DUAL_SPC_GE) {
+        let ground_effect_lv = if roll_possess(ProbGroup::DUAL_SPC_GE, rand_holder) {
             // Top rarity item has only 66% chance to accquare
             rarity_lv_cap
         } else {
-            Self::roll_lv(1, rarity_lv_cap - 1)
+            Self::roll_lv(1, rarity_lv_cap - 1, rand_holder)
         };
         result_acc_list[AccPart::GroundEffect as usize - enum_offset] = Self::compose_to_byte_array(
             ground_effect_lv,
-            Self::roll_item_index(AccPartFileName::groundEffect, ground_effect_lv),
+            Self::roll_item_index(AccPartFileName::groundEffect, ground_effect_lv, rand_holder),
         );
 
         // Roll floating item, mono or dual elements has different chance
@@ -296,12 +338,12 @@ impl AccessoryModule {
         } else {
             ProbGroup::DUAL_SPC_FI_DIFF
         };
-        if roll_possess(p_group_fi) {
-            let lv = Self::roll_lv(1, rarity_lv_cap);
+        if roll_possess(p_group_fi, rand_holder) {
+            let lv = Self::roll_lv(1, rarity_lv_cap, rand_holder);
             result_acc_list[AccPart::FloatingItem2 as usize - enum_offset] =
                 Self::compose_to_byte_array(
                     lv,
-                    Self::roll_item_index(AccPartFileName::floatingItem, lv),
+                    Self::roll_item_index(AccPartFileName::floatingItem, lv, rand_holder),
                 )
         };
 
@@ -311,22 +353,22 @@ impl AccessoryModule {
         } else {
             ProbGroup::DUAL_SPC_GI_DIFF
         };
-        if roll_possess(p_group_gi) {
-            let lv = Self::roll_lv(1, rarity_lv_cap);
+        if roll_possess(p_group_gi, rand_holder) {
+            let lv = Self::roll_lv(1, rarity_lv_cap, rand_holder);
             result_acc_list[AccPart::GroundItem2 as usize - enum_offset] =
                 Self::compose_to_byte_array(
                     lv,
-                    Self::roll_item_index(AccPartFileName::groundItem, lv),
+                    Self::roll_item_index(AccPartFileName::groundItem, lv, rand_holder),
                 )
         };
 
         // Roll background effect, if mono_sp has acquired a background effect, this field should be ignored
-        if roll_possess(ProbGroup::DUAL_SPC_BE) {
-            let lv = Self::roll_lv(1, rarity_lv_cap);
+        if roll_possess(ProbGroup::DUAL_SPC_BE, rand_holder) {
+            let lv = Self::roll_lv(1, rarity_lv_cap, rand_holder);
             result_acc_list[AccPart::BackgroundEffect2 as usize - enum_offset] =
                 Self::compose_to_byte_array(
                     lv,
-                    Self::roll_item_index(AccPartFileName::backgroundEffect, lv),
+                    Self::roll_item_index(AccPartFileName::backgroundEffect, lv, rand_holder),
                 )
         };
 
@@ -341,6 +383,7 @@ impl AccessoryModule {
         pick_num: usize,
         rarity_lv_cap: usize,
         remain_pool: &mut Vec<usize>,
+        rand_holder: &mut RandomNumHolder,
     ) -> Vec<u32> {
         let enum_offset = remain_pool[0];
         let pool_len = remain_pool.len();
@@ -349,9 +392,7 @@ impl AccessoryModule {
         let mut is_first_pickup = true;
 
         while remain_pool.len() > pool_len - pick {
-            let mut rand_holder = RANDOM_NUM_HOLDER.write().expect(LOCK_POISONED);
             let chosen_part = remain_pool[rand_holder.sample(..remain_pool.len() as u32) as usize];
-            drop(rand_holder);
 
             remain_pool.retain(|acc| *acc != chosen_part);
             let lv = if is_first_pickup {
@@ -359,10 +400,11 @@ impl AccessoryModule {
                 rarity_lv_cap
             } else {
                 // Remaining accessory part are evenly pickup from all avaliable range from lowest rairity lv
-                Self::roll_lv(1, rarity_lv_cap)
+                Self::roll_lv(1, rarity_lv_cap, rand_holder)
             };
 
-            let item_index = Self::roll_item_index(AccPartFileName::from(chosen_part), lv);
+            let item_index =
+                Self::roll_item_index(AccPartFileName::from(chosen_part), lv, rand_holder);
             let byte_array = Self::compose_to_byte_array(lv, item_index);
             result_acc_byte_array[chosen_part - enum_offset] = byte_array;
             is_first_pickup = false;
@@ -407,16 +449,14 @@ impl AccessoryModule {
         ((lv as u32) << 8) + item_index
     }
 
-    fn roll_lv(low: usize, high: usize) -> usize {
+    fn roll_lv(low: usize, high: usize, rand_holder: &mut RandomNumHolder) -> usize {
         if high <= low {
             return std::cmp::max(low, high);
         }
-        let mut rand_holder = RANDOM_NUM_HOLDER.write().expect(LOCK_POISONED);
         rand_holder.sample(low as u32..=high as u32) as usize
     }
 
-    fn roll_eyes(high: usize) -> usize {
-        let mut rand_holder = RANDOM_NUM_HOLDER.write().expect(LOCK_POISONED);
+    fn roll_eyes(high: usize, rand_holder: &mut RandomNumHolder) -> usize {
         // Special rarity of eyes is start from lv 3, so rarity lv 2 is using to indicate the primitive race eyes pool.
         // To ensure there is an uniform probability form primitive pool and high rarity pool.
         let mut eye_lv = rand_holder.sample(2..=high as u32);
@@ -431,56 +471,57 @@ impl AccessoryModule {
         eye_lv as usize
     }
 
-    fn roll_item_index(part_name: AccPartFileName, lv: usize) -> u32 {
+    fn roll_item_index(
+        part_name: AccPartFileName,
+        lv: usize,
+        rand_holder: &mut RandomNumHolder,
+    ) -> u32 {
         if part_name == AccPartFileName::eye && (lv < 3 || lv > 5) {
             // Eyes is special case, only 1 primitive eyes
             return 1;
         }
         let max_item_index = ART_ASSET_AMOUNT.accessory[part_name as usize][lv];
-        let mut rand_holder = RANDOM_NUM_HOLDER.write().expect(LOCK_POISONED);
         rand_holder.sample(..max_item_index) + 1
     }
 
-    pub fn _debug_evenly_roll_accessory() -> Self {
+    pub fn _debug_evenly_roll_accessory(rand_holder: &mut RandomNumHolder) -> Self {
         let mut accessory_list = vec![];
         let eye_lv_list = vec![11, 12, 13, 14, 3, 4, 5];
 
         for acc in AccPart::iter() {
-            let mut rand_holder = RANDOM_NUM_HOLDER.write().expect(LOCK_POISONED);
             let lv = rand_holder.sample(1..=MAX_RARITY_LV as u32) as usize;
             let eye_lv = eye_lv_list[rand_holder.sample(..eye_lv_list.len() as u32) as usize];
-            drop(rand_holder);
 
             match acc {
                 AccPart::GroundEffect => accessory_list.push(Self::compose_to_byte_array(
                     lv,
-                    Self::roll_item_index(AccPartFileName::groundEffect, lv),
+                    Self::roll_item_index(AccPartFileName::groundEffect, lv, rand_holder),
                 )),
                 AccPart::FloatingItem1 | AccPart::FloatingItem2 => {
                     accessory_list.push(Self::compose_to_byte_array(
                         lv,
-                        Self::roll_item_index(AccPartFileName::floatingItem, lv),
+                        Self::roll_item_index(AccPartFileName::floatingItem, lv, rand_holder),
                     ))
                 }
                 AccPart::GroundItem1 | AccPart::GroundItem2 => {
                     accessory_list.push(Self::compose_to_byte_array(
                         lv,
-                        Self::roll_item_index(AccPartFileName::groundItem, lv),
+                        Self::roll_item_index(AccPartFileName::groundItem, lv, rand_holder),
                     ))
                 }
                 AccPart::BackgroundEffect1 | AccPart::BackgroundEffect2 => {
                     accessory_list.push(Self::compose_to_byte_array(
                         lv,
-                        Self::roll_item_index(AccPartFileName::backgroundEffect, lv),
+                        Self::roll_item_index(AccPartFileName::backgroundEffect, lv, rand_holder),
                     ))
                 }
                 AccPart::Eyes => accessory_list.push(Self::compose_to_byte_array(
                     eye_lv,
-                    Self::roll_item_index(AccPartFileName::eye, eye_lv),
+                    Self::roll_item_index(AccPartFileName::eye, eye_lv, rand_holder),
                 )),
                 _ => accessory_list.push(Self::compose_to_byte_array(
                     lv,
-                    Self::roll_item_index(AccPartFileName::from(acc as usize), lv),
+                    Self::roll_item_index(AccPartFileName::from(acc as usize), lv, rand_holder),
                 )),
             }
         }
@@ -1,7 +1,6 @@
 use atb_types::prelude::uuid::Uuid;
 use serde::{Deserialize, Serialize};
 use std::cmp;
-use std::sync::RwLockWriteGuard;
 use strum::EnumCount;
 
 use crate::game_core::character_mod::char_const::*;
@@ -27,24 +26,27 @@ pub struct Attribute {
 }
 
 impl Attribute {
-    pub fn roll_attribute(tier_lv: usize, config: &GameplayConfigManager) -> Self {
-        let max_hp = Self::roll_max_hp(tier_lv, config);
+    pub fn roll_attribute(
+        tier_lv: usize,
+        config: &GameplayConfigManager,
+        rand_holder: &mut RandomNumHolder,
+    ) -> Self {
+        let max_hp = Self::roll_max_hp(tier_lv, config, rand_holder);
         Attribute {
             id: Uuid::new_v4(),
             max_hp,
             current_hp: max_hp,
-            atk: Self::roll_atk(tier_lv, config),
-            def: Self::roll_def(tier_lv, config),
-            element: Self::roll_element(),
-            special_tile: Self::roll_special_tile(tier_lv, config),
-            skill: Self::roll_skill(),
-            passive: Self::roll_passive(tier_lv),
+            atk: Self::roll_atk(tier_lv, config, rand_holder),
+            def: Self::roll_def(tier_lv, config, rand_holder),
+            element: Self::roll_element(rand_holder),
+            special_tile: Self::roll_special_tile(tier_lv, config, rand_holder),
+            skill: Self::roll_skill(rand_holder),
+            passive: Self::roll_passive(tier_lv, rand_holder),
             buff_states: vec![],
             assist_nerf_modifier: config.get_assist_modifier_rate(),
         }
 
         /*
-        let rand_holder = RANDOM_NUM_HOLDER.read().expect(LOCK_POISONED);
         log::debug!(
             "   ### Attribute used_bit:{}, rand_consumed: {}",
             rand_holder.bit_consumed,
@@ -67,11 +69,12 @@ impl Attribute {
         assigned_skill_param_elem: &Option<Element>,
         assigned_skill_param_clear_pattern: &Option<ClearPattern>,
         config: &GameplayConfigManager,
+        rand_holder: &mut RandomNumHolder,
     ) -> Self {
-        let max_hp = Self::roll_max_hp(tier_lv, config);
+        let max_hp = Self::roll_max_hp(tier_lv, config, rand_holder);
 
         let skill = assigned_skill.map_or_else(
-            || Self::roll_skill(),
+            || Self::roll_skill(rand_holder),
             |skill_name| {
                 Self::_debug_assigned_skill(
                     skill_name,
@@ -85,12 +88,12 @@ impl Attribute {
             id: Uuid::new_v4(),
             max_hp,
             current_hp: max_hp,
-            atk: Self::roll_atk(tier_lv, config),
-            def: Self::roll_def(tier_lv, config),
-            element: assigned_element.unwrap_or_else(|| Self::roll_element()),
-            special_tile: Self::roll_special_tile(tier_lv, config),
+            atk: Self::roll_atk(tier_lv, config, rand_holder),
+            def: Self::roll_def(tier_lv, config, rand_holder),
+            element: assigned_element.unwrap_or_else(|| Self::roll_element(rand_holder)),
+            special_tile: Self::roll_special_tile(tier_lv, config, rand_holder),
             skill,
-            passive: Self::roll_passive(tier_lv),
+            passive: Self::roll_passive(tier_lv, rand_holder),
             buff_states: vec![],
             assist_nerf_modifier: config.get_assist_modifier_rate(),
         }
@@ -128,6 +131,10 @@ impl Attribute {
         &self.id
     }
 
+    pub fn set_id(&mut self, param: Uuid) {
+        self.id = param;
+    }
+
     pub fn get_max_hp(&self) -> u32 {
         self.max_hp
     }
@@ -161,8 +168,7 @@ impl Attribute {
         &self.special_tile
     }
 
-    pub fn set_skill_meta(&mut self, skill_info: SkillInfo) {
-        let rand_holder = RANDOM_NUM_HOLDER.write().expect(LOCK_POISONED);
+    pub fn set_skill_meta(&mut self, skill_info: SkillInfo, rand_holder: &mut RandomNumHolder) {
         let param = Self::roll_skill_param(rand_holder, skill_info);
 
         self.skill = CharacterSkill::new(skill_info, 0, param);
@@ -199,42 +205,46 @@ impl Attribute {
         mono_spc_score + RARITY_DUAL_SPC_SCORE
     }
 
-    fn roll_max_hp(tier_lv: usize, config: &GameplayConfigManager) -> u32 {
-        let mut rand_holder = RANDOM_NUM_HOLDER.write().expect(LOCK_POISONED);
+    fn roll_max_hp(
+        tier_lv: usize,
+        config: &GameplayConfigManager,
+        rand_holder: &mut RandomNumHolder,
+    ) -> u32 {
         let hp = config.get_tier_range(TieredType::HP);
         rand_holder.sample(hp.tier_min[tier_lv]..=hp.tier_max[tier_lv])
     }
 
-    fn roll_atk(tier_lv: usize, config: &GameplayConfigManager) -> u32 {
-        let mut rand_holder = RANDOM_NUM_HOLDER.write().expect(LOCK_POISONED);
+    fn roll_atk(
+        tier_lv: usize,
+        config: &GameplayConfigManager,
+        rand_holder: &mut RandomNumHolder,
+    ) -> u32 {
         let atk = config.get_tier_range(TieredType::ATK);
         rand_holder.sample(atk.tier_min[tier_lv]..=atk.tier_max[tier_lv])
     }
 
-    fn roll_def(tier_lv: usize, config: &GameplayConfigManager) -> u32 {
-        let mut rand_holder = RANDOM_NUM_HOLDER.write().expect(LOCK_POISONED);
+    fn roll_def(
+        tier_lv: usize,
+        config: &GameplayConfigManager,
+        rand_holder: &mut RandomNumHolder,
+    ) -> u32 {
         let def = config.get_tier_range(TieredType::DEF);
         rand_holder.sample(def.tier_min[tier_lv]..=def.tier_max[tier_lv])
     }
 
-    fn roll_element() -> Element {
-        let mut rand_holder = RANDOM_NUM_HOLDER.write().expect(LOCK_POISONED);
+    fn roll_element(rand_holder: &mut RandomNumHolder) -> Element {
         // Subtract 1 COUNT is Element::Unknown
         Element::from(rand_holder.sample(..(Element::COUNT - 1) as u32))
     }
 
-    fn roll_skill() -> CharacterSkill {
-        let mut rand_holder = RANDOM_NUM_HOLDER.write().expect(LOCK_POISONED);
+    fn roll_skill(rand_holder: &mut RandomNumHolder) -> CharacterSkill {
         let info = SkillInfo::from(rand_holder.sample(SkillInfo::available_skill_range()));
         let param = Self::roll_skill_param(rand_holder, info);
 
         CharacterSkill::new(info, 0, param)
     }
 
-    fn roll_skill_param(
-        mut rand_holder: RwLockWriteGuard<RandomNumHolder>,
-        skill_info: SkillInfo,
-    ) -> SkillParam {
+    fn roll_skill_param(rand_holder: &mut RandomNumHolder, skill_info: SkillInfo) -> SkillParam {
         match skill_info {
             info @ SkillInfo::ElementalExplosion => {
                 let skill_param_element =
@@ -262,34 +272,34 @@ impl Attribute {
         }
     }
 
-    fn roll_passive(tier_lv: usize) -> PassiveName {
+    fn roll_passive(tier_lv: usize, rand_holder: &mut RandomNumHolder) -> PassiveName {
         let mut passive = PassiveName::default();
-        if roll_possess(ProbGroup::PASSIVE(tier_lv)) {
-            let mut rand_holder = RANDOM_NUM_HOLDER.write().expect(LOCK_POISONED);
+        if roll_possess(ProbGroup::PASSIVE(tier_lv), rand_holder) {
             passive = PassiveName::from(rand_holder.sample(..(PassiveName::COUNT) as u32));
         }
         passive
     }
 
-    fn roll_special_tile(tier_lv: usize, config: &GameplayConfigManager) -> SpecialTile {
+    fn roll_special_tile(
+        tier_lv: usize,
+        config: &GameplayConfigManager,
+        rand_holder: &mut RandomNumHolder,
+    ) -> SpecialTile {
         let mut special_tile = SpecialTile::new();
 
         // Roll first special tile
-        if roll_possess(ProbGroup::MONO_SPC_TILE(tier_lv)) {
+        if roll_possess(ProbGroup::MONO_SPC_TILE(tier_lv), rand_holder) {
             // Roll boost element and value
-            let mut rand_holder = RANDOM_NUM_HOLDER.write().expect(LOCK_POISONED);
             let element1 = rand_holder.sample(..(Element::COUNT - 1) as u32);
 
             let mono_sp_gem = config.get_tier_range(TieredType::MONO_SP_GEM);
             let elem1_boost_val =
                 rand_holder.sample(mono_sp_gem.tier_min[tier_lv]..=mono_sp_gem.tier_max[tier_lv]);
             special_tile.set_element1(Element::from(element1), elem1_boost_val);
-            drop(rand_holder);
 
             // Roll second special tile
-            if roll_possess(ProbGroup::DUAL_SPC_TILE(tier_lv)) {
+            if roll_possess(ProbGroup::DUAL_SPC_TILE(tier_lv), rand_holder) {
                 // Roll boost element and value
-                let mut rand_holder = RANDOM_NUM_HOLDER.write().expect(LOCK_POISONED);
                 let element2 = rand_holder.sample(..(Element::COUNT - 1) as u32);
 
                 let attr_config = config.get_char_attr_config();
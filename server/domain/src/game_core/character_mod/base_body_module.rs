@@ -88,9 +88,7 @@ pub struct BaseBodyModule {
 }
 
 impl BaseBodyModule {
-    pub fn roll_base_body_module() -> BaseBodyModule {
-        let mut rand_holder = RANDOM_NUM_HOLDER.write().expect(LOCK_POISONED);
-
+    pub fn roll_base_body_module(rand_holder: &mut RandomNumHolder) -> BaseBodyModule {
         // Roll race
         // ### MEMO: roll race first may cause not uniform probability of all type of body module
         let race = BaseRace::from(rand_holder.sample(..BaseRace::COUNT as u32));
@@ -183,15 +183,16 @@ pub fn run_simulator(tier_lv: usize, simulation_count: u32) -> String {
     let mut chosen_one = 0;
 
     let config = GameplayConfigManager::new();
+    let mut rand_holder = RANDOM_NUM_HOLDER.write().expect(LOCK_POISONED);
 
     // -- RUN SIMULATE --
     for _ in 0..simulation_count {
         // Create new character
-        let char_attr_test = Attribute::roll_attribute(tier_lv, &config);
+        let char_attr_test = Attribute::roll_attribute(tier_lv, &config, &mut rand_holder);
         let char_module_test = if tier_lv == EVEN_CHANCE_TIER_LV {
-            AccessoryModule::_debug_evenly_roll_accessory()
+            AccessoryModule::_debug_evenly_roll_accessory(&mut rand_holder)
         } else {
-            AccessoryModule::roll_accessory(&char_attr_test, &config)
+            AccessoryModule::roll_accessory(&char_attr_test, &config, &mut rand_holder)
         };
         let char_rarity = Attribute::get_char_rarity(&char_attr_test, &config);
 
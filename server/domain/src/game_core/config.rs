@@ -27,9 +27,21 @@ pub const RATE_UNIT: u32 = 1_000;
 //#TODO: These config should be fed from external config!
 //0.001ETH
 pub const STAKE: &str = "1000000000000000";
-//0.005ETH
-// pub const WITHDRAWAL_FEE: &str = "5000000000000000";
+//#NOTE: bounds of the stake a private room creator may choose, STAKE is used when none is given
+//0.0001ETH
+pub const MIN_STAKE: &str = "100000000000000";
+//1ETH
+pub const MAX_STAKE: &str = "1000000000000000000";
+//#NOTE: upper bound of operations carried by a single batched input
+pub const MAX_BATCH_SIZE: usize = 32;
+//#NOTE: platform fee taken from the pot of a settled staked match, in basis points, credited to ADMIN_WALLET_ADDRESS.
+//The default of GameplayConfigManager, the cartesi dapp overrides it with DAZZLE_PLATFORM_FEE_BPS
+pub const DEFAULT_PLATFORM_FEE_BPS: u32 = 250;
+pub const BPS_UNIT: u32 = 10_000;
 pub const ADMIN_WALLET_ADDRESS: &str = "0x2Af645839ea4ca82452aFd195e210420e7Cc1F90";
+//#NOTE: lifetime of an in-game wallet session key in seconds, when attached without expiry, and its upper bound
+pub const SESSION_DURATION: u64 = 7 * 24 * 60 * 60;
+pub const MAX_SESSION_DURATION: u64 = 30 * 24 * 60 * 60;
 
 pub const DEFAULT_INGAME_CURRENCY: u32 = 0;
 pub const CURRENCY_DECAY_RATE: f64 = 0.1;
@@ -78,6 +90,13 @@ pub struct GameplayConfigManager {
     // Runtime initialized fields, evaluated by config_info
     tier_boundary_config: TierBoundaryConfig,
     element_modifier: HashMap<Element, Vec<u32>>,
+
+    #[serde(default = "default_platform_fee_bps")]
+    platform_fee_bps: u32,
+}
+
+fn default_platform_fee_bps() -> u32 {
+    DEFAULT_PLATFORM_FEE_BPS
 }
 
 impl GameplayConfigManager {
@@ -90,6 +109,7 @@ impl GameplayConfigManager {
             config_info,
             tier_boundary_config,
             element_modifier,
+            platform_fee_bps: DEFAULT_PLATFORM_FEE_BPS,
         }
     }
 
@@ -227,6 +247,14 @@ impl GameplayConfigManager {
         self.config_info.char_game_init_status.cd_filled_rate = percent;
     }
 
+    pub fn get_platform_fee_bps(&self) -> u32 {
+        self.platform_fee_bps
+    }
+
+    pub fn set_platform_fee_bps(&mut self, bps: u32) {
+        self.platform_fee_bps = bps.min(BPS_UNIT);
+    }
+
     pub fn overwrite_enemy_config(&mut self, enemy_attr: &EnemyAttribute) {
         self.config_info.overwrite_enemy_config(enemy_attr);
         self.tier_boundary_config = TierBoundaryConfig::new(&self.config_info.char_attr_config)
@@ -1,9 +1,11 @@
 use super::{config::ClearPattern, lazy_static};
 //use atb::prelude::*;
-use serde::{Deserialize, Serialize};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
 use std::collections::HashMap;
 
 use crate::game_core::config::Element;
+use crate::game_core::full_state::FullState;
 
 lazy_static::lazy_static! {
     pub static ref EVENT_CONDITION_CONFIG: EventConditionConfig = serde_json::from_slice(include_bytes!("./config/event_condition.json")).expect("can't not parse EVENT_CONDITION_CONFIG config");
@@ -117,10 +119,27 @@ pub struct LazyInitEventInfo {
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct EventInfo {
     pub target_elem: Element,
-    #[serde(skip, default = "default_effect_type")]
+    #[serde(default = "default_effect_type", skip_serializing)]
     pub effect_type: EventEffectType,
 }
 
+impl Serialize for FullState<'_, EventInfo> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let EventInfo {
+            target_elem,
+            effect_type,
+        } = self.0;
+
+        let mut info = serializer.serialize_struct("EventInfo", 2)?;
+        info.serialize_field("target_elem", target_elem)?;
+        info.serialize_field("effect_type", effect_type)?;
+        info.end()
+    }
+}
+
 fn default_effect_type() -> EventEffectType {
     EventEffectType::Multiple
 }
@@ -131,6 +150,20 @@ pub struct GameEvent {
     pub info: EventInfo,
 }
 
+impl Serialize for FullState<'_, GameEvent> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let GameEvent { name, info } = self.0;
+
+        let mut event = serializer.serialize_struct("GameEvent", 2)?;
+        event.serialize_field("name", name)?;
+        event.serialize_field("info", &FullState(info))?;
+        event.end()
+    }
+}
+
 impl GameEvent {
     pub fn is_valid(&self, current_turn: u8, config_expired_turn: u8) -> bool {
         current_turn <= config_expired_turn
@@ -0,0 +1,53 @@
+use crate::game_core::game::Room;
+use atb_types::prelude::uuid::Uuid;
+use serde::{Serialize, Serializer};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Serializes the wrapped value with every field, including those its client view leaves out
+/// (the rng, character stats, dungeon details...), so it can be deserialized back as it was.
+///
+/// Implemented next to `Room` and each type inside it that hides fields, the others are written as they are
+pub struct FullState<'a, T>(pub &'a T);
+
+/// `serialize_with` of the rooms of `RoomManager`, which is only serialized for state snapshots
+pub fn serialize_rooms<S>(
+    rooms: &HashMap<Uuid, Arc<Room>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_map(
+        rooms
+            .iter()
+            .map(|(uuid, room)| (uuid, FullState(room.as_ref()))),
+    )
+}
+
+impl<T> Serialize for FullState<'_, Option<T>>
+where
+    for<'a> FullState<'a, T>: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self.0 {
+            Some(value) => serializer.serialize_some(&FullState(value)),
+            None => serializer.serialize_none(),
+        }
+    }
+}
+
+impl<T> Serialize for FullState<'_, Vec<T>>
+where
+    for<'a> FullState<'a, T>: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(self.0.iter().map(FullState))
+    }
+}
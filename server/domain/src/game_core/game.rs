@@ -4,22 +4,24 @@ use serde::ser::SerializeStruct;
 use serde::{Deserialize, Serialize, Serializer};
 
 use ethers_core::types::U256;
-use rand::{rngs::StdRng, Rng, SeedableRng};
+use rand::{Rng, SeedableRng};
 use strum::EnumCount;
 
 use crate::game_core::board::{
-    Board, BoardState, ClearValueDisplay, MoveAction, PlayerAction, SkillAction,
+    Board, BoardState, ClearValueDisplay, GameRng, MoveAction, PlayerAction, SkillAction,
 };
 use crate::game_core::character::{
     AttackDecision, CharacterLogicData, CharacterV2, Command, EnemyScriptMap, EnemyTemplate,
 };
 use crate::game_core::config::{
     Bead, ClearPattern, DamageResult, DamageSource, DungeonGamer, Element, GameplayConfigManager,
-    GetHitRecoveryType, BOARD_HEIGHT, BOARD_NUM_COLORS, BOARD_WIDTH, DEFAULT_ENEMY_SCRIPT_NAME,
-    DEFAULT_ENEMY_TEMPLATE_NAME, ENEMY_ADDR, MAX_PARTY_MEMBER, MAX_ZONE_RECORD_SIZE, RATE_UNIT,
+    GetHitRecoveryType, BOARD_HEIGHT, BOARD_NUM_COLORS, BOARD_WIDTH, BPS_UNIT,
+    DEFAULT_ENEMY_SCRIPT_NAME, DEFAULT_ENEMY_TEMPLATE_NAME, ENEMY_ADDR, MAX_PARTY_MEMBER,
+    MAX_ZONE_RECORD_SIZE, RATE_UNIT, STAKE,
 };
 use crate::game_core::event_module::{update_event, GameEvent, GamerMove};
-use crate::game_core::probability_mod::is_new_character_get;
+use crate::game_core::full_state::FullState;
+use crate::game_core::probability_mod::{is_new_character_get, RandomNumHolder};
 use crate::game_core::room_manager::GameMode;
 use crate::game_core::skill::{BuffInfo, SkillInfo};
 use crate::game_core::{DazzleError, GameError, ServerError};
@@ -144,6 +146,9 @@ pub struct GameOverResult {
     pub winner_id: Option<String>,
     pub forfeit_game: bool,
     pub winner_reward: String,
+    //#NOTE: taken from the pot before `winner_reward`, they add up to the stakes of the room
+    #[serde(default)]
+    pub platform_fee: String,
     pub acquire_new_character: bool,
     pub nft_reward_dispatched: bool,
 }
@@ -155,6 +160,7 @@ impl Default for GameOverResult {
             winner_id: Default::default(),
             forfeit_game: Default::default(),
             winner_reward: Default::default(),
+            platform_fee: Default::default(),
             acquire_new_character: Default::default(),
             nft_reward_dispatched: Default::default(),
         }
@@ -178,6 +184,16 @@ pub struct Room {
     pub game: Game,
     pub game_over_result: Option<GameOverResult>,
     pub opt_reward_character_uuid: Option<Uuid>,
+    //#NOTE: ERC-20 token address the stakes are paid in, None for Ether
+    #[serde(default)]
+    pub stake_token: Option<String>,
+    //#NOTE: amount every player stakes to enter the room
+    #[serde(default = "default_stake")]
+    pub stake: String,
+}
+
+fn default_stake() -> String {
+    STAKE.to_owned()
 }
 
 impl Serialize for Room {
@@ -185,9 +201,10 @@ impl Serialize for Room {
     where
         S: Serializer,
     {
-        let mut len = 6;
+        //#NOTE: the client view only shows the result of a finished game, `FullState` writes every field for snapshots
+        let mut len = 8;
         if self.is_finished() {
-            len = 7;
+            len = 9;
         }
 
         let mut room = serializer.serialize_struct("Room", len)?;
@@ -197,6 +214,8 @@ impl Serialize for Room {
         room.serialize_field("gamers", &self.gamers)?;
         room.serialize_field("start_with", &self.start_with)?;
         room.serialize_field("game", &self.game)?;
+        room.serialize_field("stake_token", &self.stake_token)?;
+        room.serialize_field("stake", &self.stake)?;
 
         if self.is_finished() {
             room.serialize_field("game_over_result", &self.game_over_result)?;
@@ -205,6 +224,42 @@ impl Serialize for Room {
     }
 }
 
+impl Serialize for FullState<'_, Room> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        //#NOTE: destructured, so a new field can't be left out of the snapshots
+        let Room {
+            uuid,
+            private_code,
+            game_mode,
+            opt_dungeon_details,
+            gamers,
+            start_with,
+            game,
+            game_over_result,
+            opt_reward_character_uuid,
+            stake_token,
+            stake,
+        } = self.0;
+
+        let mut room = serializer.serialize_struct("Room", 11)?;
+        room.serialize_field("uuid", uuid)?;
+        room.serialize_field("private_code", private_code)?;
+        room.serialize_field("game_mode", game_mode)?;
+        room.serialize_field("opt_dungeon_details", opt_dungeon_details)?;
+        room.serialize_field("gamers", gamers)?;
+        room.serialize_field("start_with", start_with)?;
+        room.serialize_field("game", &FullState(game))?;
+        room.serialize_field("game_over_result", game_over_result)?;
+        room.serialize_field("opt_reward_character_uuid", opt_reward_character_uuid)?;
+        room.serialize_field("stake_token", stake_token)?;
+        room.serialize_field("stake", stake)?;
+        room.end()
+    }
+}
+
 impl Room {
     pub fn new(
         private_code: Option<String>,
@@ -221,6 +276,8 @@ impl Room {
             game: Default::default(),
             game_over_result: Default::default(),
             opt_reward_character_uuid: Default::default(),
+            stake_token: None,
+            stake: default_stake(),
         }
     }
 
@@ -411,12 +468,22 @@ impl Room {
 
                     if !dungeon_details.is_next_stage_exist(stage_lv + 1) {
                         if let Some(winner) = self.check_game_winner(&next_game_state) {
-                            self.set_game_result(winner, &self.gamers[winner].id.clone(), false)?;
+                            self.set_game_result(
+                                winner,
+                                &self.gamers[winner].id.clone(),
+                                false,
+                                config.get_platform_fee_bps(),
+                            )?;
                         }
                     }
                 } else {
                     if let Some(winner) = self.check_game_winner(&next_game_state) {
-                        self.set_game_result(winner, &self.gamers[winner].id.clone(), false)?;
+                        self.set_game_result(
+                            winner,
+                            &self.gamers[winner].id.clone(),
+                            false,
+                            config.get_platform_fee_bps(),
+                        )?;
                     }
                 }
 
@@ -471,12 +538,22 @@ impl Room {
 
                     if !dungeon_details.is_next_stage_exist(stage_lv + 1) {
                         if let Some(winner) = self.check_game_winner(&next_game_state) {
-                            self.set_game_result(winner, &self.gamers[winner].id.clone(), false)?;
+                            self.set_game_result(
+                                winner,
+                                &self.gamers[winner].id.clone(),
+                                false,
+                                config.get_platform_fee_bps(),
+                            )?;
                         }
                     }
                 } else {
                     if let Some(winner) = self.check_game_winner(&next_game_state) {
-                        self.set_game_result(winner, &self.gamers[winner].id.clone(), false)?;
+                        self.set_game_result(
+                            winner,
+                            &self.gamers[winner].id.clone(),
+                            false,
+                            config.get_platform_fee_bps(),
+                        )?;
                     }
                 }
 
@@ -509,12 +586,14 @@ impl Room {
             return Ok(());
         }
 
+        //#NOTE: the enemy picks its commands and targets with the room rng, so the same seed replays the same turn
+        let mut rng = self.game.rng.clone();
         for attacker_id in &alive_enemy_list {
             match self.game.states.last() {
                 Some(state) => {
                     // TODO: Need a mechanism to assign script name. Currently using a hard-coded script.
                     let command = enemy_script_map
-                        .get_command(DEFAULT_ENEMY_SCRIPT_NAME, self.game.turn as usize)
+                        .get_command(DEFAULT_ENEMY_SCRIPT_NAME, self.game.turn as usize, &mut rng)
                         .unwrap_or_else(|e| {
                             let default_command = Command::default();
                             log::warn!("{}", e.to_string());
@@ -533,6 +612,7 @@ impl Room {
                         &state.gamer[player].characters,
                         attacker_element,
                         &command,
+                        &mut rng,
                     )? {
                         let mut game_state_manager = GameResourceManager::init(
                             state,
@@ -551,7 +631,12 @@ impl Room {
                         )?;
 
                         if let Some(winner) = self.check_game_winner(&next_game_state) {
-                            self.set_game_result(winner, &self.gamers[winner].id.clone(), false)?;
+                            self.set_game_result(
+                                winner,
+                                &self.gamers[winner].id.clone(),
+                                false,
+                                config.get_platform_fee_bps(),
+                            )?;
                         }
 
                         self.game.states.push(next_game_state);
@@ -576,7 +661,11 @@ impl Room {
             .map(|(index, _)| 1 - index)
     }
 
-    pub fn set_game_forfeit(&mut self, forfeit_player_id: &str) -> Result<(), ServerError> {
+    pub fn set_game_forfeit(
+        &mut self,
+        forfeit_player_id: &str,
+        config: &GameplayConfigManager,
+    ) -> Result<(), ServerError> {
         // Check game_over_result
         if self.game_over_result.is_some() {
             return Ok(());
@@ -584,8 +673,13 @@ impl Room {
 
         for (i, gamer) in self.gamers.iter().enumerate() {
             if forfeit_player_id == gamer.id {
-                self.set_game_result(1 - i, &self.gamers[1 - i].id.clone(), true)
-                    .map_err(|_| ServerError::UserNotFound)?;
+                self.set_game_result(
+                    1 - i,
+                    &self.gamers[1 - i].id.clone(),
+                    true,
+                    config.get_platform_fee_bps(),
+                )
+                .map_err(|_| ServerError::UserNotFound)?;
                 return Ok(());
             }
         }
@@ -636,6 +730,7 @@ impl Room {
         winner: usize,
         winner_id: &str,
         forfeit_game: bool,
+        platform_fee_bps: u32,
     ) -> Result<(), GameError> {
         if self.gamers.iter().find(|c| c.id == winner_id).is_none() {
             return Err(GameError::UserNotFound);
@@ -645,13 +740,25 @@ impl Room {
         let reward_stake = self.gamers.iter().fold(U256::zero(), |acc, x| {
             acc.saturating_add(U256::from_dec_str(&x.stake).unwrap())
         });
+        // Only a staked match pays the platform fee
+        let platform_fee = match reward_stake.is_zero() {
+            true => U256::zero(),
+            false => {
+                reward_stake.saturating_mul(U256::from(platform_fee_bps)) / U256::from(BPS_UNIT)
+            }
+        };
 
         self.game_over_result = Some(GameOverResult {
             winner: winner as i8,
             winner_id: Some(winner_id.to_owned()),
             forfeit_game,
-            winner_reward: reward_stake.to_string(),
-            acquire_new_character: self.is_mode_dispatch_nft() && is_new_character_get(winner_id),
+            winner_reward: (reward_stake - platform_fee).to_string(),
+            platform_fee: platform_fee.to_string(),
+            acquire_new_character: self.is_mode_dispatch_nft()
+                && is_new_character_get(
+                    winner_id,
+                    &mut RandomNumHolder::seeded(self.game.rng.clone().gen()),
+                ),
             nft_reward_dispatched: false,
         });
 
@@ -788,6 +895,7 @@ impl Room {
         character_data_list: &'a [CharacterLogicData],
         attacker_element: Element,
         command: &Command,
+        rng: &mut GameRng,
     ) -> Result<Option<&Uuid>, GameError> {
         if !command.is_attack_action() {
             return Ok(None);
@@ -810,7 +918,7 @@ impl Room {
 
         let defender_id = match command.attack_decision {
             AttackDecision::Random => {
-                let random_pick = rand::thread_rng().gen_range(0..candidate_list.len());
+                let random_pick = rng.gen_range(0..candidate_list.len());
                 &candidate_list[random_pick].id
             }
             AttackDecision::LowestHp => candidate_list
@@ -841,10 +949,10 @@ impl Room {
 
                 if filtered_id_list.is_empty() {
                     // No good target, random pick
-                    let random_pick = rand::thread_rng().gen_range(0..candidate_list.len());
+                    let random_pick = rng.gen_range(0..candidate_list.len());
                     &candidate_list[random_pick].id
                 } else {
-                    let random_pick = rand::thread_rng().gen_range(0..filtered_id_list.len());
+                    let random_pick = rng.gen_range(0..filtered_id_list.len());
                     filtered_id_list[random_pick]
                 }
             }
@@ -888,7 +996,7 @@ impl Room {
             total_states_count,
             states: games,
             //seed: 0i64,
-            rng: StdRng::seed_from_u64(0),
+            rng: GameRng::seed_from_u64(0),
         };
 
         let snapshot_room = Room {
@@ -901,6 +1009,8 @@ impl Room {
             game: snapshot_game,
             game_over_result: self.game_over_result.clone(),
             opt_reward_character_uuid: None,
+            stake_token: self.stake_token.clone(),
+            stake: self.stake.clone(),
         };
 
         snapshot_room
@@ -937,12 +1047,35 @@ pub struct Game {
     pub total_states_count: usize,
     pub states: Vec<GameState>,
 
-    #[serde(skip, default = "default_rng")]
-    rng: StdRng,
+    #[serde(default = "default_rng", skip_serializing)]
+    rng: GameRng,
 }
 
-fn default_rng() -> StdRng {
-    StdRng::seed_from_u64(0)
+impl Serialize for FullState<'_, Game> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let Game {
+            current_active_player_idx,
+            turn,
+            total_states_count,
+            states,
+            rng,
+        } = self.0;
+
+        let mut game = serializer.serialize_struct("Game", 5)?;
+        game.serialize_field("currentActivePlayerIdx", current_active_player_idx)?;
+        game.serialize_field("turn", turn)?;
+        game.serialize_field("totalStatesCount", total_states_count)?;
+        game.serialize_field("states", &FullState(states))?;
+        game.serialize_field("rng", rng)?;
+        game.end()
+    }
+}
+
+fn default_rng() -> GameRng {
+    GameRng::seed_from_u64(0)
 }
 
 impl Default for Game {
@@ -952,7 +1085,7 @@ impl Default for Game {
             states: vec![],
             current_active_player_idx: 0,
             turn: 0,
-            rng: StdRng::seed_from_u64(0),
+            rng: GameRng::seed_from_u64(0),
         }
     }
 }
@@ -967,7 +1100,7 @@ impl Game {
     ) -> Self {
         let time = Utc::now();
         let rng_seed = seed.unwrap_or_else(|| time.timestamp() as u64);
-        let mut rng = StdRng::seed_from_u64(rng_seed);
+        let mut rng = GameRng::seed_from_u64(rng_seed);
 
         let start_with = 0; // Should be random pick in the future
 
@@ -1022,7 +1155,7 @@ impl Game {
     }
 
     pub fn update_rng(&mut self, seed: u64) {
-        self.rng = StdRng::seed_from_u64(seed as u64);
+        self.rng = GameRng::seed_from_u64(seed as u64);
     }
 
     pub fn switch_player(&mut self) {
@@ -1041,7 +1174,7 @@ pub struct GameResourceManager<'a> {
     attacker_id: &'a Uuid,         // "attacker" in move, "caster" in skill
     defender_id: Option<&'a Uuid>, // "defender" in move, "target" in skill
     config: &'a GameplayConfigManager,
-    rng: StdRng, // TODO: May replace to RandomHalder in the future
+    rng: GameRng, // TODO: May replace to RandomHalder in the future
 }
 
 impl<'a> GameResourceManager<'a> {
@@ -1052,7 +1185,7 @@ impl<'a> GameResourceManager<'a> {
         attacker_id: &'a Uuid,
         defender_id: Option<&'a Uuid>,
         config: &'a GameplayConfigManager,
-        rng: StdRng,
+        rng: GameRng,
     ) -> Self {
         Self {
             next_state: state.clone(),
@@ -1399,11 +1532,11 @@ impl<'a> GameResourceManager<'a> {
                         .contains(&caster_char.element.get_disadvantage_element()?)
                 {
                     // If skill is triggered by NPC and there is no available target, temporary using a random value to handle it
+                    let mut rng = self.rng.clone();
                     let mut picked_elem;
                     loop {
                         picked_elem = Element::from(
-                            rand::thread_rng()
-                                .gen_range(Element::Fire as u32..=Element::Shadow as u32),
+                            rng.gen_range(Element::Fire as u32..=Element::Shadow as u32),
                         );
 
                         if available_elements.contains(&picked_elem)
@@ -1463,7 +1596,9 @@ impl<'a> GameResourceManager<'a> {
                 let target_element = if is_npc_action {
                     // Temporary using a random value
                     Element::from(
-                        rand::thread_rng().gen_range(Element::Fire as u32..=Element::Shadow as u32),
+                        self.rng
+                            .clone()
+                            .gen_range(Element::Fire as u32..=Element::Shadow as u32),
                     )
                 } else {
                     caster_char
@@ -1486,13 +1621,14 @@ impl<'a> GameResourceManager<'a> {
             SkillInfo::LineEliminate => {
                 let (line_num, clear_pattern) = if is_npc_action {
                     // Temporary using random values
-                    let clear_pattern = ClearPattern::from(rand::thread_rng().gen_range(1..=2));
+                    let mut rng = self.rng.clone();
+                    let clear_pattern = ClearPattern::from(rng.gen_range(1..=2));
                     let max_value = match clear_pattern {
                         ClearPattern::Horizontal => BOARD_HEIGHT,
                         ClearPattern::Vertical => BOARD_WIDTH,
                         _ => unreachable!(),
                     };
-                    let line_num = rand::thread_rng().gen_range(0..max_value);
+                    let line_num = rng.gen_range(0..max_value);
                     (line_num, clear_pattern)
                 } else {
                     let line_num = caster_char.get_skill_param_value();
@@ -1795,9 +1931,44 @@ pub struct GameState {
     pub gamer: Vec<GamerState>,
 }
 
+impl Serialize for FullState<'_, GameState> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let GameState {
+            board,
+            turn,
+            game_event,
+            mover,
+            player_action,
+            attacker_id,
+            defender_id,
+            board_states,
+            damage_result,
+            opt_dungeon_state,
+            gamer,
+        } = self.0;
+
+        let mut state = serializer.serialize_struct("GameState", 11)?;
+        state.serialize_field("board", board)?;
+        state.serialize_field("turn", turn)?;
+        state.serialize_field("game_event", &FullState(game_event))?;
+        state.serialize_field("mover", mover)?;
+        state.serialize_field("player_action", player_action)?;
+        state.serialize_field("attacker_id", attacker_id)?;
+        state.serialize_field("defender_id", defender_id)?;
+        state.serialize_field("board_states", board_states)?;
+        state.serialize_field("damage_result", damage_result)?;
+        state.serialize_field("opt_dungeon_state", opt_dungeon_state)?;
+        state.serialize_field("gamer", &FullState(gamer))?;
+        state.end()
+    }
+}
+
 impl GameState {
     pub fn init(
-        rng: &mut StdRng,
+        rng: &mut GameRng,
         num_colors: u32,
         width: u32,
         height: u32,
@@ -1888,6 +2059,25 @@ pub struct GamerState {
     move_buffer: Vec<GamerMove>,
 }
 
+impl Serialize for FullState<'_, GamerState> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let GamerState {
+            player,
+            characters,
+            move_buffer,
+        } = self.0;
+
+        let mut gamer = serializer.serialize_struct("GamerState", 3)?;
+        gamer.serialize_field("player", player)?;
+        gamer.serialize_field("characters", &FullState(characters))?;
+        gamer.serialize_field("move_buffer", move_buffer)?;
+        gamer.end()
+    }
+}
+
 impl GamerState {
     pub fn new(
         player: &str,
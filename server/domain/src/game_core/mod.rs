@@ -3,6 +3,7 @@ pub mod character;
 pub mod character_mod;
 pub mod config;
 pub mod event_module;
+pub mod full_state;
 pub mod game;
 pub mod probability_mod;
 pub mod reward;
@@ -35,6 +36,27 @@ pub enum ServerError {
     #[error("Invalid request")]
     InvalidRequest,
 
+    #[error("Unsupported request version: {0}")]
+    UnsupportedRequestVersion(u32),
+
+    #[error("Unsupported snapshot version: {0}")]
+    UnsupportedSnapshotVersion(u32),
+
+    #[error("Failed to access snapshot {0}: {1}")]
+    SnapshotFile(String, String),
+
+    #[error("Invalid snapshot: {0}")]
+    InvalidSnapshot(String),
+
+    #[error("Advance input {1} was received but the state is waiting for input {0}")]
+    MissingInputs(u64, u64),
+
+    #[error("Invalid batch: {0}")]
+    InvalidBatch(String),
+
+    #[error("Invalid inspect query: {0}")]
+    InvalidInspectQuery(String),
+
     #[error("Invalid file path")]
     InvalidFilePath,
 
@@ -56,9 +78,51 @@ pub enum ServerError {
     #[error("Insufficient tournament fee - balance: {0}, stake: {1}")]
     InsufficientTournamentFee(String, String),
 
+    #[error("Stake {0} is out of the allowed range [{1}, {2}]")]
+    InvalidStake(String, String, String),
+
+    #[error("No pending voucher #{1} of input {0}")]
+    VoucherNotFound(u64, u64),
+
+    #[error("Accounting of token {0} is broken: {1} deposited, {2} accounted for")]
+    AccountingViolation(String, String, String),
+
+    #[error("Rollup server gave the {0} index {2} instead of {1}")]
+    OutputIndexMismatch(String, u64, u64),
+
     #[error("Invalid ingame-wallet: {0}")]
     InvalidIngameWallet(String),
 
+    #[error("{0} doesn't own the funds of {1}")]
+    NotFundsOwner(String, String),
+
+    #[error("{0} can't act as user {1}")]
+    UserMismatch(String, String),
+
+    #[error("In-game wallet {0} is already attached")]
+    IngameWalletAlreadyAttached(String),
+
+    #[error("Nonce {0} was already used")]
+    NonceAlreadyUsed(u64),
+
+    #[error("{0} already has a session key, rotate it instead")]
+    SessionKeyExists(String),
+
+    #[error("No session key {0}")]
+    SessionKeyNotFound(String),
+
+    #[error("Session key {0} is playing in a room")]
+    SessionKeyInRoom(String),
+
+    #[error("Session expiry {0} is in the past or too far ahead")]
+    InvalidSessionExpiry(u64),
+
+    #[error("Session key {0} isn't allowed to stake")]
+    SessionScopeDenied(String),
+
+    #[error("Relayed action was signed for turn {0}, the room is at turn {1}")]
+    StaleRelayedAction(u64, u64),
+
     #[error("Failed to build http request")]
     FailedToBuildRequest,
 
@@ -71,9 +135,18 @@ pub enum ServerError {
     #[error("Failed to send report")]
     FailedToSendReport,
 
+    #[error("Incomplete report chunks: got {0} of {1}")]
+    IncompleteReportChunks(usize, usize),
+
+    #[error("Failed to send exception")]
+    FailedToSendException,
+
     #[error("Invalid abi-encoded data")]
     InvalidABIData,
 
+    #[error("Invalid {0} portal payload: {1}")]
+    InvalidPortalPayload(String, String),
+
     #[error("Graphql query failed")]
     GraphqlQueryFailed,
 
@@ -113,6 +186,18 @@ pub enum ServerError {
     #[error("Request for a character: {0} that is not owned by user on chain")]
     RequestNotOwnedNFT(String),
 
+    #[error("NFT {0} carries no character stats")]
+    NftWithoutCharacter(String),
+
+    #[error("NFT {0} can't be withdrawn while its owner is in a room")]
+    NftInRoom(String),
+
+    #[error("NFT collection {0} holds no characters")]
+    UnknownNftCollection(String),
+
+    #[error("Rooms are entered with deposited NFT characters only")]
+    RawCharacterListDenied,
+
     #[error("Failed to query current block number")]
     FailedToFetchBlockNumber,
 
@@ -1,8 +1,10 @@
 use super::lazy_static;
 use rand::distributions::{Distribution, Uniform};
+use rand::{Rng, SeedableRng};
 use std::ops::{Bound, RangeBounds};
 use std::sync::{Arc, RwLock};
 
+use crate::game_core::board::GameRng;
 use crate::game_core::config::TUTORIAL_RIVAL_ADDR;
 
 pub const LOCK_POISONED: &str = "Lock is poisoned";
@@ -76,12 +78,13 @@ pub const P_ATTRIBUTE_WEIGHT_RANGE: u32 = 100;
 pub const P_ACQUIRE_NEW_CHARACTER_TH: u32 = 100;
 pub const P_ACQUIRE_NEW_CHARACTER_WEIGHT_RANGE: u32 = 100;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 pub struct RandomNumHolder {
     pub bitmask_rand_pool: u128,
     pub valid_bit: u32,
     pub bit_consumed: u32,  // for debug
     pub rand_consumed: u32, // for debug
+    rng: GameRng,
 }
 
 lazy_static::lazy_static! {
@@ -89,18 +92,30 @@ lazy_static::lazy_static! {
 }
 
 impl RandomNumHolder {
+    /// Holder seeded from the thread rng, only for characters rolled outside a room
     pub fn new(consumed: u32) -> RandomNumHolder {
-        let mut rng = rand::thread_rng();
+        Self::from_rng(GameRng::seed_from_u64(rand::thread_rng().gen()), consumed)
+    }
+
+    /// Holder whose rolls are fully determined by `seed`
+    pub fn seeded(seed: u64) -> RandomNumHolder {
+        Self::from_rng(GameRng::seed_from_u64(seed), 0)
+    }
+
+    fn from_rng(mut rng: GameRng, consumed: u32) -> RandomNumHolder {
         Self {
             bitmask_rand_pool: Uniform::new(0, std::u128::MAX).sample(&mut rng),
             valid_bit: u128::BITS,
             bit_consumed: 0,
             rand_consumed: consumed,
+            rng,
         }
     }
 
     fn generate_new_rand_pool(&mut self) {
-        *self = RandomNumHolder::new(self.rand_consumed);
+        self.bitmask_rand_pool = Uniform::new(0, std::u128::MAX).sample(&mut self.rng);
+        self.valid_bit = u128::BITS;
+        self.bit_consumed = 0;
         self.rand_consumed += 1;
     }
 
@@ -131,14 +146,13 @@ impl RandomNumHolder {
 }
 
 /// Decide how many items can be acquired
-pub fn roll_possess_amount(p_group: ProbGroup) -> usize {
+pub fn roll_possess_amount(p_group: ProbGroup, rand_holder: &mut RandomNumHolder) -> usize {
     let (threshold_list, weight_range) = match p_group {
         ProbGroup::HP_head_face_neck => (P_HP_ACC_TH_LIST, P_HP_ACC_WEIGHT_RANGE),
         ProbGroup::DEF_body_waist => (P_DEF_ACC_TH_LIST, P_DEF_ACC_WEIGHT_RANGE),
         _ => unreachable!(),
     };
 
-    let mut rand_holder = RANDOM_NUM_HOLDER.write().expect(LOCK_POISONED);
     let rand = rand_holder.sample(..weight_range);
     let mut acquired_amount = 0;
     for acquire_threshold in threshold_list {
@@ -150,7 +164,7 @@ pub fn roll_possess_amount(p_group: ProbGroup) -> usize {
 }
 
 /// Decide whether a single item can be acquired
-pub fn roll_possess(p_group: ProbGroup) -> bool {
+pub fn roll_possess(p_group: ProbGroup, rand_holder: &mut RandomNumHolder) -> bool {
     let (acquire_threshold, weight_range) = match p_group {
         ProbGroup::DEF_arm => (P_DEF_ACC_GET_ARM_TH, P_DEF_ACC_WEIGHT_RANGE),
         ProbGroup::DEF_foot => (P_DEF_ACC_GET_FOOT_TH, P_DEF_ACC_WEIGHT_RANGE),
@@ -183,16 +197,15 @@ pub fn roll_possess(p_group: ProbGroup) -> bool {
         }
         _ => unreachable!(),
     };
-    let mut rand_holder = RANDOM_NUM_HOLDER.write().expect(LOCK_POISONED);
     let rand = rand_holder.sample(..weight_range);
     rand < acquire_threshold
 }
 
-pub fn is_new_character_get(winner_id: &str) -> bool {
+pub fn is_new_character_get(winner_id: &str, rand_holder: &mut RandomNumHolder) -> bool {
     if winner_id == TUTORIAL_RIVAL_ADDR {
         return false;
     }
     // ### TODO: No rule for now, using a fixed probability.
     // Related issue: #465
-    roll_possess(ProbGroup::ACQUIRED_NEW_CHAR)
+    roll_possess(ProbGroup::ACQUIRED_NEW_CHAR, rand_holder)
 }
@@ -11,7 +11,7 @@ pub struct Reward {
     pub acquire_new_character: bool,
 }
 
-#[derive(Debug, Serialize, Clone, Default)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct RewardCache {
     pub reward_types: Vec<RewardType>,
     pub character_rewards: Vec<CharacterReward>,
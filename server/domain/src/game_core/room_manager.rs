@@ -3,17 +3,20 @@ use crate::game_core::character::{CharacterV2, EnemyScriptMap};
 #[cfg(feature = "debug_tool")]
 use crate::game_core::config::TEST_BOARD_PATH;
 use crate::game_core::config::{
-    DungeonGamer, GameplayConfigManager, ENEMY_ADDR, PRIVATE_CODE_LENGTH, STAKE,
+    DungeonGamer, GameplayConfigManager, ENEMY_ADDR, PRIVATE_CODE_LENGTH,
 };
+use crate::game_core::full_state::serialize_rooms;
 use crate::game_core::game::{DungeonDetails, GameResult, Gamer, Room};
 use crate::game_core::skill::SkillInfo;
 use crate::game_core::{DazzleError, ServerError};
 
 use atb::prelude::*;
-use atb_types::prelude::uuid::Uuid;
+use atb_types::prelude::uuid::{Builder as UuidBuilder, Uuid, Variant, Version};
 use rand::distributions::{Distribution, Uniform};
+use rand::{rngs::StdRng, Rng, RngCore, SeedableRng};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
 use strum_macros::EnumString;
 
 use super::reward::RewardCache;
@@ -162,22 +165,25 @@ pub struct RoomManagerState {
     pub room_data: HashMap<Uuid, Room>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+//#NOTE: only serialized for state snapshots, so its rooms keep every field
+//#NOTE: rooms are replaced instead of mutated in place, so a clone shares them and doesn't copy their game history
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct RoomManager {
-    room_map: HashMap<Uuid, Room>,                    // room uuid -> Room
-    config_map: HashMap<Uuid, GameplayConfigManager>, // room uuid -> config
-    enemy_script_map: HashMap<Uuid, EnemyScriptMap>,  // room uuid -> enemy script
-    player_map: HashMap<String, Uuid>,                // player name -> room uuid
-    private_map: HashMap<String, Uuid>,               // private code -> room uuid
-    reward_cache: HashMap<String, RewardCache>,       // player name -> reward cache
+    #[serde(serialize_with = "serialize_rooms")]
+    room_map: HashMap<Uuid, Arc<Room>>, // room uuid -> Room
+    config_map: HashMap<Uuid, Arc<GameplayConfigManager>>, // room uuid -> config
+    enemy_script_map: HashMap<Uuid, Arc<EnemyScriptMap>>,  // room uuid -> enemy script
+    player_map: HashMap<String, Uuid>,                     // player name -> room uuid
+    private_map: HashMap<String, Uuid>,                    // private code -> room uuid
+    reward_cache: HashMap<String, RewardCache>,            // player name -> reward cache
 }
 
 impl RoomManager {
     pub fn new() -> Self {
         RoomManager {
-            room_map: HashMap::<Uuid, Room>::new(),
-            config_map: HashMap::<Uuid, GameplayConfigManager>::new(),
-            enemy_script_map: HashMap::<Uuid, EnemyScriptMap>::new(),
+            room_map: HashMap::<Uuid, Arc<Room>>::new(),
+            config_map: HashMap::<Uuid, Arc<GameplayConfigManager>>::new(),
+            enemy_script_map: HashMap::<Uuid, Arc<EnemyScriptMap>>::new(),
             player_map: HashMap::<String, Uuid>::new(),
             private_map: HashMap::<String, Uuid>::new(),
             reward_cache: HashMap::<String, RewardCache>::new(),
@@ -239,8 +245,12 @@ impl RoomManager {
         }
     }
 
+    pub fn iter_rooms(&self) -> impl Iterator<Item = &Room> {
+        self.room_map.values().map(Arc::as_ref)
+    }
+
     pub fn get_room(&self, uuid: &Uuid) -> Option<&Room> {
-        self.room_map.get(uuid)
+        self.room_map.get(uuid).map(Arc::as_ref)
     }
 
     pub fn get_uuid_by_player(&self, player: &str) -> Option<&Uuid> {
@@ -517,7 +527,7 @@ impl RoomManager {
             // YES, join
             Some((uuid, r)) => {
                 log::debug!("    Join room, Init game");
-                let mut room = r.clone();
+                let mut room = Room::clone(r);
                 room.set_player(player, &party_characters, "0", &config, seed, None, None);
 
                 // Postgres DB update should be done here.
@@ -566,6 +576,8 @@ impl RoomManager {
         config_manager: Option<&GameplayConfigManager>,
         game_mode: GameMode,
         seed: Option<u64>,
+        stake_token: Option<String>,
+        stake: &str,
     ) -> Result<RoomStatus, ServerError> {
         if character_list.is_empty() {
             return Err(ServerError::InvalidRequest);
@@ -575,9 +587,15 @@ impl RoomManager {
             .cloned()
             .unwrap_or_else(GameplayConfigManager::new);
 
-        let private_code = self.gen_unique_random_id(&self.private_map, PRIVATE_CODE_LENGTH);
+        // With a seed (e.g. derived from the rollup input), the room id and private code are reproducible as well
+        let mut rng = StdRng::seed_from_u64(seed.unwrap_or_else(|| rand::thread_rng().gen()));
+        let private_code =
+            self.gen_unique_random_id(&self.private_map, PRIVATE_CODE_LENGTH, &mut rng);
         let mut new_room = Room::new(Some(private_code.clone()), game_mode, None);
-        new_room.set_player(player, &character_list, STAKE, &config, seed, None, None);
+        new_room.uuid = self.gen_room_uuid(&mut rng);
+        new_room.stake_token = stake_token;
+        new_room.stake = stake.to_owned();
+        new_room.set_player(player, &character_list, stake, &config, seed, None, None);
 
         let room_status = RoomStatus {
             room_id: new_room.uuid,
@@ -629,7 +647,8 @@ impl RoomManager {
             .unwrap_or_else(GameplayConfigManager::new);
 
         log::debug!("    Join private room, Init game");
-        room.set_player(&player, &character_list, STAKE, &config, seed, None, None);
+        let stake = room.stake.clone();
+        room.set_player(&player, &character_list, &stake, &config, seed, None, None);
         let room_status = RoomStatus {
             room_id: *uuid,
             private_code: private_code.clone(),
@@ -674,7 +693,7 @@ impl RoomManager {
             private_code: private_code.clone(),
             match_result: MatchResult::Playing,
         };
-        self.room_map.insert(uuid, room);
+        self.room_map.insert(uuid, Arc::new(room));
         Ok(room_status)
     }
 
@@ -861,7 +880,7 @@ impl RoomManager {
         config_manager: Option<&GameplayConfigManager>,
         enemy_script_map: Option<&EnemyScriptMap>,
     ) {
-        self.room_map.insert(uuid, room);
+        self.room_map.insert(uuid, Arc::new(room));
         self.player_map.insert(player.to_owned(), uuid);
 
         if let Some(code) = private_code {
@@ -874,12 +893,13 @@ impl RoomManager {
             let config = config_manager
                 .cloned()
                 .unwrap_or_else(GameplayConfigManager::new);
-            self.config_map.insert(uuid, config);
+            self.config_map.insert(uuid, Arc::new(config));
         }
 
         //###TODO: Will be remove and merge to enemy_template
         if let Some(script_map) = enemy_script_map {
-            self.enemy_script_map.insert(uuid, script_map.clone());
+            self.enemy_script_map
+                .insert(uuid, Arc::new(script_map.clone()));
         }
     }
 
@@ -950,7 +970,7 @@ impl RoomManager {
 
     // For update room data during gameplay (move, active skill)
     pub fn update_room(&mut self, uuid: &Uuid, room: &Room) {
-        self.room_map.insert(uuid.clone(), room.clone());
+        self.room_map.insert(uuid.clone(), Arc::new(room.clone()));
     }
 
     pub fn move_action(
@@ -1045,8 +1065,12 @@ impl RoomManager {
                 .ok_or(ServerError::RoomNotFound)?
                 .clone()
         };
+        let config = self
+            .config_map
+            .get(&room.uuid)
+            .ok_or(ServerError::ConfigNotFound)?;
 
-        room.set_game_forfeit(player)?;
+        room.set_game_forfeit(player, config)?;
 
         self.update_room(&room.uuid, &room);
 
@@ -1072,29 +1096,48 @@ impl RoomManager {
         }
     }
 
-    fn gen_unique_random_id(&self, private_map: &HashMap<String, Uuid>, length: usize) -> String {
+    fn gen_unique_random_id(
+        &self,
+        private_map: &HashMap<String, Uuid>,
+        length: usize,
+        rng: &mut impl RngCore,
+    ) -> String {
         let char_white_list: Vec<char> = "23456789ABCDEFGHJKMNPQRSTUVWXYZ"
             .to_string()
             .chars()
             .collect();
-        let mut private_code = self.generate_code(length, &char_white_list);
+        let mut private_code = self.generate_code(length, &char_white_list, rng);
         // Check duplicated
         while let Some(_) = private_map.get(&private_code) {
-            private_code = self.generate_code(length, &char_white_list);
+            private_code = self.generate_code(length, &char_white_list, rng);
         }
         private_code
     }
 
-    fn generate_code(&self, length: usize, charset: &[char]) -> String {
+    fn generate_code(&self, length: usize, charset: &[char], rng: &mut impl RngCore) -> String {
         let mut code = String::new();
-        let mut rng = rand::thread_rng();
         let die = Uniform::from(0..charset.len());
         for _ in 0..length {
-            code.push(charset[die.sample(&mut rng)]);
+            code.push(charset[die.sample(rng)]);
         }
         code
     }
 
+    fn gen_room_uuid(&self, rng: &mut impl RngCore) -> Uuid {
+        loop {
+            let mut bytes = [0u8; 16];
+            rng.fill_bytes(&mut bytes);
+            let uuid = UuidBuilder::from_bytes(bytes)
+                .set_variant(Variant::RFC4122)
+                .set_version(Version::Random)
+                .build();
+
+            if !self.room_map.contains_key(&uuid) {
+                return uuid;
+            }
+        }
+    }
+
     pub fn get_current_state(&self) -> RoomManagerState {
         let user_to_room = self
             .player_map
@@ -1149,10 +1192,16 @@ impl RoomManager {
 
         let mut new_room = room.clone();
 
-        new_room.set_game_result(0, player, false).map_err(|e| {
-            log::error!("    InternalServerError: \"{}\"", e.to_string());
-            ServerError::InvalidRequest
-        })?;
+        let config = self
+            .config_map
+            .get(&room_uuid)
+            .ok_or(ServerError::ConfigNotFound)?;
+        new_room
+            .set_game_result(0, player, false, config.get_platform_fee_bps())
+            .map_err(|e| {
+                log::error!("    InternalServerError: \"{}\"", e.to_string());
+                ServerError::InvalidRequest
+            })?;
 
         self.update_room(&room_uuid, &new_room);
         Ok(())